@@ -0,0 +1,13 @@
+use proc_macro::TokenStream;
+
+// On the host a circuit module is plain Rust
+#[proc_macro_attribute]
+pub fn encrypted(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+
+// Instructions are ordinary functions over their plaintext inputs
+#[proc_macro_attribute]
+pub fn instruction(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
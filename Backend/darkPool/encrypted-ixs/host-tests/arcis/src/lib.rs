@@ -0,0 +1,60 @@
+// Plaintext stand-in for the parts of the arcis API the circuits use. Values
+// are never encrypted: Enc just carries the value and its owner, and reveal()
+// is the identity, so circuit logic can be exercised with ordinary asserts
+pub use arcis_host_macros::{encrypted, instruction};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArcisX25519Pubkey([u8; 32]);
+
+impl ArcisX25519Pubkey {
+    pub fn from_uint8(bytes: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(bytes);
+        ArcisX25519Pubkey(key)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mxe;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Shared {
+    pub key: ArcisX25519Pubkey,
+}
+
+impl Shared {
+    pub fn new(key: ArcisX25519Pubkey) -> Self {
+        Shared { key }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Enc<O, T> {
+    pub owner: O,
+    pub value: T,
+}
+
+impl<O, T: Copy> Enc<O, T> {
+    pub fn to_arcis(&self) -> T {
+        self.value
+    }
+}
+
+pub trait Owner: Copy + Sized {
+    // Named after the arcis method, which encrypts to `self`
+    #[allow(clippy::wrong_self_convention)]
+    fn from_arcis<T>(&self, value: T) -> Enc<Self, T> {
+        Enc { owner: *self, value }
+    }
+}
+
+impl Owner for Mxe {}
+impl Owner for Shared {}
+
+pub trait Reveal: Sized {
+    fn reveal(self) -> Self {
+        self
+    }
+}
+
+impl<T: Copy> Reveal for T {}
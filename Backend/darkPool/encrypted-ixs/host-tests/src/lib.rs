@@ -0,0 +1,5 @@
+// The circuit source compiled as ordinary Rust against the arcis stand-in in
+// ../arcis, so the #[cfg(test)] module inside it runs under `cargo test`
+#[allow(dead_code, unused, clippy::all)]
+#[path = "../../match_orders.rs"]
+mod match_orders;
@@ -5,6 +5,33 @@ mod circuits {
     use arcis::*;
 
     const MAX_ORDERS: usize = 100;
+    const MAX_BATCH_FILLS: usize = 8;
+    const MAX_CRANK_FILLS: usize = 8; // match rounds per crank_match
+    const MAX_CANCEL_IDS: usize = 8;
+    const MAX_NET_FILLS: usize = 16; // two legs per batch fill
+    const MAX_ALLOWLIST: usize = 16;
+    const DEPTH_BUCKETS: usize = 20;
+    const MAX_DEPTH_RANGE: usize = 8;
+    const CHANGE_LOG_LEN: usize = 8;
+    const MAX_HEARTBEAT_USERS: usize = 16;
+    const MAX_TRACKED_USERS: usize = 16;
+    const AGE_BUCKETS: usize = 4;
+    const TRADE_LOG_LEN: usize = 16;
+
+    // Order.flags bits; unassigned bits are reserved and ignored
+    const FLAG_POST_ONLY: u64 = 1; // rejected by add_order if it would cross on entry
+    const FLAG_ALL_OR_NONE: u64 = 2; // only fills for its full remaining amount
+    const FLAG_AUCTION_ONLY: u64 = 4; // only fills when crank_match clears a matching window
+
+    // MatchConfig.pair_rules bits, see can_cross for the full matrix
+    const PAIR_RULE_NO_MARKET_PAIRS: u8 = 1; // market x market never crosses
+    const PAIR_RULE_ORACLE_MARKET_LIMIT: u8 = 2; // market x limit trades at the oracle when the limit allows it
+    const MAX_USER_FILLS: usize = 8;
+    const LADDER_LEVELS: usize = 10;
+    const MAX_MODIFIED_ORDERS: usize = 8;
+    const VOLUME_BUCKETS: usize = 24; // hourly buckets for the rolling 24h volume
+    const BOOK_PAGE: usize = 10;
+    const MAX_IOC_FILLS: usize = 8; // resting orders one IOC order can take from
 
     #[derive(Copy, Clone)]
     pub struct Order {
@@ -13,13 +40,230 @@ mod circuits {
         pub side: u8, // 0 = buy, 1 = sell
         pub order_type: u8, // 0 = market, 1 = limit
         pub user_id: u128,
+        pub metadata: u128, // opaque client tag, ignored by matching
+        pub oco_group: u128, // one-cancels-the-other group, 0 = none
+        pub flags: u64, // FLAG_* bits, encrypted with the order
+        pub min_edge_bps: u64, // edge vs the mid a resting order needs to be filled, 0 = any
+        pub trade_group_id: u128, // orders sharing a non-zero group never trade with each other
         pub active: u8, // 0 = inactive, 1 = active
+        pub sequence: u64, // arrival order, assigned by add_order
+        pub order_id: u64, // stable id reserved by the program, independent of slot
+        pub created_slot: u64, // slot the order was added in, set by add_order
+        pub created_ts: u64, // unix timestamp the order was added at, set by add_order
+        pub priority_price: u64, // price snapped to the tick grid, used for queue priority
+        pub modified_slot: u64, // slot of the last add, fill, amendment or removal
+        pub notify_key: ArcisX25519Pubkey, // owner's key for private fill notices, all-zero = none
     }
 
     #[derive(Copy, Clone)]
     pub struct OrderBook {
         pub orders: [Order; MAX_ORDERS],
         pub order_count: u64,
+        pub next_sequence: u64,
+        pub throttle_slot: u64, // slot the fill counter belongs to
+        pub fills_this_slot: u64,
+        pub allowlist: [u128; MAX_ALLOWLIST], // permitted user_ids, 0 = empty entry
+        pub last_trade_price: u64,
+        pub last_trade_amount: u64,
+        pub fees_collected: u128, // maker + taker fees accrued by the venue
+        pub rebates_owed: u128, // maker rebates accrued when the maker fee is negative
+        pub book_version: u64, // number of order changes ever logged
+        pub change_log: [ChangeEntry; CHANGE_LOG_LEN], // ring, indexed by version % len
+        pub heartbeat_users: [u128; MAX_HEARTBEAT_USERS], // cancel-on-disconnect users, 0 = empty
+        pub heartbeat_ts: [u64; MAX_HEARTBEAT_USERS], // last heartbeat per entry (unix seconds)
+        pub user_stats: [UserStats; MAX_TRACKED_USERS], // per-user fill statistics
+        pub recent_trades: [TradeRecord; TRADE_LOG_LEN], // ring, indexed by trade_count % len
+        pub trade_count: u64, // number of trades ever recorded
+        pub volume_buckets: [u128; VOLUME_BUCKETS], // traded notional per hour, indexed by hour % len
+        pub volume_bucket_hours: [u64; VOLUME_BUCKETS], // hour (unix / 3600) each bucket holds
+        pub depth_base: [u64; DEPTH_BUCKETS], // depth at the last snapshot_depth
+        pub depth_base_version: u64, // book_version when depth_base was taken
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct SideQuery {
+        pub user_id: u128,
+        pub side: u8,
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct DepthSnapshot {
+        pub depth: [u64; DEPTH_BUCKETS],
+        pub book_version: u64,
+    }
+
+    // Buckets that changed since the depth snapshot, packed at the front
+    // (count entries are valid); valid = 0 when the client's version isn't
+    // the current snapshot's and it must take a new snapshot
+    #[derive(Copy, Clone)]
+    pub struct DepthDelta {
+        pub bucket_indices: [u64; DEPTH_BUCKETS],
+        pub volumes: [u64; DEPTH_BUCKETS],
+        pub count: u64,
+        pub valid: u8,
+    }
+
+    // Per-user statistics kept for the first MAX_TRACKED_USERS users that trade
+    #[derive(Copy, Clone)]
+    pub struct UserStats {
+        pub user_id: u128, // 0 = empty entry
+        pub lifetime_volume: u128, // base amount filled, maker and taker
+        pub last_fill_slot: u64,
+    }
+
+    // One order change, as recorded in the book's change log
+    #[derive(Copy, Clone)]
+    pub struct ChangeEntry {
+        pub version: u64,
+        pub order_id: u64,
+        pub kind: u8, // 0 = none, 1 = added, 2 = removed, 3 = amended (price or amount)
+    }
+
+    // Changes since a client-held version; complete = 0 means the ring has
+    // wrapped past that version and the client must resync from a snapshot
+    #[derive(Copy, Clone)]
+    pub struct BookDiff {
+        pub entries: [ChangeEntry; CHANGE_LOG_LEN],
+        pub current_version: u64,
+        pub complete: u8,
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct LastTrade {
+        pub price: u64,
+        pub amount: u64,
+    }
+
+    // Cancelled order handed to settlement for collateral release
+    #[derive(Copy, Clone)]
+    pub struct CancelRecord {
+        pub cancelled: u8,
+        pub user_id: u128,
+        pub side: u8,
+        pub price: u64,
+        pub amount: u64,
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct OrderStatus {
+        pub active: u8,
+        pub price: u64,
+        pub amount: u64,
+        pub side: u8,
+        pub order_type: u8,
+        pub metadata: u128,
+        pub flags: u64,
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct TopOfBook {
+        pub best_bid: u64,
+        pub best_ask: u64,
+        pub has_bid: u8,
+        pub has_ask: u8,
+    }
+
+    // Whether an order's activation delay has passed (it stays cancellable
+    // meanwhile; only matching waits)
+    fn is_activated(order: Order, config: MatchConfig) -> bool {
+        config.current_slot >= order.created_slot
+            && config.current_slot - order.created_slot >= config.activation_delay_slots
+    }
+
+    // Self-trade prevention: orders from the same user, or from sub-accounts
+    // sharing a trade_group_id, must not trade with each other
+    fn same_owner(a: Order, b: Order) -> bool {
+        let same_group = a.trade_group_id != 0 && a.trade_group_id == b.trade_group_id;
+        a.user_id == b.user_id || same_group
+    }
+
+    // Whether a FLAG_* bit is set on an order
+    fn has_flag(order: Order, flag: u64) -> bool {
+        (order.flags / flag) % 2 == 1
+    }
+
+    // Scan active limit orders for the best bid and best ask
+    fn top_of_book(ob: OrderBook) -> TopOfBook {
+        let mut top = TopOfBook {
+            best_bid: 0,
+            best_ask: 0,
+            has_bid: 0,
+            has_ask: 0,
+        };
+
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_live_limit = order.active == 1 && order.order_type == 1;
+            let is_buy = order.side == 0;
+
+            let better_bid = top.has_bid == 0 || order.price > top.best_bid;
+            if is_live_limit && is_buy && better_bid {
+                top.best_bid = order.price;
+                top.has_bid = 1;
+            }
+
+            let better_ask = top.has_ask == 0 || order.price < top.best_ask;
+            if is_live_limit && !is_buy && better_ask {
+                top.best_ask = order.price;
+                top.has_ask = 1;
+            }
+        }
+
+        top
+    }
+
+    // Overflow-safe a * b, saturating at u64::MAX
+    fn mul_sat(a: u64, b: u64) -> u64 {
+        let wide = (a as u128) * (b as u128);
+        if wide > (u64::MAX as u128) { u64::MAX } else { wide as u64 }
+    }
+
+    // Overflow-safe a * b / d with a u128 intermediate, saturating at u64::MAX
+    // Returns 0 when d == 0
+    fn mul_div(a: u64, b: u64, d: u64) -> u64 {
+        let divisor = if d == 0 { 1u128 } else { d as u128 };
+        let wide = (a as u128) * (b as u128) / divisor;
+        let quotient = if wide > (u64::MAX as u128) { u64::MAX } else { wide as u64 };
+        if d == 0 { 0 } else { quotient }
+    }
+
+    // Whether `a` ranks strictly ahead of `b` in same-side price-time priority
+    // Prices are compared on the tick grid, so a sub-tick improvement doesn't
+    // jump the queue
+    fn has_priority(a: Order, b: Order) -> bool {
+        let better_price = if a.side == 0 {
+            a.priority_price > b.priority_price
+        } else {
+            a.priority_price < b.priority_price
+        };
+        let earlier_at_price = a.priority_price == b.priority_price && a.sequence < b.sequence;
+        better_price || earlier_at_price
+    }
+
+    // Snap a price to the tick grid on the passive side: bids round down and
+    // asks round up, so only a whole-tick improvement reaches a better level
+    fn priority_price(price: u64, side: u8, tick_size: u64) -> u64 {
+        let divisor = if tick_size > 0 { tick_size } else { 1 };
+        let rest = price % divisor;
+        let floor = price - rest;
+        let ceil = if rest > 0 { floor + divisor } else { floor };
+
+        if tick_size == 0 {
+            price
+        } else if side == 0 {
+            floor
+        } else {
+            ceil
+        }
+    }
+
+    // A fill in the recent-trades ring with the users on each side
+    // (for pro-rata fills the sell or buy user is the maker given the remainder)
+    #[derive(Copy, Clone)]
+    pub struct TradeRecord {
+        pub result: MatchResult,
+        pub buy_user_id: u128,
+        pub sell_user_id: u128,
     }
 
     #[derive(Copy, Clone)]
@@ -27,188 +271,5009 @@ mod circuits {
         pub matched: u8,
         pub match_price: u64,
         pub match_amount: u64,
-        pub buy_order_id: u64,
+        pub buy_order_id: u64, // stable order ids, not slot indices
         pub sell_order_id: u64,
+        pub taker_side: u8, // side of the aggressing (newer) order
+        pub scaled_price: u64, // match price * price_scale, unrounded (100.5 at scale 10 is 1005)
+        pub price_scale: u64, // fixed-point scale of scaled_price; rounding happens at settlement
+    }
+
+    fn empty_order() -> Order {
+        Order {
+            price: 0,
+            amount: 0,
+            side: 0,
+            order_type: 0,
+            user_id: 0,
+            metadata: 0,
+            oco_group: 0,
+            flags: 0,
+            min_edge_bps: 0,
+            trade_group_id: 0,
+            active: 0,
+            sequence: 0,
+            order_id: 0,
+            created_slot: 0,
+            created_ts: 0,
+            priority_price: 0,
+            modified_slot: 0,
+            notify_key: no_notify_key(),
+        }
+    }
+
+    // Placeholder notify_key for orders that asked for no fill notices
+    fn no_notify_key() -> ArcisX25519Pubkey {
+        ArcisX25519Pubkey::from_uint8(&[0u8; 32])
+    }
+
+    fn empty_change() -> ChangeEntry {
+        ChangeEntry {
+            version: 0,
+            order_id: 0,
+            kind: 0,
+        }
+    }
+
+    // Append one entry per order slot that differs between `prev` and `next`
+    // to the change log of `next`
+    fn log_book_changes(prev: OrderBook, mut next: OrderBook, current_slot: u64) -> OrderBook {
+        for i in 0..MAX_ORDERS {
+            let before = prev.orders[i];
+            let after = next.orders[i];
+
+            let added = after.active == 1 && (before.active == 0 || before.order_id != after.order_id);
+            let removed = before.active == 1 && after.active == 0;
+            let changed = before.active == 1
+                && after.active == 1
+                && before.order_id == after.order_id
+                && (before.amount != after.amount || before.price != after.price);
+
+            let kind = if added { 1u8 } else if removed { 2u8 } else if changed { 3u8 } else { 0u8 };
+            let order_id = if removed { before.order_id } else { after.order_id };
+            if kind != 0 {
+                next.orders[i].modified_slot = current_slot;
+            }
+
+            let position = next.book_version % (CHANGE_LOG_LEN as u64);
+            for k in 0..CHANGE_LOG_LEN {
+                if kind != 0 && (k as u64) == position {
+                    next.change_log[k] = ChangeEntry {
+                        version: next.book_version,
+                        order_id,
+                        kind,
+                    };
+                }
+            }
+            let version_increment = if kind != 0 { 1u64 } else { 0u64 };
+            next.book_version = next.book_version + version_increment;
+        }
+        next
+    }
+
+    // Create an empty encrypted order book
+    #[instruction]
+    pub fn init_orderbook(mxe: Mxe) -> Enc<Mxe, OrderBook> {
+        let ob = OrderBook {
+            orders: [empty_order(); MAX_ORDERS],
+            order_count: 0,
+            next_sequence: 0,
+            throttle_slot: 0,
+            fills_this_slot: 0,
+            allowlist: [0; MAX_ALLOWLIST],
+            last_trade_price: 0,
+            last_trade_amount: 0,
+            fees_collected: 0,
+            rebates_owed: 0,
+            book_version: 0,
+            change_log: [empty_change(); CHANGE_LOG_LEN],
+            heartbeat_users: [0; MAX_HEARTBEAT_USERS],
+            heartbeat_ts: [0; MAX_HEARTBEAT_USERS],
+            user_stats: [UserStats { user_id: 0, lifetime_volume: 0, last_fill_slot: 0 }; MAX_TRACKED_USERS],
+            recent_trades: [TradeRecord { result: empty_match_result(), buy_user_id: 0, sell_user_id: 0 }; TRADE_LOG_LEN],
+            trade_count: 0,
+            volume_buckets: [0; VOLUME_BUCKETS],
+            volume_bucket_hours: [0; VOLUME_BUCKETS],
+            depth_base: [0; DEPTH_BUCKETS],
+            depth_base_version: 0,
+        };
+
+        mxe.from_arcis(ob)
+    }
+
+    // Re-encrypt the order book unchanged
+    // Run once after init_orderbook so the first real match doesn't pay the
+    // one-off cost of the book's first load in the cluster
+    #[instruction]
+    pub fn warm_book(orderbook_ctxt: Enc<Mxe, OrderBook>) -> Enc<Mxe, OrderBook> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Order and OrderBook as encrypted under layouts v0-v10, before orders
+    // carried a notify_key. Only migrate_book reads them
+    #[derive(Copy, Clone)]
+    pub struct LegacyOrder {
+        pub price: u64,
+        pub amount: u64,
+        pub side: u8,
+        pub order_type: u8,
+        pub user_id: u128,
+        pub metadata: u128,
+        pub oco_group: u128,
+        pub flags: u64,
+        pub min_edge_bps: u64,
+        pub trade_group_id: u128,
+        pub active: u8,
+        pub sequence: u64,
+        pub order_id: u64,
+        pub created_slot: u64,
+        pub created_ts: u64,
+        pub priority_price: u64,
+        pub modified_slot: u64,
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct LegacyOrderBook {
+        pub orders: [LegacyOrder; MAX_ORDERS],
+        pub order_count: u64,
+        pub next_sequence: u64,
+        pub throttle_slot: u64,
+        pub fills_this_slot: u64,
+        pub allowlist: [u128; MAX_ALLOWLIST],
+        pub last_trade_price: u64,
+        pub last_trade_amount: u64,
+        pub fees_collected: u128,
+        pub rebates_owed: u128,
+        pub book_version: u64,
+        pub change_log: [ChangeEntry; CHANGE_LOG_LEN],
+        pub heartbeat_users: [u128; MAX_HEARTBEAT_USERS],
+        pub heartbeat_ts: [u64; MAX_HEARTBEAT_USERS],
+        pub user_stats: [UserStats; MAX_TRACKED_USERS],
+        pub recent_trades: [TradeRecord; TRADE_LOG_LEN],
+        pub trade_count: u64,
+        pub volume_buckets: [u128; VOLUME_BUCKETS],
+        pub volume_bucket_hours: [u64; VOLUME_BUCKETS],
+        pub depth_base: [u64; DEPTH_BUCKETS],
+        pub depth_base_version: u64,
+    }
+
+    // Re-encrypt a v0-v10 book under the current layout. Migrated orders get
+    // no notify_key, so they receive no fill notices until re-placed
+    #[instruction]
+    pub fn migrate_book(orderbook_ctxt: Enc<Mxe, LegacyOrderBook>) -> Enc<Mxe, OrderBook> {
+        let old = orderbook_ctxt.to_arcis();
+
+        let mut orders = [empty_order(); MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            let old_order = old.orders[i];
+            orders[i] = Order {
+                price: old_order.price,
+                amount: old_order.amount,
+                side: old_order.side,
+                order_type: old_order.order_type,
+                user_id: old_order.user_id,
+                metadata: old_order.metadata,
+                oco_group: old_order.oco_group,
+                flags: old_order.flags,
+                min_edge_bps: old_order.min_edge_bps,
+                trade_group_id: old_order.trade_group_id,
+                active: old_order.active,
+                sequence: old_order.sequence,
+                order_id: old_order.order_id,
+                created_slot: old_order.created_slot,
+                created_ts: old_order.created_ts,
+                priority_price: old_order.priority_price,
+                modified_slot: old_order.modified_slot,
+                notify_key: no_notify_key(),
+            };
+        }
+
+        let ob = OrderBook {
+            orders,
+            order_count: old.order_count,
+            next_sequence: old.next_sequence,
+            throttle_slot: old.throttle_slot,
+            fills_this_slot: old.fills_this_slot,
+            allowlist: old.allowlist,
+            last_trade_price: old.last_trade_price,
+            last_trade_amount: old.last_trade_amount,
+            fees_collected: old.fees_collected,
+            rebates_owed: old.rebates_owed,
+            book_version: old.book_version,
+            change_log: old.change_log,
+            heartbeat_users: old.heartbeat_users,
+            heartbeat_ts: old.heartbeat_ts,
+            user_stats: old.user_stats,
+            recent_trades: old.recent_trades,
+            trade_count: old.trade_count,
+            volume_buckets: old.volume_buckets,
+            volume_bucket_hours: old.volume_bucket_hours,
+            depth_base: old.depth_base,
+            depth_base_version: old.depth_base_version,
+        };
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    fn is_allowlisted(ob: OrderBook, user_id: u128) -> bool {
+        let mut listed = false;
+        for k in 0..MAX_ALLOWLIST {
+            listed = listed || (ob.allowlist[k] != 0 && ob.allowlist[k] == user_id);
+        }
+        listed
     }
 
     // Add order to encrypted order book
+    // The order is dropped if an active order already carries the same id, if
+    // the allow-list is enforced and the user isn't on it, or if a limit order
+    // is priced outside the price caps (a buy above max_bid_price or a sell below
+    // min_ask_price; a cap of 0 is off), or if a limit order's notional
+    // (price * amount / amount_unit) is below min_notional (0 is off; market
+    // orders carry no price to check)
+    // When the book is full, eviction_policy 1 replaces the worst-priced resting
+    // limit order on the same side if the new limit order ranks ahead of it; the
+    // evicted order comes back as a cancel record and the flag is revealed
+    // The last output reveals only whether order_count rose to
+    // capacity_threshold with this order (0 disables the alert)
     #[instruction]
     pub fn add_order(
+        order_id: u64,
+        allowlist_enabled: u8,
+        max_bid_price: u64,
+        min_ask_price: u64,
+        current_slot: u64,
+        current_ts: u64,
+        tick_size: u64,
+        eviction_policy: u8,
+        capacity_threshold: u64,
+        min_notional: u64,
+        amount_unit: u64,
+        price_offset: u64,
+        notify_key: ArcisX25519Pubkey,
         order_ctxt: Enc<Shared, Order>,
         orderbook_ctxt: Enc<Mxe, OrderBook>,
-    ) -> Enc<Mxe, OrderBook> {
+    ) -> (Enc<Mxe, OrderBook>, Enc<Mxe, CancelRecord>, bool, bool) {
         let order = order_ctxt.to_arcis();
-        let mut ob = orderbook_ctxt.to_arcis();
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = prev;
+
+        let mut duplicate = false;
+        for i in 0..MAX_ORDERS {
+            duplicate = duplicate || (ob.orders[i].active == 1 && ob.orders[i].order_id == order_id);
+        }
+        let not_allowed = allowlist_enabled == 1 && !is_allowlisted(ob, order.user_id);
+        let is_limit = order.order_type == 1;
+        let bid_too_high = max_bid_price > 0 && order.side == 0 && order.price > max_bid_price;
+        let ask_too_low = min_ask_price > 0 && order.side == 1 && order.price < min_ask_price;
+        let outside_caps = is_limit && (bid_too_high || ask_too_low);
+        let notional = mul_div(price_magnitude(order.price, price_offset), order.amount, amount_unit);
+        let below_notional = is_limit && min_notional > 0 && notional < min_notional;
+        let top = top_of_book(prev);
+        let would_take = if order.side == 0 {
+            top.has_ask == 1 && order.price >= top.best_ask
+        } else {
+            top.has_bid == 1 && order.price <= top.best_bid
+        };
+        let post_only_rejected = has_flag(order, FLAG_POST_ONLY) && (!is_limit || would_take);
+        let duplicate = duplicate || not_allowed || outside_caps || below_notional || post_only_rejected;
+
+        let mut incoming = order;
+        incoming.active = 1;
+        incoming.sequence = ob.next_sequence;
+        incoming.order_id = order_id;
+        incoming.created_slot = current_slot;
+        incoming.created_ts = current_ts;
+        incoming.priority_price = priority_price(order.price, order.side, tick_size);
+        incoming.notify_key = notify_key;
 
         // Find empty slot and add order
-        let mut added = 0u8;
+        let mut added = if duplicate { 1u8 } else { 0u8 };
         for i in 0..MAX_ORDERS {
             let is_empty = ob.orders[i].active == 0;
             let should_add = is_empty && added == 0;
             
             if should_add {
-                ob.orders[i] = order;
-                ob.orders[i].active = 1;
+                ob.orders[i] = incoming;
                 added = 1;
             }
         }
+        if duplicate {
+            added = 0;
+        }
+
+        // Book full: find the same-side limit order that ranks last
+        let mut worst = empty_order();
+        let mut worst_slot = 0u64;
+        let mut has_worst = false;
+        for i in 0..MAX_ORDERS {
+            let resting = ob.orders[i];
+            let candidate = resting.active == 1 && resting.order_type == 1 && resting.side == order.side;
+            let behind = !has_worst || has_priority(worst, resting);
+            if candidate && behind {
+                worst = resting;
+                worst_slot = i as u64;
+                has_worst = true;
+            }
+        }
+        let evict = eviction_policy == 1
+            && added == 0
+            && !duplicate
+            && is_limit
+            && has_worst
+            && has_priority(incoming, worst);
+
+        let mut evicted = CancelRecord {
+            cancelled: 0,
+            user_id: 0,
+            side: 0,
+            price: 0,
+            amount: 0,
+        };
+        for i in 0..MAX_ORDERS {
+            if evict && (i as u64) == worst_slot {
+                evicted = CancelRecord {
+                    cancelled: 1,
+                    user_id: worst.user_id,
+                    side: worst.side,
+                    price: worst.price,
+                    amount: worst.amount,
+                };
+                ob.orders[i] = incoming;
+            }
+        }
 
-        // Increment order count if added
+        // Increment order count and sequence if added; an eviction swaps one
+        // order for another, so only the sequence moves
         let count_increment = if added == 1 { 1u64 } else { 0u64 };
+        let sequence_increment = if added == 1 || evict { 1u64 } else { 0u64 };
         ob.order_count = ob.order_count + count_increment;
+        ob.next_sequence = ob.next_sequence + sequence_increment;
+        let ob = log_book_changes(prev, ob, current_slot);
 
-        orderbook_ctxt.owner.from_arcis(ob)
+        let near_capacity = capacity_threshold > 0
+            && prev.order_count < capacity_threshold
+            && ob.order_count >= capacity_threshold;
+
+        let updated_ob = orderbook_ctxt.owner.from_arcis(ob);
+        let evicted_record = orderbook_ctxt.owner.from_arcis(evicted);
+
+        (updated_ob, evicted_record, evict.reveal(), near_capacity.reveal())
     }
 
-    // Match orders in encrypted order book
-    #[instruction]
-    pub fn match_orders(
-        orderbook_ctxt: Enc<Mxe, OrderBook>,
-    ) -> (Enc<Mxe, OrderBook>, Enc<Shared, MatchResult>) {
-        let mut ob = orderbook_ctxt.to_arcis();
-        
-        let mut result = MatchResult {
+    // Plaintext matching parameters shared by all match paths
+    #[derive(Copy, Clone)]
+    pub struct MatchConfig {
+        pub oracle_price: u64,
+        pub oracle_fresh: u8,
+        pub rounding: u8, // 0 = floor, 1 = ceil, 2 = toward-maker
+        pub fill_increment: u64, // fills are floored to a multiple of this (0 = off)
+        pub maker_fee_bps: i64, // negative = rebate paid to the maker
+        pub taker_fee_bps: u64,
+        pub fee_rounding: u8, // 0 = floor, 1 = ceil, 2 = nearest (half up)
+        pub amount_unit: u64, // 10^amount_scale, converts price * amount to notional
+        pub priority_policy: u8, // 0 = market first then slot order, 1 = market first then price-time
+        pub max_take_per_maker: u64, // most one call may take from a resting order (0 = off)
+        pub spread_policy: u8, // 0 = midpoint, 1 = maker-favorable, 2 = taker-favorable
+        pub round_lot_size: u64, // when non-zero, round-lot pairs cross before odd lots
+        pub current_slot: u64,
+        pub match_cooldown_slots: u64, // a user who just filled sits out this long (0 = off)
+        pub price_scale: u64, // 10^price_decimals, fixed-point scale of MatchResult.scaled_price
+        pub cross_tolerance: u64, // limit pairs need buy - sell >= this to cross (0 = touching crosses)
+        pub pair_rules: u8, // PAIR_RULE_* bits
+        pub price_offset: u64, // 2^63 when prices are offset-binary signed, else 0
+        pub current_ts: u64, // unix timestamp, buckets the rolling volume
+        pub price_source_policy: u8, // 0 = oracle only, 1 = oracle then book, 2 = book then oracle
+        pub max_match_fraction_bps: u64, // cap on one fill vs the maker side's visible volume, 0 = none
+        pub lock_policy: u8, // 0 = locked limits (bid == ask) match, 1 = they rest
+        pub activation_delay_slots: u64, // slots after posting before an order can match
+        pub auction: bool, // set by crank_match; continuous matching skips FLAG_AUCTION_ONLY orders
+    }
+
+    // Whether an order's remaining amount is a whole number of round lots
+    fn is_round_lot(order: Order, round_lot_size: u64) -> bool {
+        let divisor = if round_lot_size > 0 { round_lot_size } else { 1 };
+        round_lot_size > 0 && order.amount % divisor == 0
+    }
+
+    // Fee of `bps` basis points on `notional` with the configured rounding
+    fn fee_on(notional: u64, bps: u64, fee_rounding: u8) -> u128 {
+        let scaled = (notional as u128) * (bps as u128);
+        let floor = scaled / 10000;
+        let rest = scaled % 10000;
+
+        let round_up = (fee_rounding == 1 && rest > 0) || (fee_rounding == 2 && rest >= 5000);
+
+        if round_up { floor + 1 } else { floor }
+    }
+
+    // Floor a fill amount to a multiple of the market's fill increment
+    fn floor_to_increment(amount: u64, fill_increment: u64) -> u64 {
+        if fill_increment > 1 { amount / fill_increment * fill_increment } else { amount }
+    }
+
+    // Midpoint of two limit prices with the configured rounding
+    // Toward-maker rounds in the maker's favour: down when the (older) buy
+    // order is the maker, up when the sell order is
+    // Halves are taken separately so offset-binary prices near 2^63 can't overflow
+    fn midpoint(buy_order: Order, sell_order: Order, rounding: u8) -> u64 {
+        let odd_bits = buy_order.price % 2 + sell_order.price % 2;
+        let floor = buy_order.price / 2 + sell_order.price / 2 + odd_bits / 2;
+        let half_tick = odd_bits % 2;
+
+        let buyer_is_maker = buy_order.sequence < sell_order.sequence;
+        let round_up = rounding == 1 || (rounding == 2 && !buyer_is_maker);
+
+        if round_up { floor + half_tick } else { floor }
+    }
+
+    fn empty_match_result() -> MatchResult {
+        MatchResult {
             matched: 0,
             match_price: 0,
             match_amount: 0,
             buy_order_id: 0,
             sell_order_id: 0,
-        };
+            taker_side: 0,
+            scaled_price: 0,
+            price_scale: 0,
+        }
+    }
 
-        // Find matching buy and sell orders
-        for i in 0..MAX_ORDERS {
-            for j in 0..MAX_ORDERS {
-                let buy_order = ob.orders[i];
-                let sell_order = ob.orders[j];
-
-                // Check if orders can match
-                let is_buy = buy_order.side == 0;
-                let is_sell = sell_order.side == 1;
-                let both_active = buy_order.active == 1 && sell_order.active == 1;
-                let not_same_user = buy_order.user_id != sell_order.user_id;
-                
-                // Price matching logic
-                let price_match = if buy_order.order_type == 0 || sell_order.order_type == 0 {
-                    // Market order - always matches
-                    1u8
-                } else {
-                    // Limit order - buy price >= sell price
-                    if buy_order.price >= sell_order.price { 1u8 } else { 0u8 }
-                };
+    // Start a new per-slot fill window when the slot advances
+    fn roll_fill_window(mut ob: OrderBook, current_slot: u64) -> OrderBook {
+        let new_slot = ob.throttle_slot != current_slot;
+        if new_slot {
+            ob.throttle_slot = current_slot;
+            ob.fills_this_slot = 0;
+        }
+        ob
+    }
 
-                let can_match = is_buy && is_sell && both_active && not_same_user && price_match == 1 && result.matched == 0;
+    // Whether a bid at `bid` crosses an ask at `ask` by at least the tolerance.
+    // A locked pair (bid == ask) is held when lock_policy is 1
+    fn crosses_by(bid: u64, ask: u64, config: MatchConfig) -> bool {
+        let held = config.lock_policy == 1 && bid == ask;
+        bid >= ask && bid - ask >= config.cross_tolerance && !held
+    }
 
-                if can_match {
-                    // Calculate match price (midpoint for limit orders, limit price for market)
-                    let match_price = if buy_order.order_type == 0 {
-                        sell_order.price
-                    } else if sell_order.order_type == 0 {
-                        buy_order.price
-                    } else {
-                        (buy_order.price + sell_order.price) / 2
-                    };
+    // Whether a PAIR_RULE_* bit is set
+    fn has_pair_rule(config: MatchConfig, rule: u8) -> bool {
+        (config.pair_rules / rule) % 2 == 1
+    }
 
-                    // Calculate match amount (minimum of both orders)
-                    let match_amount = if buy_order.amount < sell_order.amount {
-                        buy_order.amount
-                    } else {
-                        sell_order.amount
-                    };
+    // Midpoint of the book touch (both sides must be present)
+    fn touch_mid(top: TopOfBook) -> u64 {
+        top.best_bid / 2 + top.best_ask / 2 + (top.best_bid % 2 + top.best_ask % 2) / 2
+    }
 
-                    // Update result
-                    result.matched = 1;
-                    result.match_price = match_price;
-                    result.match_amount = match_amount;
-                    result.buy_order_id = i as u64;
-                    result.sell_order_id = j as u64;
+    // Whether filling `maker` at `exec_price` gives it at least its min_edge_bps
+    // of edge versus the touch midpoint. Without a two-sided touch there is no
+    // mid, so only makers without a minimum can be filled
+    fn has_min_edge(maker: Order, exec_price: u64, top: TopOfBook, price_offset: u64) -> bool {
+        let has_mid = top.has_bid == 1 && top.has_ask == 1;
+        let mid = touch_mid(top);
+        let edge = if maker.side == 0 {
+            if mid > exec_price { mid - exec_price } else { 0 }
+        } else {
+            if exec_price > mid { exec_price - mid } else { 0 }
+        };
+        let required = (maker.min_edge_bps as u128) * (price_magnitude(mid, price_offset) as u128);
 
-                    // Update order amounts
-                    ob.orders[i].amount = ob.orders[i].amount - match_amount;
-                    ob.orders[j].amount = ob.orders[j].amount - match_amount;
+        maker.min_edge_bps == 0 || (has_mid && (edge as u128) * 10000 >= required)
+    }
 
-                    // Deactivate fully filled orders
-                    if ob.orders[i].amount == 0 {
-                        ob.orders[i].active = 0;
-                    }
-                    if ob.orders[j].amount == 0 {
-                        ob.orders[j].active = 0;
-                    }
-                }
+    // Largest fill allowed against `side`: max_match_fraction_bps of that side's
+    // visible (active limit) volume
+    fn depth_fraction_cap(ob: OrderBook, side: u8, max_match_fraction_bps: u64) -> u64 {
+        let mut visible = 0u128;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_visible = order.active == 1 && order.order_type == 1 && order.side == side;
+
+            if is_visible {
+                visible = visible + (order.amount as u128);
             }
         }
 
-        let updated_ob = orderbook_ctxt.owner.from_arcis(ob);
-        let match_result = orderbook_ctxt.owner.from_arcis(result);
+        ((visible * (max_match_fraction_bps as u128)) / 10000) as u64
+    }
+
+    // Price source for market orders, per price_source_policy:
+    //   0 = oracle only: market orders need a fresh oracle
+    //   1 = oracle first, falling back to the book when the oracle is stale
+    //   2 = book first, falling back to the oracle when a book side is empty
+    // The book price is the counterparty's limit, or the touch midpoint for
+    // market x market. Returns (available, use_oracle)
+    fn market_price_source(config: MatchConfig, top: TopOfBook, both_market: bool) -> (bool, bool) {
+        let oracle_ok = config.oracle_fresh == 1;
+        let book_ok = !both_market || (top.has_bid == 1 && top.has_ask == 1);
 
-        (updated_ob, match_result)
+        if config.price_source_policy == 1 {
+            (oracle_ok || book_ok, oracle_ok)
+        } else if config.price_source_policy == 2 {
+            (book_ok || oracle_ok, !book_ok)
+        } else {
+            (oracle_ok, true)
+        }
     }
 
-    // Cancel order from encrypted order book
-    #[instruction]
-    pub fn cancel_order(
-        order_id: u64,
-        user_id: Enc<Shared, u128>,
-        orderbook_ctxt: Enc<Mxe, OrderBook>,
-    ) -> Enc<Mxe, OrderBook> {
-        let user = user_id.to_arcis();
-        let mut ob = orderbook_ctxt.to_arcis();
+    // Whether a buy/sell pair is allowed to trade. Eligibility and pricing by
+    // order type (buy x sell):
+    //
+    //   market x market  a price source (see market_price_source), unless
+    //                    PAIR_RULE_NO_MARKET_PAIRS; oracle or touch midpoint
+    //   market x limit   a price source; the limit price, or the oracle price when
+    //   limit x market   PAIR_RULE_ORACLE_MARKET_LIMIT is set, the oracle is the
+    //                    source and the limit accepts it
+    //   limit x limit    buy >= sell + cross_tolerance (buy > sell when lock_policy
+    //                    holds locked markets); priced per spread_policy
+    //
+    // In every cell an all-or-none order also needs a counterparty at least as
+    // large as itself, the maker must get its min_edge_bps at the execution
+    // price, auction-only orders only trade in crank_match, and both orders
+    // must be past their activation delay
+    fn can_cross(buy_order: Order, sell_order: Order, config: MatchConfig, top: TopOfBook) -> bool {
+        let is_buy = buy_order.side == 0;
+        let is_sell = sell_order.side == 1;
+        let both_active = buy_order.active == 1 && sell_order.active == 1;
+        let not_same_user = !same_owner(buy_order, sell_order);
 
-        // Find and cancel order if user matches
-        for i in 0..MAX_ORDERS {
-            let is_target_order = (i as u64) == order_id;
-            let is_owner = ob.orders[i].user_id == user;
-            let is_active = ob.orders[i].active == 1;
-            
-            let should_cancel = is_target_order && is_owner && is_active;
+        let buy_market = buy_order.order_type == 0;
+        let sell_market = sell_order.order_type == 0;
+        let (source_available, _) = market_price_source(config, top, buy_market && sell_market);
+        let price_match = if buy_market && sell_market {
+            source_available && !has_pair_rule(config, PAIR_RULE_NO_MARKET_PAIRS)
+        } else if buy_market || sell_market {
+            source_available
+        } else {
+            crosses_by(buy_order.price, sell_order.price, config)
+        };
 
-            if should_cancel {
-                ob.orders[i].active = 0;
-                ob.order_count = ob.order_count - 1;
-            }
+        // An all-or-none order needs a counterparty at least as large
+        let buy_fillable = !has_flag(buy_order, FLAG_ALL_OR_NONE) || sell_order.amount >= buy_order.amount;
+        let sell_fillable = !has_flag(sell_order, FLAG_ALL_OR_NONE) || buy_order.amount >= sell_order.amount;
+        let in_session = config.auction
+            || (!has_flag(buy_order, FLAG_AUCTION_ONLY) && !has_flag(sell_order, FLAG_AUCTION_ONLY));
+        let activated = is_activated(buy_order, config) && is_activated(sell_order, config);
+
+        let buyer_is_maker = buy_order.sequence < sell_order.sequence;
+        let maker = if buyer_is_maker { buy_order } else { sell_order };
+        let maker_edge = has_min_edge(maker, cross_price(buy_order, sell_order, config, top), top, config.price_offset);
+
+        is_buy && is_sell && both_active && not_same_user && price_match && buy_fillable && sell_fillable && maker_edge && in_session && activated
+    }
+
+    // Execution price for a crossing pair, per the matrix on can_cross. Two
+    // limit orders trade at the midpoint, at the taker's limit (maker-favorable:
+    // the maker earns the whole spread) or at the maker's limit
+    // (taker-favorable), per spread_policy
+    fn cross_price(buy_order: Order, sell_order: Order, config: MatchConfig, top: TopOfBook) -> u64 {
+        let buyer_is_maker = buy_order.sequence < sell_order.sequence;
+        let maker_price = if buyer_is_maker { buy_order.price } else { sell_order.price };
+        let taker_price = if buyer_is_maker { sell_order.price } else { buy_order.price };
+
+        let both_market = buy_order.order_type == 0 && sell_order.order_type == 0;
+        let (_, use_oracle) = market_price_source(config, top, both_market);
+        let oracle_pricing = has_pair_rule(config, PAIR_RULE_ORACLE_MARKET_LIMIT) && use_oracle;
+        let seller_accepts_oracle = config.oracle_price >= sell_order.price;
+        let buyer_accepts_oracle = config.oracle_price <= buy_order.price;
+
+        if both_market {
+            if use_oracle { config.oracle_price } else { touch_mid(top) }
+        } else if buy_order.order_type == 0 {
+            if oracle_pricing && seller_accepts_oracle { config.oracle_price } else { sell_order.price }
+        } else if sell_order.order_type == 0 {
+            if oracle_pricing && buyer_accepts_oracle { config.oracle_price } else { buy_order.price }
+        } else if config.spread_policy == 1 {
+            taker_price
+        } else if config.spread_policy == 2 {
+            maker_price
+        } else {
+            midpoint(buy_order, sell_order, config.rounding)
         }
+    }
 
-        orderbook_ctxt.owner.from_arcis(ob)
+    // Absolute value of a price: the offset-binary magnitude for signed books,
+    // the price itself otherwise. Notionals and fees are charged on it
+    fn price_magnitude(price: u64, price_offset: u64) -> u64 {
+        if price >= price_offset { price - price_offset } else { price_offset - price }
     }
 
-    // Get order book depth (privacy-preserving aggregation)
-    #[instruction]
-    pub fn get_orderbook_depth(
-        orderbook_ctxt: Enc<Mxe, OrderBook>,
-        price_levels: u64,
-    ) -> Enc<Shared, [u64; 20]> {
-        let ob = orderbook_ctxt.to_arcis();
-        let mut depth: [u64; 20] = [0; 20];
+    // Re-encode an (encoded price * scale) product so the sign offset isn't
+    // scaled along with it: the result is real_price * scale + price_offset
+    fn rescale_price(wide: u128, scale: u64, price_offset: u64) -> u64 {
+        let shifted = wide + (price_offset as u128);
+        let scaled_offset = (price_offset as u128) * (scale as u128);
+        let rescaled = if shifted > scaled_offset { shifted - scaled_offset } else { 0 };
 
-        // Aggregate buy orders by price level (simplified)
-        for i in 0..10 {
-            let mut level_volume = 0u64;
-            for j in 0..MAX_ORDERS {
-                let order = ob.orders[j];
-                let is_buy = order.side == 0;
-                let is_active = order.active == 1;
-                
-                if is_buy && is_active {
-                    level_volume = level_volume + order.amount;
+        if rescaled > (u64::MAX as u128) { u64::MAX } else { rescaled as u64 }
+    }
+
+    // Crossing price at `price_scale` precision. A limit-vs-limit midpoint keeps
+    // the half tick the integer match price rounds away; every other price is
+    // already a whole price and is just scaled up
+    fn scaled_cross_price(buy_order: Order, sell_order: Order, config: MatchConfig, top: TopOfBook) -> u64 {
+        let scale = if config.price_scale > 0 { config.price_scale } else { 1 };
+        let is_midpoint = buy_order.order_type == 1
+            && sell_order.order_type == 1
+            && config.spread_policy != 1
+            && config.spread_policy != 2;
+        let sum = (buy_order.price as u128) + (sell_order.price as u128);
+        let wide = if is_midpoint {
+            sum * (scale as u128) / 2
+        } else {
+            (cross_price(buy_order, sell_order, config, top) as u128) * (scale as u128)
+        };
+
+        rescale_price(wide, scale, config.price_offset)
+    }
+
+    // Whether crossing a pair involving a market order would trade through the
+    // book: a market buy paying above the best ask, or a market sell receiving
+    // below the best bid. Limit-vs-limit pairs are bounded by their own limits
+    fn trades_through(buy_order: Order, sell_order: Order, config: MatchConfig, top: TopOfBook) -> bool {
+        let price = cross_price(buy_order, sell_order, config, top);
+        let buy_through = buy_order.order_type == 0 && top.has_ask == 1 && price > top.best_ask;
+        let sell_through = sell_order.order_type == 0 && top.has_bid == 1 && price < top.best_bid;
+        buy_through || sell_through
+    }
+
+    // Reduce both orders of a fill and deactivate fully filled ones
+    fn apply_fill(mut ob: OrderBook, buy_id: u64, sell_id: u64, amount: u64) -> OrderBook {
+        for i in 0..MAX_ORDERS {
+            let is_filled = (i as u64) == buy_id || (i as u64) == sell_id;
+            if is_filled {
+                ob.orders[i].amount = ob.orders[i].amount - amount;
+                if ob.orders[i].amount == 0 {
+                    ob.orders[i].active = 0;
+                    ob.order_count = ob.order_count - 1;
                 }
             }
-            depth[i] = level_volume;
         }
+        ob
+    }
 
-        // Aggregate sell orders by price level
-        for i in 10..20 {
-            let mut level_volume = 0u64;
-            for j in 0..MAX_ORDERS {
-                let order = ob.orders[j];
-                let is_sell = order.side == 1;
-                let is_active = order.active == 1;
-                
-                if is_sell && is_active {
-                    level_volume = level_volume + order.amount;
-                }
+    // Book-level bookkeeping after a match attempt: per-slot fill counter, last
+    // trade and fees (each side's fee is rounded on its own). A negative maker
+    // fee is a rebate: it accrues to rebates_owed instead of fees_collected
+    fn record_fill(mut ob: OrderBook, result: MatchResult, config: MatchConfig) -> OrderBook {
+        let price = price_magnitude(result.match_price, config.price_offset);
+        let notional = mul_div(price, result.match_amount, config.amount_unit);
+        let is_rebate = config.maker_fee_bps < 0;
+        let maker_bps = if is_rebate { (-config.maker_fee_bps) as u64 } else { config.maker_fee_bps as u64 };
+        let maker_fee = fee_on(notional, maker_bps, config.fee_rounding);
+        let taker_fee = fee_on(notional, config.taker_fee_bps, config.fee_rounding);
+
+        if result.matched == 1 {
+            ob.fills_this_slot = ob.fills_this_slot + 1;
+            ob.last_trade_price = result.match_price;
+            ob.last_trade_amount = result.match_amount;
+            if is_rebate {
+                ob.fees_collected = ob.fees_collected + taker_fee;
+                ob.rebates_owed = ob.rebates_owed + maker_fee;
+            } else {
+                ob.fees_collected = ob.fees_collected + maker_fee + taker_fee;
+            }
+        }
+
+        // Rolling volume: a bucket still holding an older hour starts over
+        let hour = config.current_ts / 3600;
+        let position = hour % (VOLUME_BUCKETS as u64);
+        for k in 0..VOLUME_BUCKETS {
+            if result.matched == 1 && (k as u64) == position {
+                let stale = ob.volume_bucket_hours[k] != hour;
+                let carried = if stale { 0u128 } else { ob.volume_buckets[k] };
+                ob.volume_buckets[k] = carried + (notional as u128);
+                ob.volume_bucket_hours[k] = hour;
             }
-            depth[i] = level_volume;
         }
+        ob
+    }
+
+    // Append a fill to the recent-trades ring, overwriting the oldest entry
+    fn record_trade(mut ob: OrderBook, result: MatchResult, buy_user_id: u128, sell_user_id: u128) -> OrderBook {
+        let record = TradeRecord { result, buy_user_id, sell_user_id };
+        let position = ob.trade_count % (TRADE_LOG_LEN as u64);
+        for k in 0..TRADE_LOG_LEN {
+            if result.matched == 1 && (k as u64) == position {
+                ob.recent_trades[k] = record;
+            }
+        }
+        let count_increment = if result.matched == 1 { 1u64 } else { 0u64 };
+        ob.trade_count = ob.trade_count + count_increment;
+        ob
+    }
 
-        orderbook_ctxt.owner.from_arcis(depth)
+    // Add a fill to a user's stats, creating their entry on first fill
+    // (dropped once the table is full)
+    fn record_user_fill(mut ob: OrderBook, user: u128, amount: u64, slot: u64) -> OrderBook {
+        let mut credited = amount == 0 || user == 0;
+        for k in 0..MAX_TRACKED_USERS {
+            let is_user = ob.user_stats[k].user_id == user;
+            if is_user && !credited {
+                ob.user_stats[k].lifetime_volume = ob.user_stats[k].lifetime_volume + (amount as u128);
+                ob.user_stats[k].last_fill_slot = slot;
+                credited = true;
+            }
+        }
+        for k in 0..MAX_TRACKED_USERS {
+            let is_empty = ob.user_stats[k].user_id == 0;
+            if is_empty && !credited {
+                ob.user_stats[k].user_id = user;
+                ob.user_stats[k].lifetime_volume = amount as u128;
+                ob.user_stats[k].last_fill_slot = slot;
+                credited = true;
+            }
+        }
+        ob
+    }
+
+    // Whether `user_id` filled within the last match_cooldown_slots slots
+    fn user_cooling(ob: OrderBook, user_id: u128, config: MatchConfig) -> bool {
+        let mut cooling = false;
+        for k in 0..MAX_TRACKED_USERS {
+            let entry = ob.user_stats[k];
+            let is_owner = entry.user_id != 0 && entry.user_id == user_id;
+            let recent = entry.last_fill_slot + config.match_cooldown_slots > config.current_slot;
+            if config.match_cooldown_slots > 0 && is_owner && recent {
+                cooling = true;
+            }
+        }
+        cooling
+    }
+
+    // Per slot: whether the order's owner is still in its match cooldown
+    fn cooling_down(ob: OrderBook, config: MatchConfig) -> [bool; MAX_ORDERS] {
+        let mut cooling = [false; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            cooling[i] = user_cooling(ob, ob.orders[i].user_id, config);
+        }
+        cooling
+    }
+
+    // Cancel the OCO siblings (same user, same non-zero group) of every filled order
+    fn cancel_oco_siblings(mut ob: OrderBook, filled: [bool; MAX_ORDERS]) -> OrderBook {
+        for i in 0..MAX_ORDERS {
+            let leg = ob.orders[i];
+            let triggers = filled[i] && leg.oco_group != 0;
+            for j in 0..MAX_ORDERS {
+                let sibling = ob.orders[j];
+                let is_sibling = i != j
+                    && !filled[j]
+                    && sibling.active == 1
+                    && sibling.user_id == leg.user_id
+                    && sibling.oco_group == leg.oco_group;
+
+                if triggers && is_sibling {
+                    ob.orders[j].active = 0;
+                    ob.order_count = ob.order_count - 1;
+                }
+            }
+        }
+        ob
+    }
+
+    // Select and execute one crossing buy/sell pair (no-op when not enabled)
+    // Pairs involving a market order are always crossed before limit-only pairs,
+    // unless they would trade through the best opposite limit price. With lot
+    // segregation on, a pair of two round lots then beats any pair touching an
+    // odd lot. Orders of users still in their match cooldown are skipped.
+    // Within the same class, priority_policy 0 takes the first pair in slot
+    // order; policy 1 takes the best buy by price-time, then the best sell by
+    // price-time against it
+    // `taken` is how much each resting order has already given up in this
+    // computation; with max_take_per_maker set, a maker at the cap is skipped
+    fn match_once(
+        mut ob: OrderBook,
+        config: MatchConfig,
+        mut taken: [u64; MAX_ORDERS],
+        enabled: bool,
+    ) -> (OrderBook, MatchResult, [u64; MAX_ORDERS]) {
+        let mut result = empty_match_result();
+        let capped = config.max_take_per_maker > 0;
+
+        let mut found = false;
+        let mut maker_headroom = 0u64;
+        let mut buy_slot = 0u64;
+        let mut sell_slot = 0u64;
+        let top = top_of_book(ob);
+        let cooling = cooling_down(ob, config);
+        let mut best_has_market = false;
+        let mut best_round_pair = false;
+        let mut buy_order = empty_order();
+        let mut sell_order = empty_order();
+
+        // Find matching buy and sell orders
+        for i in 0..MAX_ORDERS {
+            for j in 0..MAX_ORDERS {
+                let candidate_buy = ob.orders[i];
+                let candidate_sell = ob.orders[j];
+
+                let has_market = candidate_buy.order_type == 0 || candidate_sell.order_type == 0;
+                let round_pair = is_round_lot(candidate_buy, config.round_lot_size)
+                    && is_round_lot(candidate_sell, config.round_lot_size);
+                let same_class = has_market == best_has_market;
+                let same_lot_class = same_class && round_pair == best_round_pair;
+                let same_buy = buy_slot == (i as u64);
+                let price_time_better = has_priority(candidate_buy, buy_order)
+                    || (same_buy && has_priority(candidate_sell, sell_order));
+                let better = !found
+                    || (has_market && !best_has_market)
+                    || (same_class && round_pair && !best_round_pair)
+                    || (config.priority_policy == 1 && same_lot_class && price_time_better);
+
+                let buyer_is_maker = candidate_buy.sequence < candidate_sell.sequence;
+                let maker_taken = if buyer_is_maker { taken[i] } else { taken[j] };
+                let under_cap = !capped || maker_taken < config.max_take_per_maker;
+
+                let crosses = can_cross(candidate_buy, candidate_sell, config, top)
+                    && !trades_through(candidate_buy, candidate_sell, config, top)
+                    && !cooling[i]
+                    && !cooling[j];
+
+                if crosses && under_cap && better {
+                    found = true;
+                    maker_headroom = if capped { config.max_take_per_maker - maker_taken } else { 0 };
+                    best_has_market = has_market;
+                    best_round_pair = round_pair;
+                    buy_order = candidate_buy;
+                    sell_order = candidate_sell;
+                    buy_slot = i as u64;
+                    sell_slot = j as u64;
+                }
+            }
+        }
+
+        // Calculate match amount (minimum of both orders, the maker's remaining
+        // per-call allowance and the depth fraction cap, floored to the fill
+        // increment; the rounded-off remainder keeps resting)
+        let match_amount = if buy_order.amount < sell_order.amount {
+            buy_order.amount
+        } else {
+            sell_order.amount
+        };
+        let match_amount = if capped && match_amount > maker_headroom { maker_headroom } else { match_amount };
+        let maker_side = if buy_order.sequence < sell_order.sequence { 0u8 } else { 1u8 };
+        let depth_cap = depth_fraction_cap(ob, maker_side, config.max_match_fraction_bps);
+        let depth_capped = config.max_match_fraction_bps > 0 && match_amount > depth_cap;
+        let match_amount = if depth_capped { depth_cap } else { match_amount };
+        let match_amount = floor_to_increment(match_amount, config.fill_increment);
+        let buyer_is_maker = buy_order.sequence < sell_order.sequence;
+        let maker_slot = if buyer_is_maker { buy_slot } else { sell_slot };
+        // Caps and the fill increment can still cut an all-or-none fill short
+        let partial_aon = (has_flag(buy_order, FLAG_ALL_OR_NONE) && match_amount < buy_order.amount)
+            || (has_flag(sell_order, FLAG_ALL_OR_NONE) && match_amount < sell_order.amount);
+
+        if enabled && found && match_amount > 0 && !partial_aon {
+            result.matched = 1;
+            result.match_price = cross_price(buy_order, sell_order, config, top);
+            result.scaled_price = scaled_cross_price(buy_order, sell_order, config, top);
+            result.price_scale = config.price_scale;
+            result.match_amount = match_amount;
+            result.buy_order_id = buy_order.order_id;
+            result.sell_order_id = sell_order.order_id;
+            result.taker_side = if buyer_is_maker { 1 } else { 0 };
+
+            ob = apply_fill(ob, buy_slot, sell_slot, match_amount);
+            ob = record_user_fill(ob, buy_order.user_id, match_amount, config.current_slot);
+            ob = record_user_fill(ob, sell_order.user_id, match_amount, config.current_slot);
+
+            let mut filled = [false; MAX_ORDERS];
+            for i in 0..MAX_ORDERS {
+                filled[i] = (i as u64) == buy_slot || (i as u64) == sell_slot;
+                if (i as u64) == maker_slot {
+                    taken[i] = taken[i] + match_amount;
+                }
+            }
+            ob = cancel_oco_siblings(ob, filled);
+        } else {
+            result = empty_match_result();
+        }
+
+        let ob = record_fill(ob, result, config);
+        let ob = record_trade(ob, result, buy_order.user_id, sell_order.user_id);
+
+        (ob, result, taken)
+    }
+
+    // Pro-rata fill of the newest crossing limit order (the taker) against every
+    // resting order at the opposite touch, in proportion to resting size. If the
+    // touch has no order that may make, nothing fills rather than trading
+    // through it at the next level.
+    // The fill and every share are floored to the fill increment, and integer
+    // division leaves a remainder; it goes to the maker whose rank (in slot
+    // order) equals seed % maker_count, floored to the increment and capped at
+    // that maker's unfilled size. Any part left over stays with the taker.
+    // With max_take_per_maker set, no maker's share (remainder included) exceeds it.
+    // Orders of users still in their match cooldown neither take nor make.
+    fn match_pro_rata(mut ob: OrderBook, config: MatchConfig, seed: u64, enabled: bool) -> (OrderBook, MatchResult) {
+        let mut result = empty_match_result();
+        let top = top_of_book(ob);
+        let cooling = cooling_down(ob, config);
+        let crossed = top.has_bid == 1
+            && top.has_ask == 1
+            && crosses_by(top.best_bid, top.best_ask, config);
+
+        // Select the taker: the most recently added crossing limit order
+        let mut taker = empty_order();
+        let mut taker_id = 0u64;
+        let mut has_taker = false;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_live_limit = order.active == 1
+                && order.order_type == 1
+                && !has_flag(order, FLAG_ALL_OR_NONE)
+                && !has_flag(order, FLAG_AUCTION_ONLY)
+                && is_activated(order, config)
+                && !cooling[i];
+            let crosses = if order.side == 0 {
+                crosses_by(order.price, top.best_ask, config)
+            } else {
+                crosses_by(top.best_bid, order.price, config)
+            };
+            let newer = !has_taker || order.sequence > taker.sequence;
+
+            if is_live_limit && crosses && newer {
+                taker = order;
+                taker_id = i as u64;
+                has_taker = true;
+            }
+        }
+
+        // Opposite-side orders allowed to make against the taker, and the best
+        // price among them
+        let mut eligible = [false; MAX_ORDERS];
+        let mut level_price = 0u64;
+        let mut has_level = false;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            eligible[i] = order.active == 1
+                && order.order_type == 1
+                && order.side != taker.side
+                && !same_owner(order, taker)
+                && !has_flag(order, FLAG_ALL_OR_NONE)
+                && !has_flag(order, FLAG_AUCTION_ONLY)
+                && is_activated(order, config)
+                && !cooling[i];
+            let better = !has_level
+                || (taker.side == 0 && order.price < level_price)
+                || (taker.side == 1 && order.price > level_price);
+
+            if eligible[i] && better {
+                level_price = order.price;
+                has_level = true;
+            }
+        }
+
+        // Trade-through protection: when the book's touch can't make (skipped,
+        // self-trade, cooling, ...), don't fill the taker at a worse level
+        let trades_through = if taker.side == 0 {
+            level_price > top.best_ask
+        } else {
+            level_price < top.best_bid
+        };
+
+        // Resting volume and maker count at that level
+        let mut level_volume = 0u64;
+        let mut maker_count = 0u64;
+        let mut is_maker_slot = [false; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_maker = eligible[i]
+                && order.price == level_price
+                && has_min_edge(order, level_price, top, config.price_offset);
+
+            if is_maker {
+                level_volume = level_volume + order.amount;
+                maker_count = maker_count + 1;
+            }
+            is_maker_slot[i] = is_maker;
+        }
+
+        let can_match = enabled && crossed && has_taker && has_level && !trades_through && maker_count > 0;
+        let fill = if taker.amount < level_volume { taker.amount } else { level_volume };
+        let maker_side = if taker.side == 0 { 1u8 } else { 0u8 };
+        let depth_cap = depth_fraction_cap(ob, maker_side, config.max_match_fraction_bps);
+        let depth_capped = config.max_match_fraction_bps > 0 && fill > depth_cap;
+        let fill = if depth_capped { depth_cap } else { fill };
+        let fill = floor_to_increment(fill, config.fill_increment);
+        let remainder_rank = if maker_count > 0 { seed % maker_count } else { 0 };
+
+        // Proportional shares
+        let mut allocated = 0u64;
+        let mut shares = [0u64; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            if is_maker_slot[i] && level_volume > 0 {
+                let share = ((fill as u128) * (order.amount as u128) / (level_volume as u128)) as u64;
+                let capped_share = config.max_take_per_maker > 0 && share > config.max_take_per_maker;
+                let share = if capped_share { config.max_take_per_maker } else { share };
+                shares[i] = floor_to_increment(share, config.fill_increment);
+                allocated = allocated + shares[i];
+            }
+        }
+
+        // Seeded remainder assignment
+        let remainder = fill - allocated;
+        let mut rank = 0u64;
+        let mut remainder_order_id = 0u64;
+        let mut remainder_user_id = 0u128;
+        for i in 0..MAX_ORDERS {
+            if is_maker_slot[i] {
+                if rank == remainder_rank {
+                    let limit = ob.orders[i].amount;
+                    let capped_limit = config.max_take_per_maker > 0 && limit > config.max_take_per_maker;
+                    let limit = if capped_limit { config.max_take_per_maker } else { limit };
+                    let headroom = limit - shares[i];
+                    let extra = if remainder < headroom { remainder } else { headroom };
+                    let extra = floor_to_increment(extra, config.fill_increment);
+                    shares[i] = shares[i] + extra;
+                    allocated = allocated + extra;
+                    remainder_order_id = ob.orders[i].order_id;
+                    remainder_user_id = ob.orders[i].user_id;
+                }
+                rank = rank + 1;
+            }
+        }
+
+        // A fraction cap that rounds down to nothing leaves no fill to report
+        if can_match && allocated > 0 {
+            let mut filled = [false; MAX_ORDERS];
+            for i in 0..MAX_ORDERS {
+                ob.orders[i].amount = ob.orders[i].amount - shares[i];
+                if is_maker_slot[i] && ob.orders[i].amount == 0 {
+                    ob.orders[i].active = 0;
+                    ob.order_count = ob.order_count - 1;
+                }
+                filled[i] = shares[i] > 0;
+                ob = record_user_fill(ob, ob.orders[i].user_id, shares[i], config.current_slot);
+            }
+            ob = record_user_fill(ob, taker.user_id, allocated, config.current_slot);
+            for i in 0..MAX_ORDERS {
+                if (i as u64) == taker_id {
+                    ob.orders[i].amount = ob.orders[i].amount - allocated;
+                    if ob.orders[i].amount == 0 {
+                        ob.orders[i].active = 0;
+                        ob.order_count = ob.order_count - 1;
+                    }
+                    filled[i] = allocated > 0;
+                }
+            }
+            ob = cancel_oco_siblings(ob, filled);
+
+            result.matched = 1;
+            result.match_price = level_price;
+            let scale = if config.price_scale > 0 { config.price_scale } else { 1 };
+            result.scaled_price = rescale_price((level_price as u128) * (scale as u128), scale, config.price_offset);
+            result.price_scale = config.price_scale;
+            result.match_amount = allocated;
+            result.buy_order_id = if taker.side == 0 { taker.order_id } else { remainder_order_id };
+            result.sell_order_id = if taker.side == 0 { remainder_order_id } else { taker.order_id };
+            result.taker_side = taker.side;
+        }
+
+        let ob = record_fill(ob, result, config);
+        let buy_user_id = if taker.side == 0 { taker.user_id } else { remainder_user_id };
+        let sell_user_id = if taker.side == 0 { remainder_user_id } else { taker.user_id };
+        let ob = record_trade(ob, result, buy_user_id, sell_user_id);
+
+        (ob, result)
+    }
+
+    // Whether some active market order has no active opposite-side order from
+    // another user (or trade group) to trade against
+    fn has_unfilled_market_order(ob: OrderBook) -> bool {
+        let mut unfilled = false;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_market = order.active == 1 && order.order_type == 0;
+
+            let mut has_liquidity = false;
+            for j in 0..MAX_ORDERS {
+                let other = ob.orders[j];
+                if other.active == 1 && other.side != order.side && !same_owner(other, order) {
+                    has_liquidity = true;
+                }
+            }
+
+            if is_market && !has_liquidity {
+                unfilled = true;
+            }
+        }
+        unfilled
+    }
+
+    // Match orders in encrypted order book
+    // match_mode: 0 = single price-time pair, 1 = pro-rata at the touch
+    // In pro-rata mode the reported sell/buy id on the maker side is the maker
+    // that received the seeded remainder
+    // The third output is revealed: true when a market order found no liquidity
+    // The two fill notices are encrypted to the buy and sell orders' own
+    // notify keys, so each party can read its fill and nobody else's. The last
+    // two outputs are revealed: whether the call filled, and the keeper reward
+    // taken out of the venue's net fees for it (0 when nothing filled or the
+    // fees can't cover keeper_reward)
+    #[instruction]
+    pub fn match_orders(
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        oracle_price: u64,
+        oracle_fresh: u8,
+        current_slot: u64,
+        max_fills_per_slot: u64,
+        rounding: u8,
+        match_mode: u8,
+        seed: u64,
+        fill_increment: u64,
+        maker_fee_bps: i64,
+        taker_fee_bps: u64,
+        fee_rounding: u8,
+        amount_unit: u64,
+        priority_policy: u8,
+        max_take_per_maker: u64,
+        spread_policy: u8,
+        round_lot_size: u64,
+        match_cooldown_slots: u64,
+        price_scale: u64,
+        cross_tolerance: u64,
+        pair_rules: u8,
+        price_offset: u64,
+        current_ts: u64,
+        price_source_policy: u8,
+        max_match_fraction_bps: u64,
+        lock_policy: u8,
+        activation_delay_slots: u64,
+        dust_policy: u8,
+        lot_size: u64,
+        min_notional: u64,
+        keeper_reward: u64,
+    ) -> (
+        Enc<Mxe, OrderBook>,
+        Enc<Mxe, MatchResult>,
+        Enc<Shared, FillNotice>,
+        Enc<Shared, FillNotice>,
+        bool,
+        bool,
+        u64,
+    ) {
+        let config = MatchConfig {
+            oracle_price,
+            oracle_fresh,
+            rounding,
+            fill_increment,
+            maker_fee_bps,
+            taker_fee_bps,
+            fee_rounding,
+            amount_unit,
+            priority_policy,
+            max_take_per_maker,
+            spread_policy,
+            round_lot_size,
+            current_slot,
+            match_cooldown_slots,
+            price_scale,
+            cross_tolerance,
+            pair_rules,
+            price_offset,
+            current_ts,
+            price_source_policy,
+            max_match_fraction_bps,
+            lock_policy,
+            activation_delay_slots,
+            auction: false,
+        };
+        let prev = orderbook_ctxt.to_arcis();
+        let ob = roll_fill_window(prev, current_slot);
+
+        let under_slot_cap = ob.fills_this_slot < max_fills_per_slot;
+        let (ob, result) = if match_mode == 1 {
+            match_pro_rata(ob, config, seed, under_slot_cap)
+        } else {
+            let (ob, result, _) = match_once(ob, config, [0; MAX_ORDERS], under_slot_cap);
+            (ob, result)
+        };
+        let ob = if dust_policy == 1 {
+            cancel_dust(ob, result, lot_size, min_notional, config)
+        } else {
+            ob
+        };
+        let market_unfilled = has_unfilled_market_order(ob);
+        let filled = result.matched == 1;
+        let (ob, keeper_paid) = pay_keeper(ob, filled, keeper_reward);
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        let (buy_notice, buy_key) = fill_notice(ob, result, result.buy_order_id);
+        let (sell_notice, sell_key) = fill_notice(ob, result, result.sell_order_id);
+
+        let updated_ob = orderbook_ctxt.owner.from_arcis(ob);
+        let match_result = orderbook_ctxt.owner.from_arcis(result);
+        let buy_notice = Shared::new(buy_key).from_arcis(buy_notice);
+        let sell_notice = Shared::new(sell_key).from_arcis(sell_notice);
+
+        (
+            updated_ob,
+            match_result,
+            buy_notice,
+            sell_notice,
+            market_unfilled.reveal(),
+            filled.reveal(),
+            keeper_paid.reveal(),
+        )
+    }
+
+    // One party's view of a fill, encrypted to that order's notify_key
+    #[derive(Copy, Clone)]
+    pub struct FillNotice {
+        pub filled: u8,
+        pub order_id: u64,
+        pub fill_amount: u64,
+        pub fill_price: u64,
+        pub remaining: u64, // amount still resting after the fill
+    }
+
+    // The notice for one side of `result` and the key to encrypt it to. An
+    // order without a notify_key gets an empty notice, since the placeholder
+    // key isn't a real recipient
+    fn fill_notice(ob: OrderBook, result: MatchResult, order_id: u64) -> (FillNotice, ArcisX25519Pubkey) {
+        let mut notice = FillNotice {
+            filled: 0,
+            order_id: 0,
+            fill_amount: 0,
+            fill_price: 0,
+            remaining: 0,
+        };
+        let mut key = no_notify_key();
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_party = result.matched == 1 && order.order_id == order_id;
+            let has_key = order.notify_key != no_notify_key();
+            if is_party && has_key {
+                notice = FillNotice {
+                    filled: 1,
+                    order_id,
+                    fill_amount: result.match_amount,
+                    fill_price: result.match_price,
+                    remaining: order.amount,
+                };
+                key = order.notify_key;
+            }
+        }
+        (notice, key)
+    }
+
+    // Take the keeper reward for a filling call out of the venue's net fees.
+    // Pays all of it or nothing, so rebates owed to makers are never touched
+    fn pay_keeper(mut ob: OrderBook, filled: bool, keeper_reward: u64) -> (OrderBook, u64) {
+        let covered = net_fees(ob) >= keeper_reward as u128;
+        let paid = if filled && covered { keeper_reward } else { 0 };
+        ob.fees_collected = ob.fees_collected - paid as u128;
+        (ob, paid)
+    }
+
+    // Cancel either order of a fill whose remainder is dust: below lot_size, or
+    // for a limit order worth less than min_notional (0 turns either test off).
+    // Leaving the book releases the collateral the remainder reserved
+    fn cancel_dust(mut ob: OrderBook, result: MatchResult, lot_size: u64, min_notional: u64, config: MatchConfig) -> OrderBook {
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let in_fill = result.matched == 1
+                && (order.order_id == result.buy_order_id || order.order_id == result.sell_order_id);
+            let notional = mul_div(price_magnitude(order.price, config.price_offset), order.amount, config.amount_unit);
+            let below_lot = lot_size > 0 && order.amount < lot_size;
+            let below_notional = order.order_type == 1 && min_notional > 0 && notional < min_notional;
+            if order.active == 1 && in_fill && (below_lot || below_notional) {
+                ob.orders[i].active = 0;
+                ob.order_count = ob.order_count - 1;
+            }
+        }
+        ob
+    }
+
+    // Aggregate outcome of one match_orders_batch call
+    #[derive(Copy, Clone)]
+    pub struct BatchSummary {
+        pub fills: u64,
+        pub volume: u64,
+        pub vwap: u64, // volume-weighted match price, 0 when nothing filled
+    }
+
+    fn batch_summary(results: [MatchResult; MAX_BATCH_FILLS]) -> BatchSummary {
+        let mut fills = 0u64;
+        let mut volume = 0u128;
+        let mut notional = 0u128;
+        for k in 0..MAX_BATCH_FILLS {
+            let result = results[k];
+            if result.matched == 1 {
+                fills = fills + 1;
+                volume = volume + (result.match_amount as u128);
+                notional = notional + (result.match_price as u128) * (result.match_amount as u128);
+            }
+        }
+
+        let divisor = if volume > 0 { volume } else { 1 };
+        BatchSummary {
+            fills,
+            volume: if volume > (u64::MAX as u128) { u64::MAX } else { volume as u64 },
+            vwap: (notional / divisor) as u64,
+        }
+    }
+
+    // One order's fills across a batch, netted into a single settlement entry
+    #[derive(Copy, Clone)]
+    pub struct NetFill {
+        pub present: u8,
+        pub order_id: u64,
+        pub side: u8,
+        pub amount: u64,
+        pub notional: u64, // sum of price * amount / amount_unit over the order's fills
+    }
+
+    // Add one leg of a fill to the netted entries: onto the order's existing
+    // entry, or into the first free one
+    fn net_leg(
+        mut net: [NetFill; MAX_NET_FILLS],
+        matched: bool,
+        order_id: u64,
+        side: u8,
+        amount: u64,
+        notional: u64,
+    ) -> [NetFill; MAX_NET_FILLS] {
+        let mut placed = !matched;
+        for e in 0..MAX_NET_FILLS {
+            if !placed && net[e].present == 1 && net[e].order_id == order_id {
+                net[e].amount = net[e].amount + amount;
+                net[e].notional = net[e].notional + notional;
+                placed = true;
+            }
+        }
+        for e in 0..MAX_NET_FILLS {
+            if !placed && net[e].present == 0 {
+                net[e] = NetFill { present: 1, order_id, side, amount, notional };
+                placed = true;
+            }
+        }
+        net
+    }
+
+    // Net a batch's fills into one entry per order, so a taker crossing several
+    // makers settles once
+    fn net_fills(results: [MatchResult; MAX_BATCH_FILLS], config: MatchConfig) -> [NetFill; MAX_NET_FILLS] {
+        let mut net = [NetFill { present: 0, order_id: 0, side: 0, amount: 0, notional: 0 }; MAX_NET_FILLS];
+        for k in 0..MAX_BATCH_FILLS {
+            let result = results[k];
+            let matched = result.matched == 1;
+            let price = price_magnitude(result.match_price, config.price_offset);
+            let notional = mul_div(price, result.match_amount, config.amount_unit);
+            net = net_leg(net, matched, result.buy_order_id, 0, result.match_amount, notional);
+            net = net_leg(net, matched, result.sell_order_id, 1, result.match_amount, notional);
+        }
+        net
+    }
+
+    // Match up to MAX_BATCH_FILLS pairs in one computation
+    // Fills are capped by both the per-call limit and the per-slot fill counter
+    #[instruction]
+    pub fn match_orders_batch(
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        oracle_price: u64,
+        oracle_fresh: u8,
+        current_slot: u64,
+        max_fills_this_call: u64,
+        max_fills_per_slot: u64,
+        rounding: u8,
+        fill_increment: u64,
+        maker_fee_bps: i64,
+        taker_fee_bps: u64,
+        fee_rounding: u8,
+        amount_unit: u64,
+        priority_policy: u8,
+        max_take_per_maker: u64,
+        spread_policy: u8,
+        round_lot_size: u64,
+        match_cooldown_slots: u64,
+        price_scale: u64,
+        cross_tolerance: u64,
+        pair_rules: u8,
+        price_offset: u64,
+        current_ts: u64,
+        price_source_policy: u8,
+        max_match_fraction_bps: u64,
+        lock_policy: u8,
+        activation_delay_slots: u64,
+    ) -> (
+        Enc<Mxe, OrderBook>,
+        Enc<Mxe, [MatchResult; MAX_BATCH_FILLS]>,
+        Enc<Mxe, [NetFill; MAX_NET_FILLS]>,
+        Enc<Mxe, BatchSummary>,
+    ) {
+        let config = MatchConfig {
+            oracle_price,
+            oracle_fresh,
+            rounding,
+            fill_increment,
+            maker_fee_bps,
+            taker_fee_bps,
+            fee_rounding,
+            amount_unit,
+            priority_policy,
+            max_take_per_maker,
+            spread_policy,
+            round_lot_size,
+            current_slot,
+            match_cooldown_slots,
+            price_scale,
+            cross_tolerance,
+            pair_rules,
+            price_offset,
+            current_ts,
+            price_source_policy,
+            max_match_fraction_bps,
+            lock_policy,
+            activation_delay_slots,
+            auction: false,
+        };
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = roll_fill_window(prev, current_slot);
+        let mut results = [empty_match_result(); MAX_BATCH_FILLS];
+        let mut fills = 0u64;
+        let mut taken = [0u64; MAX_ORDERS];
+
+        for k in 0..MAX_BATCH_FILLS {
+            let under_call_cap = fills < max_fills_this_call;
+            let under_slot_cap = ob.fills_this_slot < max_fills_per_slot;
+
+            let (next_ob, result, next_taken) = match_once(ob, config, taken, under_call_cap && under_slot_cap);
+            ob = next_ob;
+            taken = next_taken;
+            results[k] = result;
+
+            let fill_increment = if result.matched == 1 { 1u64 } else { 0u64 };
+            fills = fills + fill_increment;
+        }
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        let net = net_fills(results, config);
+        let summary = batch_summary(results);
+
+        let updated_ob = orderbook_ctxt.owner.from_arcis(ob);
+        let match_results = orderbook_ctxt.owner.from_arcis(results);
+        let net_settlements = orderbook_ctxt.owner.from_arcis(net);
+        let batch_summary = orderbook_ctxt.owner.from_arcis(summary);
+
+        (updated_ob, match_results, net_settlements, batch_summary)
+    }
+
+    // Aggregate outcome of a crank over one matching window
+    #[derive(Copy, Clone)]
+    pub struct CrankSummary {
+        pub fills: u64,
+        pub volume: u64,
+    }
+
+    // Clear the crossing pairs accumulated during the matching window at once,
+    // up to MAX_CRANK_FILLS fills. Capped or increment-floored fills can leave
+    // both orders resting, so a deeply crossed book may need the next window's
+    // crank to finish clearing. The window is the fairness unit, so neither
+    // the per-slot fill cap nor the per-user cooldown applies
+    #[instruction]
+    pub fn crank_match(
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        oracle_price: u64,
+        oracle_fresh: u8,
+        current_slot: u64,
+        rounding: u8,
+        fill_increment: u64,
+        maker_fee_bps: i64,
+        taker_fee_bps: u64,
+        fee_rounding: u8,
+        amount_unit: u64,
+        priority_policy: u8,
+        spread_policy: u8,
+        round_lot_size: u64,
+        price_scale: u64,
+        cross_tolerance: u64,
+        pair_rules: u8,
+        price_offset: u64,
+        current_ts: u64,
+        price_source_policy: u8,
+        max_match_fraction_bps: u64,
+        lock_policy: u8,
+        activation_delay_slots: u64,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Mxe, CrankSummary>) {
+        let config = MatchConfig {
+            oracle_price,
+            oracle_fresh,
+            rounding,
+            fill_increment,
+            maker_fee_bps,
+            taker_fee_bps,
+            fee_rounding,
+            amount_unit,
+            priority_policy,
+            max_take_per_maker: 0,
+            spread_policy,
+            round_lot_size,
+            current_slot,
+            match_cooldown_slots: 0,
+            price_scale,
+            cross_tolerance,
+            pair_rules,
+            price_offset,
+            current_ts,
+            price_source_policy,
+            max_match_fraction_bps,
+            lock_policy,
+            activation_delay_slots,
+            auction: true,
+        };
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = roll_fill_window(prev, current_slot);
+        let mut summary = CrankSummary { fills: 0, volume: 0 };
+        let taken = [0u64; MAX_ORDERS];
+
+        for _k in 0..MAX_CRANK_FILLS {
+            let (next_ob, result, _) = match_once(ob, config, taken, true);
+            ob = next_ob;
+
+            if result.matched == 1 {
+                summary.fills = summary.fills + 1;
+                summary.volume = summary.volume + result.match_amount;
+            }
+        }
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        let updated_ob = orderbook_ctxt.owner.from_arcis(ob);
+        let summary = orderbook_ctxt.owner.from_arcis(summary);
+
+        (updated_ob, summary)
+    }
+
+    // Whether an order has rested at least min_rest_slots (0 = no minimum)
+    fn has_rested(order: Order, current_slot: u64, min_rest_slots: u64) -> bool {
+        current_slot >= order.created_slot + min_rest_slots
+    }
+
+    // New price and size for a resting order, encrypted by its owner
+    #[derive(Copy, Clone)]
+    pub struct Amendment {
+        pub user_id: u128,
+        pub price: u64,
+        pub amount: u64,
+    }
+
+    // Amend the price and size of a user's active order
+    // Reducing the size or moving the price away from the touch keeps queue
+    // priority; growing the size or pricing more aggressively takes a new
+    // sequence (back of the queue). An amount of 0 cancels the order. With
+    // reduce_only set (a draining book) an amendment that grows the size or
+    // reprices toward the touch is ignored
+    #[instruction]
+    pub fn modify_order(
+        order_id: u64,
+        tick_size: u64,
+        current_slot: u64,
+        reduce_only: u8,
+        amendment_ctxt: Enc<Shared, Amendment>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Mxe, OrderBook> {
+        let amendment = amendment_ctxt.to_arcis();
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = prev;
+
+        let mut requeued = false;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let grows = amendment.amount > order.amount;
+            let more_aggressive = if order.side == 0 {
+                amendment.price > order.price
+            } else {
+                amendment.price < order.price
+            };
+            let is_target = order.order_id == order_id
+                && order.user_id == amendment.user_id
+                && order.active == 1
+                && !(reduce_only == 1 && (grows || more_aggressive));
+            let closes = amendment.amount == 0;
+
+            if is_target {
+                ob.orders[i].price = amendment.price;
+                ob.orders[i].priority_price = priority_price(amendment.price, order.side, tick_size);
+                ob.orders[i].amount = amendment.amount;
+                if grows || more_aggressive {
+                    ob.orders[i].sequence = ob.next_sequence;
+                    requeued = true;
+                }
+                if closes {
+                    ob.orders[i].active = 0;
+                    ob.order_count = ob.order_count - 1;
+                }
+            }
+        }
+        let sequence_increment = if requeued { 1u64 } else { 0u64 };
+        ob.next_sequence = ob.next_sequence + sequence_increment;
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Cancel order from encrypted order book
+    // Orders younger than min_rest_slots are left in place
+    #[instruction]
+    pub fn cancel_order(
+        order_id: u64,
+        current_slot: u64,
+        min_rest_slots: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Mxe, OrderBook> {
+        let user = user_id.to_arcis();
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = prev;
+
+        // Find and cancel order if user matches
+        for i in 0..MAX_ORDERS {
+            let is_target_order = ob.orders[i].order_id == order_id;
+            let is_owner = ob.orders[i].user_id == user;
+            let is_active = ob.orders[i].active == 1;
+            
+            let rested = has_rested(ob.orders[i], current_slot, min_rest_slots);
+
+            let should_cancel = is_target_order && is_owner && is_active && rested;
+
+            if should_cancel {
+                ob.orders[i].active = 0;
+                ob.order_count = ob.order_count - 1;
+            }
+        }
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Cancel a list of orders in one computation
+    // Ids not owned by the caller, inactive ids, orders younger than
+    // min_rest_slots and padding (u64::MAX) are ignored
+    #[instruction]
+    pub fn cancel_orders_by_ids(
+        order_ids: [u64; MAX_CANCEL_IDS],
+        current_slot: u64,
+        min_rest_slots: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Mxe, OrderBook> {
+        let user = user_id.to_arcis();
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = prev;
+
+        for i in 0..MAX_ORDERS {
+            let mut is_listed = false;
+            for k in 0..MAX_CANCEL_IDS {
+                is_listed = is_listed || order_ids[k] == ob.orders[i].order_id;
+            }
+            let is_owner = ob.orders[i].user_id == user;
+            let is_active = ob.orders[i].active == 1;
+
+            let rested = has_rested(ob.orders[i], current_slot, min_rest_slots);
+
+            let should_cancel = is_listed && is_owner && is_active && rested;
+
+            if should_cancel {
+                ob.orders[i].active = 0;
+                ob.order_count = ob.order_count - 1;
+            }
+        }
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Aggregate resting limit volume into depth buckets by price level, best
+    // first: bids in 0..10 and asks in 10..20 (the sorted ladder's levels;
+    // unused levels are 0)
+    fn depth_buckets(ob: OrderBook) -> [u64; DEPTH_BUCKETS] {
+        let (_, bid_volumes) = ladder_side(ob, 0);
+        let (_, ask_volumes) = ladder_side(ob, 1);
+
+        let mut depth: [u64; DEPTH_BUCKETS] = [0; DEPTH_BUCKETS];
+        for k in 0..LADDER_LEVELS {
+            depth[k] = bid_volumes[k];
+            depth[LADDER_LEVELS + k] = ask_volumes[k];
+        }
+
+        depth
+    }
+
+    // Emergency kill-switch: cancel every order of every user
+    // Each slot yields a cancellation record (cancelled = 0 for empty slots)
+    #[instruction]
+    pub fn emergency_cancel_all(
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        current_slot: u64,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Mxe, [CancelRecord; MAX_ORDERS]>) {
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = prev;
+        let mut records = [CancelRecord {
+            cancelled: 0,
+            user_id: 0,
+            side: 0,
+            price: 0,
+            amount: 0,
+        }; MAX_ORDERS];
+
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_active = order.active == 1;
+
+            if is_active {
+                records[i].cancelled = 1;
+                records[i].user_id = order.user_id;
+                records[i].side = order.side;
+                records[i].price = order.price;
+                records[i].amount = order.amount;
+            }
+            ob.orders[i].active = 0;
+        }
+        ob.order_count = 0;
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        let updated_ob = orderbook_ctxt.owner.from_arcis(ob);
+        let cancel_records = orderbook_ctxt.owner.from_arcis(records);
+
+        (updated_ob, cancel_records)
+    }
+
+    // Same bucketing as depth_buckets, in notional (price * volume) per level
+    // Amounts are fixed-point with `amount_unit` = 10^amount_scale base units per lot
+    fn quote_depth_buckets(ob: OrderBook, amount_unit: u64, price_offset: u64) -> [u64; DEPTH_BUCKETS] {
+        let (bid_prices, bid_volumes) = ladder_side(ob, 0);
+        let (ask_prices, ask_volumes) = ladder_side(ob, 1);
+
+        let mut depth: [u64; DEPTH_BUCKETS] = [0; DEPTH_BUCKETS];
+        for k in 0..LADDER_LEVELS {
+            depth[k] = mul_div(price_magnitude(bid_prices[k], price_offset), bid_volumes[k], amount_unit);
+            depth[LADDER_LEVELS + k] = mul_div(price_magnitude(ask_prices[k], price_offset), ask_volumes[k], amount_unit);
+        }
+
+        depth
+    }
+
+    // Get order book depth (privacy-preserving aggregation)
+    #[instruction]
+    pub fn get_orderbook_depth(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        price_levels: u64,
+    ) -> Enc<Shared, [u64; 20]> {
+        let depth = depth_buckets(orderbook_ctxt.to_arcis());
+
+        receiver.from_arcis(depth)
+    }
+
+    // Get depth buckets start..=end only (bounds validated by the program)
+    // Slots past the requested range are zero
+    #[instruction]
+    pub fn get_depth_range(
+        start: u64,
+        end: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [u64; MAX_DEPTH_RANGE]> {
+        let depth = depth_buckets(orderbook_ctxt.to_arcis());
+        let mut range: [u64; MAX_DEPTH_RANGE] = [0; MAX_DEPTH_RANGE];
+
+        for k in 0..MAX_DEPTH_RANGE {
+            let bucket = start + (k as u64);
+            for b in 0..DEPTH_BUCKETS {
+                if (b as u64) == bucket && bucket <= end {
+                    range[k] = depth[b];
+                }
+            }
+        }
+
+        receiver.from_arcis(range)
+    }
+
+    // Get queue position of a user's order (number of same-side orders ahead of it)
+    // Returns u64::MAX if the order isn't an active order owned by the caller
+    #[instruction]
+    pub fn get_queue_position(
+        order_id: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u64> {
+        let user = user_id.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        // Select the target order obliviously
+        let mut target = ob.orders[0];
+        let mut found = 0u8;
+        for i in 0..MAX_ORDERS {
+            let is_target_order = ob.orders[i].order_id == order_id;
+            let is_owner = ob.orders[i].user_id == user;
+            let is_active = ob.orders[i].active == 1;
+
+            if is_target_order && is_owner && is_active {
+                target = ob.orders[i];
+                found = 1;
+            }
+        }
+
+        // Count active same-side orders with strictly better priority
+        let mut position = 0u64;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let same_side = order.side == target.side;
+            let is_active = order.active == 1;
+
+            if same_side && is_active && has_priority(order, target) {
+                position = position + 1;
+            }
+        }
+
+        let result = if found == 1 { position } else { u64::MAX };
+
+        user_id.owner.from_arcis(result)
+    }
+
+    // Get status of a user's order
+    // Returns an all-zero status (active = 0) if the caller doesn't own the order
+    #[instruction]
+    pub fn get_order_status(
+        order_id: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, OrderStatus> {
+        let user = user_id.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut status = OrderStatus {
+            active: 0,
+            price: 0,
+            amount: 0,
+            side: 0,
+            order_type: 0,
+            metadata: 0,
+            flags: 0,
+        };
+
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_target_order = ob.orders[i].order_id == order_id;
+            let is_owner = order.user_id == user;
+
+            if is_target_order && is_owner {
+                status.active = order.active;
+                status.price = order.price;
+                status.amount = order.amount;
+                status.side = order.side;
+                status.order_type = order.order_type;
+                status.metadata = order.metadata;
+                status.flags = order.flags;
+            }
+        }
+
+        user_id.owner.from_arcis(status)
+    }
+
+    // Check whether the book is crossed (best bid >= best ask, both sides non-empty)
+    #[instruction]
+    pub fn is_crossed(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u8> {
+        let top = top_of_book(orderbook_ctxt.to_arcis());
+
+        let both_sides = top.has_bid == 1 && top.has_ask == 1;
+        let crossed = if both_sides && top.best_bid >= top.best_ask { 1u8 } else { 0u8 };
+
+        receiver.from_arcis(crossed)
+    }
+
+    // Get the quoted spread in bps of mid: (best_ask - best_bid) * 10000 / mid
+    // Returns 0 for an empty side, a zero mid or a crossed book
+    #[instruction]
+    pub fn get_effective_spread_bps(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u64> {
+        let top = top_of_book(orderbook_ctxt.to_arcis());
+
+        let both_sides = top.has_bid == 1 && top.has_ask == 1;
+        let not_crossed = top.best_ask >= top.best_bid;
+        let spread = if not_crossed { top.best_ask - top.best_bid } else { 0 };
+        let mid = top.best_bid / 2 + top.best_ask / 2 + (top.best_bid % 2 + top.best_ask % 2) / 2;
+
+        let spread_bps = if both_sides && not_crossed { mul_div(spread, 10000, mid) } else { 0 };
+
+        receiver.from_arcis(spread_bps)
+    }
+
+    // Get quote-denominated order book depth (per-bucket notional)
+    #[instruction]
+    pub fn get_orderbook_depth_quote(
+        amount_unit: u64,
+        price_offset: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [u64; 20]> {
+        let depth = quote_depth_buckets(orderbook_ctxt.to_arcis(), amount_unit, price_offset);
+
+        receiver.from_arcis(depth)
+    }
+
+    // Add a user to the market allow-list (no-op if already listed or full)
+    #[instruction]
+    pub fn add_to_allowlist(
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Mxe, OrderBook> {
+        let user = user_id.to_arcis();
+        let mut ob = orderbook_ctxt.to_arcis();
+
+        let mut added = is_allowlisted(ob, user) || user == 0;
+        for k in 0..MAX_ALLOWLIST {
+            let is_empty = ob.allowlist[k] == 0;
+            if is_empty && !added {
+                ob.allowlist[k] = user;
+                added = true;
+            }
+        }
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Remove a user from the market allow-list; resting orders are left in place
+    #[instruction]
+    pub fn remove_from_allowlist(
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Mxe, OrderBook> {
+        let user = user_id.to_arcis();
+        let mut ob = orderbook_ctxt.to_arcis();
+
+        for k in 0..MAX_ALLOWLIST {
+            if ob.allowlist[k] == user {
+                ob.allowlist[k] = 0;
+            }
+        }
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Get the price and amount of the most recent fill (zero before any trade)
+    #[instruction]
+    pub fn get_last_trade(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, LastTrade> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let last_trade = LastTrade {
+            price: ob.last_trade_price,
+            amount: ob.last_trade_amount,
+        };
+
+        receiver.from_arcis(last_trade)
+    }
+
+    // Get (active_slots, highest_active_index) so a keeper can judge whether
+    // compaction pays off; the index is 0 when the book is empty
+    #[instruction]
+    pub fn get_fragmentation(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, (u64, u64)> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut active_slots = 0u64;
+        let mut highest_active_index = 0u64;
+        for i in 0..MAX_ORDERS {
+            let is_active = ob.orders[i].active == 1;
+            if is_active {
+                active_slots = active_slots + 1;
+                highest_active_index = i as u64;
+            }
+        }
+
+        receiver.from_arcis((active_slots, highest_active_index))
+    }
+
+    // Price of the last level consumed by an incoming `side` order sweeping
+    // `target_volume` (a buy sweeps asks upward, a sell sweeps bids downward)
+    // Returns 0 when the opposite side cannot fill the whole volume
+    #[instruction]
+    pub fn get_price_at_volume(
+        target_volume: u64,
+        side: u8,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u64> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut price = 0u64;
+        let mut found = false;
+        for i in 0..MAX_ORDERS {
+            let level = ob.orders[i];
+            let is_opposite = level.active == 1 && level.order_type == 1 && level.side != side;
+
+            // Opposite volume at or better than this level
+            let mut cumulative = 0u64;
+            for j in 0..MAX_ORDERS {
+                let order = ob.orders[j];
+                let at_or_better = if side == 0 { order.price <= level.price } else { order.price >= level.price };
+                if order.active == 1 && order.order_type == 1 && order.side != side && at_or_better {
+                    cumulative = cumulative + order.amount;
+                }
+            }
+
+            let better = if side == 0 { level.price < price } else { level.price > price };
+            if is_opposite && cumulative >= target_volume && (!found || better) {
+                price = level.price;
+                found = true;
+            }
+        }
+
+        receiver.from_arcis(price)
+    }
+
+    // Fillable ratio of a user's resting limit order, in bps: opposite-side
+    // volume at or better than its price over its remaining size
+    // (10000 = fully fillable now, 0 when the order is not found)
+    #[instruction]
+    pub fn get_fill_likelihood(
+        order_id: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u64> {
+        let user = user_id.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut target = empty_order();
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_target_order = order.order_id == order_id && order.active == 1;
+            if is_target_order && order.user_id == user {
+                target = order;
+            }
+        }
+
+        let mut opposite_volume = 0u64;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_opposite = order.active == 1 && order.order_type == 1 && order.side != target.side;
+            let at_or_better = if target.side == 0 { order.price <= target.price } else { order.price >= target.price };
+            if is_opposite && at_or_better {
+                opposite_volume = opposite_volume + order.amount;
+            }
+        }
+
+        let ratio_bps = if target.active == 1 { mul_div(opposite_volume, 10000, target.amount) } else { 0 };
+
+        user_id.owner.from_arcis(ratio_bps)
+    }
+
+    // Count active orders (not volume) per price bucket, buys 0..10 and sells
+    // 10..20. Bucket k of a side covers [base_price + k * bucket_width,
+    // base_price + (k + 1) * bucket_width); prices past either end are clamped
+    // into the first or last bucket
+    #[instruction]
+    pub fn get_active_distribution(
+        base_price: u64,
+        bucket_width: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [u64; 20]> {
+        let ob = orderbook_ctxt.to_arcis();
+        let half = (DEPTH_BUCKETS / 2) as u64;
+
+        let mut counts: [u64; DEPTH_BUCKETS] = [0; DEPTH_BUCKETS];
+        for j in 0..MAX_ORDERS {
+            let order = ob.orders[j];
+            let offset = if order.price > base_price { order.price - base_price } else { 0 };
+            let raw_bucket = if bucket_width > 0 { offset / bucket_width } else { 0 };
+            let bucket = if raw_bucket < half { raw_bucket } else { half - 1 };
+            let index = if order.side == 0 { bucket } else { bucket + half };
+
+            for k in 0..DEPTH_BUCKETS {
+                if order.active == 1 && (k as u64) == index {
+                    counts[k] = counts[k] + 1;
+                }
+            }
+        }
+
+        receiver.from_arcis(counts)
+    }
+
+    // Get the order changes logged at or after `book_version`
+    #[instruction]
+    pub fn get_book_diff(
+        book_version: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, BookDiff> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut diff = BookDiff {
+            entries: [empty_change(); CHANGE_LOG_LEN],
+            current_version: ob.book_version,
+            complete: 0,
+        };
+
+        for k in 0..CHANGE_LOG_LEN {
+            let entry = ob.change_log[k];
+            let is_newer = entry.kind != 0 && entry.version >= book_version;
+            if is_newer {
+                diff.entries[k] = entry;
+            }
+        }
+
+        let retained = ob.book_version <= book_version + (CHANGE_LOG_LEN as u64);
+        diff.complete = if retained { 1 } else { 0 };
+
+        receiver.from_arcis(diff)
+    }
+
+    // Get a user's order's time-priority rank on its side (number of active
+    // same-side orders that arrived earlier, at any price)
+    // Returns u64::MAX if the order isn't an active order owned by the caller
+    #[instruction]
+    pub fn get_time_rank(
+        order_id: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u64> {
+        let user = user_id.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut target = ob.orders[0];
+        let mut found = 0u8;
+        for i in 0..MAX_ORDERS {
+            let is_target_order = ob.orders[i].order_id == order_id;
+            let is_owner = ob.orders[i].user_id == user;
+            let is_active = ob.orders[i].active == 1;
+
+            if is_target_order && is_owner && is_active {
+                target = ob.orders[i];
+                found = 1;
+            }
+        }
+
+        let mut rank = 0u64;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let same_side = order.side == target.side;
+            let is_active = order.active == 1;
+
+            if same_side && is_active && order.sequence < target.sequence {
+                rank = rank + 1;
+            }
+        }
+
+        let result = if found == 1 { rank } else { u64::MAX };
+
+        user_id.owner.from_arcis(result)
+    }
+
+    // Whether a user has any active order on the book
+    fn has_active_orders(ob: OrderBook, user: u128) -> bool {
+        let mut found = false;
+        for i in 0..MAX_ORDERS {
+            found = found || (ob.orders[i].active == 1 && ob.orders[i].user_id == user);
+        }
+        found
+    }
+
+    // Refresh a user's heartbeat, enrolling them in cancel-on-disconnect on the
+    // first call. Entries of other users with no active orders are freed first,
+    // so the table only fills up with users who have something to cancel.
+    // Returns 1 to the caller if the heartbeat was recorded, 0 if the table is
+    // still full
+    #[instruction]
+    pub fn heartbeat(
+        now: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Shared, u8>) {
+        let user = user_id.to_arcis();
+        let mut ob = orderbook_ctxt.to_arcis();
+
+        for k in 0..MAX_HEARTBEAT_USERS {
+            let entry = ob.heartbeat_users[k];
+            let idle = entry != 0 && entry != user && !has_active_orders(ob, entry);
+            if idle {
+                ob.heartbeat_users[k] = 0;
+                ob.heartbeat_ts[k] = 0;
+            }
+        }
+
+        let mut enrolled = false;
+        for k in 0..MAX_HEARTBEAT_USERS {
+            let is_user = ob.heartbeat_users[k] == user;
+            if is_user {
+                ob.heartbeat_ts[k] = now;
+                enrolled = true;
+            }
+        }
+
+        let mut added = enrolled || user == 0;
+        for k in 0..MAX_HEARTBEAT_USERS {
+            let is_empty = ob.heartbeat_users[k] == 0;
+            if is_empty && !added {
+                ob.heartbeat_users[k] = user;
+                ob.heartbeat_ts[k] = now;
+                added = true;
+            }
+        }
+        let recorded = if added && user != 0 { 1u8 } else { 0u8 };
+
+        (orderbook_ctxt.owner.from_arcis(ob), user_id.owner.from_arcis(recorded))
+    }
+
+    // Cancel every active order of enrolled users whose last heartbeat is more
+    // than `ttl` seconds before `now`, and drop them from the table
+    #[instruction]
+    pub fn cancel_stale_users(
+        now: u64,
+        ttl: u64,
+        current_slot: u64,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Mxe, OrderBook> {
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = prev;
+
+        let mut stale = [false; MAX_HEARTBEAT_USERS];
+        for k in 0..MAX_HEARTBEAT_USERS {
+            let age = if now > ob.heartbeat_ts[k] { now - ob.heartbeat_ts[k] } else { 0 };
+            stale[k] = ob.heartbeat_users[k] != 0 && age > ttl;
+        }
+
+        for i in 0..MAX_ORDERS {
+            let mut is_stale_user = false;
+            for k in 0..MAX_HEARTBEAT_USERS {
+                is_stale_user = is_stale_user || (stale[k] && ob.orders[i].user_id == ob.heartbeat_users[k]);
+            }
+            let is_active = ob.orders[i].active == 1;
+
+            if is_stale_user && is_active {
+                ob.orders[i].active = 0;
+                ob.order_count = ob.order_count - 1;
+            }
+        }
+
+        // A stale user has nothing left to cancel, so free their entry
+        for k in 0..MAX_HEARTBEAT_USERS {
+            if stale[k] {
+                ob.heartbeat_users[k] = 0;
+                ob.heartbeat_ts[k] = 0;
+            }
+        }
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Buy share of total volume in bps (5000 when both sides are empty). The
+    // sums are u128 so 100 orders of any size can't overflow
+    fn imbalance_bps(buy_volume: u128, sell_volume: u128) -> u64 {
+        let total = buy_volume + sell_volume;
+        let divisor = if total == 0 { 1u128 } else { total };
+        let ratio = (buy_volume * 10000 / divisor) as u64;
+        if total == 0 { 5000 } else { ratio }
+    }
+
+    // Get the buy side's share of active volume in bps (5000 for an empty book)
+    #[instruction]
+    pub fn get_imbalance_ratio(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u64> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut buy_volume = 0u128;
+        let mut sell_volume = 0u128;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_active = order.active == 1;
+            if is_active && order.side == 0 {
+                buy_volume = buy_volume + order.amount as u128;
+            }
+            if is_active && order.side == 1 {
+                sell_volume = sell_volume + order.amount as u128;
+            }
+        }
+
+        receiver.from_arcis(imbalance_bps(buy_volume, sell_volume))
+    }
+
+    // Get the caller's lifetime filled volume (0 if not tracked)
+    #[instruction]
+    pub fn get_user_volume(
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u128> {
+        let user = user_id.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut volume = 0u128;
+        for k in 0..MAX_TRACKED_USERS {
+            let entry = ob.user_stats[k];
+            if entry.user_id == user && user != 0 {
+                volume = entry.lifetime_volume;
+            }
+        }
+
+        user_id.owner.from_arcis(volume)
+    }
+
+    // Count active orders by age: under 1 minute, under 5 minutes, under 1 hour,
+    // and older
+    #[instruction]
+    pub fn get_age_histogram(
+        now: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [u64; AGE_BUCKETS]> {
+        let ob = orderbook_ctxt.to_arcis();
+        let bounds: [u64; 3] = [60, 300, 3600];
+
+        let mut counts = [0u64; AGE_BUCKETS];
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let age = if now > order.created_ts { now - order.created_ts } else { 0 };
+
+            let bucket = if age < bounds[0] {
+                0u64
+            } else if age < bounds[1] {
+                1
+            } else if age < bounds[2] {
+                2
+            } else {
+                3
+            };
+
+            for k in 0..AGE_BUCKETS {
+                if order.active == 1 && (k as u64) == bucket {
+                    counts[k] = counts[k] + 1;
+                }
+            }
+        }
+
+        receiver.from_arcis(counts)
+    }
+
+    // Take a full depth snapshot and make it the baseline for get_depth_delta
+    #[instruction]
+    pub fn snapshot_depth(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Shared, DepthSnapshot>) {
+        let mut ob = orderbook_ctxt.to_arcis();
+
+        let depth = depth_buckets(ob);
+        ob.depth_base = depth;
+        ob.depth_base_version = ob.book_version;
+
+        let snapshot = DepthSnapshot {
+            depth,
+            book_version: ob.book_version,
+        };
+
+        (orderbook_ctxt.owner.from_arcis(ob), receiver.from_arcis(snapshot))
+    }
+
+    // Get the depth buckets whose volume changed since the snapshot taken at
+    // `book_version`, as a sparse (bucket_index, new_volume) list. Buckets are
+    // price levels, so a new level shifts the levels behind it
+    #[instruction]
+    pub fn get_depth_delta(
+        book_version: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, DepthDelta> {
+        let ob = orderbook_ctxt.to_arcis();
+        let depth = depth_buckets(ob);
+
+        let mut delta = DepthDelta {
+            bucket_indices: [0; DEPTH_BUCKETS],
+            volumes: [0; DEPTH_BUCKETS],
+            count: 0,
+            valid: 0,
+        };
+
+        for k in 0..DEPTH_BUCKETS {
+            let changed = depth[k] != ob.depth_base[k];
+            for slot in 0..DEPTH_BUCKETS {
+                if changed && (slot as u64) == delta.count {
+                    delta.bucket_indices[slot] = k as u64;
+                    delta.volumes[slot] = depth[k];
+                }
+            }
+            let count_increment = if changed { 1u64 } else { 0u64 };
+            delta.count = delta.count + count_increment;
+        }
+
+        let is_current_base = ob.depth_base_version == book_version;
+        delta.valid = if is_current_base { 1 } else { 0 };
+
+        receiver.from_arcis(delta)
+    }
+
+    // Verify book invariants, returning a bitmask of the violated ones:
+    // 1 = order_count differs from the number of active slots,
+    // 2 = an active order has zero amount,
+    // 4 = two active orders share an order_id
+    #[instruction]
+    pub fn check_invariants(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u8> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut active_slots = 0u64;
+        let mut zero_amount = false;
+        let mut duplicate_id = false;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_active = order.active == 1;
+            if is_active {
+                active_slots = active_slots + 1;
+            }
+            zero_amount = zero_amount || (is_active && order.amount == 0);
+
+            for j in (i + 1)..MAX_ORDERS {
+                let other = ob.orders[j];
+                let both_active = is_active && other.active == 1;
+                duplicate_id = duplicate_id || (both_active && order.order_id == other.order_id);
+            }
+        }
+
+        let mut violations = 0u8;
+        if active_slots != ob.order_count {
+            violations = violations + 1;
+        }
+        if zero_amount {
+            violations = violations + 2;
+        }
+        if duplicate_id {
+            violations = violations + 4;
+        }
+
+        receiver.from_arcis(violations)
+    }
+
+    // Get the caller's fills among the recent trades, in ring order; unused
+    // entries are zeroed (matched = 0)
+    #[instruction]
+    pub fn get_user_fills(
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [MatchResult; MAX_USER_FILLS]> {
+        let user = user_id.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut fills = [empty_match_result(); MAX_USER_FILLS];
+        let mut count = 0u64;
+        for t in 0..TRADE_LOG_LEN {
+            let trade = ob.recent_trades[t];
+            let involved = user != 0
+                && trade.result.matched == 1
+                && (trade.buy_user_id == user || trade.sell_user_id == user);
+
+            for k in 0..MAX_USER_FILLS {
+                if involved && (k as u64) == count {
+                    fills[k] = trade.result;
+                }
+            }
+            let count_increment = if involved { 1u64 } else { 0u64 };
+            count = count + count_increment;
+        }
+
+        user_id.owner.from_arcis(fills)
+    }
+
+    // Distinct limit prices on one side, best first, with the volume resting
+    // at each; unused levels are 0. Each pass selects the best price strictly
+    // behind the previous level, so the ladder comes out sorted
+    fn ladder_side(ob: OrderBook, side: u8) -> ([u64; LADDER_LEVELS], [u64; LADDER_LEVELS]) {
+        let mut prices = [0u64; LADDER_LEVELS];
+        let mut volumes = [0u64; LADDER_LEVELS];
+        let mut bound = 0u64;
+        let mut has_bound = false;
+
+        for k in 0..LADDER_LEVELS {
+            let mut best = 0u64;
+            let mut found = false;
+            for i in 0..MAX_ORDERS {
+                let order = ob.orders[i];
+                let eligible = order.active == 1 && order.order_type == 1 && order.side == side;
+                let behind_bound = if side == 0 { order.price < bound } else { order.price > bound };
+                let better = if side == 0 { order.price > best } else { order.price < best };
+
+                if eligible && (!has_bound || behind_bound) && (!found || better) {
+                    best = order.price;
+                    found = true;
+                }
+            }
+
+            let mut volume = 0u64;
+            for i in 0..MAX_ORDERS {
+                let order = ob.orders[i];
+                let at_level = order.active == 1 && order.order_type == 1 && order.side == side && order.price == best;
+                if found && at_level {
+                    volume = volume + order.amount;
+                }
+            }
+
+            if found {
+                prices[k] = best;
+                volumes[k] = volume;
+                bound = best;
+                has_bound = true;
+            }
+        }
+
+        (prices, volumes)
+    }
+
+    // Get the book as a sorted ladder of the best LADDER_LEVELS price levels
+    // per side: (bid prices, bid volumes, ask prices, ask volumes), bids
+    // descending and asks ascending
+    #[instruction]
+    pub fn get_sorted_ladder(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, ([u64; LADDER_LEVELS], [u64; LADDER_LEVELS], [u64; LADDER_LEVELS], [u64; LADDER_LEVELS])> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let (bid_prices, bid_volumes) = ladder_side(ob, 0);
+        let (ask_prices, ask_volumes) = ladder_side(ob, 1);
+
+        receiver.from_arcis((bid_prices, bid_volumes, ask_prices, ask_volumes))
+    }
+
+    // One of the caller's orders touched since a sync point; present = 0 marks
+    // padding, and a removed order comes back with active = 0
+    #[derive(Copy, Clone)]
+    pub struct OrderUpdate {
+        pub present: u8,
+        pub order_id: u64,
+        pub active: u8,
+        pub price: u64,
+        pub amount: u64,
+        pub modified_slot: u64,
+    }
+
+    // Get the caller's orders modified after `since_slot` for incremental sync
+    // Up to MAX_MODIFIED_ORDERS are returned in slot order
+    #[instruction]
+    pub fn get_orders_modified_since(
+        since_slot: u64,
+        user_id: Enc<Shared, u128>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [OrderUpdate; MAX_MODIFIED_ORDERS]> {
+        let user = user_id.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut updates = [OrderUpdate {
+            present: 0,
+            order_id: 0,
+            active: 0,
+            price: 0,
+            amount: 0,
+            modified_slot: 0,
+        }; MAX_MODIFIED_ORDERS];
+        let mut count = 0u64;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let touched = user != 0 && order.user_id == user && order.modified_slot > since_slot;
+
+            for k in 0..MAX_MODIFIED_ORDERS {
+                if touched && (k as u64) == count {
+                    updates[k] = OrderUpdate {
+                        present: 1,
+                        order_id: order.order_id,
+                        active: order.active,
+                        price: order.price,
+                        amount: order.amount,
+                        modified_slot: order.modified_slot,
+                    };
+                }
+            }
+            let count_increment = if touched { 1u64 } else { 0u64 };
+            count = count + count_increment;
+        }
+
+        user_id.owner.from_arcis(updates)
+    }
+
+    // Get the notional traded over the last 24 hours (the current hour plus the
+    // 23 before it)
+    #[instruction]
+    pub fn get_volume_24h(
+        now: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u128> {
+        let ob = orderbook_ctxt.to_arcis();
+        let hour = now / 3600;
+
+        let mut volume = 0u128;
+        for k in 0..VOLUME_BUCKETS {
+            let bucket_hour = ob.volume_bucket_hours[k];
+            let in_window = bucket_hour <= hour && hour - bucket_hour < (VOLUME_BUCKETS as u64);
+            if in_window {
+                volume = volume + ob.volume_buckets[k];
+            }
+        }
+
+        receiver.from_arcis(volume)
+    }
+
+    // Get the furthest-from-touch resting limit orders: (lowest bid price, its
+    // order id, highest ask price, its order id); an empty side reads 0, 0
+    #[instruction]
+    pub fn get_worst_orders(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, (u64, u64, u64, u64)> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut worst_bid = 0u64;
+        let mut worst_bid_id = 0u64;
+        let mut has_bid = false;
+        let mut worst_ask = 0u64;
+        let mut worst_ask_id = 0u64;
+        let mut has_ask = false;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_live_limit = order.active == 1 && order.order_type == 1;
+
+            if is_live_limit && order.side == 0 && (!has_bid || order.price < worst_bid) {
+                worst_bid = order.price;
+                worst_bid_id = order.order_id;
+                has_bid = true;
+            }
+            if is_live_limit && order.side == 1 && (!has_ask || order.price > worst_ask) {
+                worst_ask = order.price;
+                worst_ask_id = order.order_id;
+                has_ask = true;
+            }
+        }
+
+        receiver.from_arcis((worst_bid, worst_bid_id, worst_ask, worst_ask_id))
+    }
+
+    // Get the volume-weighted average price of the caller's active limit orders
+    // on one side (0 if they have none)
+    #[instruction]
+    pub fn get_user_avg_price(
+        query_ctxt: Enc<Shared, SideQuery>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, u64> {
+        let query = query_ctxt.to_arcis();
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut weighted = 0u128;
+        let mut volume = 0u128;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_own = order.active == 1
+                && order.order_type == 1
+                && order.side == query.side
+                && order.user_id == query.user_id
+                && query.user_id != 0;
+
+            if is_own {
+                weighted = weighted + (order.price as u128) * (order.amount as u128);
+                volume = volume + (order.amount as u128);
+            }
+        }
+
+        let divisor = if volume > 0 { volume } else { 1 };
+        let average = if volume > 0 { (weighted / divisor) as u64 } else { 0 };
+
+        query_ctxt.owner.from_arcis(average)
+    }
+
+    // Exchange the time priority of two active orders, leaving everything else untouched
+    // No-op unless both orders are found
+    #[instruction]
+    pub fn swap_priority(
+        order_a: u64,
+        order_b: u64,
+        current_slot: u64,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Mxe, OrderBook> {
+        let prev = orderbook_ctxt.to_arcis();
+        let mut ob = prev;
+
+        let mut found_a = false;
+        let mut found_b = false;
+        let mut sequence_a = 0u64;
+        let mut sequence_b = 0u64;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_active = order.active == 1;
+
+            if is_active && order.order_id == order_a {
+                found_a = true;
+                sequence_a = order.sequence;
+            }
+            if is_active && order.order_id == order_b {
+                found_b = true;
+                sequence_b = order.sequence;
+            }
+        }
+
+        let swap = found_a && found_b && order_a != order_b;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let is_active = order.active == 1;
+
+            if swap && is_active && order.order_id == order_a {
+                ob.orders[i].sequence = sequence_b;
+            }
+            if swap && is_active && order.order_id == order_b {
+                ob.orders[i].sequence = sequence_a;
+            }
+        }
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        orderbook_ctxt.owner.from_arcis(ob)
+    }
+
+    // Fees the venue keeps after paying out the rebates owed to makers
+    fn net_fees(ob: OrderBook) -> u128 {
+        if ob.fees_collected > ob.rebates_owed { ob.fees_collected - ob.rebates_owed } else { 0 }
+    }
+
+    // Reveal the fee accumulators to the operator:
+    // (fees_collected, rebates_owed, fees_net)
+    #[instruction]
+    pub fn get_fee_accounting(orderbook_ctxt: Enc<Mxe, OrderBook>) -> (u128, u128, u128) {
+        let ob = orderbook_ctxt.to_arcis();
+        let fees_net = net_fees(ob);
+
+        (ob.fees_collected.reveal(), ob.rebates_owed.reveal(), fees_net.reveal())
+    }
+
+    // Withdraw the net fees: fees_collected drops to the rebates still owed,
+    // which stay reserved for makers. Returns the withdrawn amount
+    #[instruction]
+    pub fn withdraw_fees(orderbook_ctxt: Enc<Mxe, OrderBook>) -> (Enc<Mxe, OrderBook>, u128) {
+        let mut ob = orderbook_ctxt.to_arcis();
+        let withdrawn = net_fees(ob);
+        ob.fees_collected = ob.fees_collected - withdrawn;
+
+        (orderbook_ctxt.owner.from_arcis(ob), withdrawn.reveal())
+    }
+
+    // Get cumulative depth (the depth chart): slot k holds the volume resting
+    // from the touch out to the k-th price level, bids in 0..10 and asks in
+    // 10..20. Levels are the sorted ladder's; past the last level a side stays flat
+    #[instruction]
+    pub fn get_depth_cumulative(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [u64; DEPTH_BUCKETS]> {
+        let ob = orderbook_ctxt.to_arcis();
+        let (_, bid_volumes) = ladder_side(ob, 0);
+        let (_, ask_volumes) = ladder_side(ob, 1);
+
+        let mut cumulative: [u64; DEPTH_BUCKETS] = [0; DEPTH_BUCKETS];
+        let mut bid_total = 0u64;
+        let mut ask_total = 0u64;
+        for k in 0..LADDER_LEVELS {
+            let bid_sum = (bid_total as u128) + (bid_volumes[k] as u128);
+            bid_total = if bid_sum > (u64::MAX as u128) { u64::MAX } else { bid_sum as u64 };
+            let ask_sum = (ask_total as u128) + (ask_volumes[k] as u128);
+            ask_total = if ask_sum > (u64::MAX as u128) { u64::MAX } else { ask_sum as u64 };
+
+            cumulative[k] = bid_total;
+            cumulative[LADDER_LEVELS + k] = ask_total;
+        }
+
+        receiver.from_arcis(cumulative)
+    }
+
+    // Re-run a match from its recorded inputs and check it reproduces the
+    // recorded MatchResult. The book is the pre-trade copy the program kept
+    // when the match was queued (MatchSnapshot) and the plaintext inputs those
+    // of the original match_orders call. Fees don't affect the result and are
+    // left out. The per-slot fill cap is not re-applied: a recorded fill passed it
+    #[instruction]
+    pub fn replay_match(
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        oracle_price: u64,
+        oracle_fresh: u8,
+        current_slot: u64,
+        rounding: u8,
+        match_mode: u8,
+        seed: u64,
+        fill_increment: u64,
+        priority_policy: u8,
+        max_take_per_maker: u64,
+        spread_policy: u8,
+        round_lot_size: u64,
+        match_cooldown_slots: u64,
+        price_scale: u64,
+        cross_tolerance: u64,
+        pair_rules: u8,
+        price_offset: u64,
+        price_source_policy: u8,
+        max_match_fraction_bps: u64,
+        lock_policy: u8,
+        activation_delay_slots: u64,
+        recorded_ctxt: Enc<Mxe, MatchResult>,
+    ) -> bool {
+        let config = MatchConfig {
+            oracle_price,
+            oracle_fresh,
+            rounding,
+            fill_increment,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            fee_rounding: 0,
+            amount_unit: 1,
+            priority_policy,
+            max_take_per_maker,
+            spread_policy,
+            round_lot_size,
+            current_slot,
+            match_cooldown_slots,
+            price_scale,
+            cross_tolerance,
+            pair_rules,
+            price_offset,
+            current_ts: 0,
+            price_source_policy,
+            max_match_fraction_bps,
+            lock_policy,
+            activation_delay_slots,
+            auction: false,
+        };
+        let recorded = recorded_ctxt.to_arcis();
+        let ob = roll_fill_window(orderbook_ctxt.to_arcis(), current_slot);
+
+        let replayed = if match_mode == 1 {
+            let (_, result) = match_pro_rata(ob, config, seed, true);
+            result
+        } else {
+            let (_, result, _) = match_once(ob, config, [0; MAX_ORDERS], true);
+            result
+        };
+
+        let reproducible = recorded.matched == 1
+            && replayed.matched == recorded.matched
+            && replayed.match_price == recorded.match_price
+            && replayed.match_amount == recorded.match_amount
+            && replayed.buy_order_id == recorded.buy_order_id
+            && replayed.sell_order_id == recorded.sell_order_id
+            && replayed.taker_side == recorded.taker_side
+            && replayed.scaled_price == recorded.scaled_price
+            && replayed.price_scale == recorded.price_scale;
+
+        reproducible.reveal()
+    }
+
+    // Export order slots start_index..start_index + BOOK_PAGE (bounds validated
+    // by the program), inactive slots included, so the book can be decrypted a
+    // page at a time
+    #[instruction]
+    pub fn get_book_page(
+        start_index: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, [Order; BOOK_PAGE]> {
+        let ob = orderbook_ctxt.to_arcis();
+        let mut page = [empty_order(); BOOK_PAGE];
+
+        for k in 0..BOOK_PAGE {
+            let slot = start_index + (k as u64);
+            for i in 0..MAX_ORDERS {
+                if (i as u64) == slot {
+                    page[k] = ob.orders[i];
+                }
+            }
+        }
+
+        receiver.from_arcis(page)
+    }
+
+    // Number of active limit orders resting at each ladder level of one side
+    fn ladder_counts(
+        ob: OrderBook,
+        side: u8,
+        prices: [u64; LADDER_LEVELS],
+        volumes: [u64; LADDER_LEVELS],
+    ) -> [u64; LADDER_LEVELS] {
+        let mut counts = [0u64; LADDER_LEVELS];
+        for k in 0..LADDER_LEVELS {
+            let mut count = 0u64;
+            for i in 0..MAX_ORDERS {
+                let order = ob.orders[i];
+                let at_level = order.active == 1
+                    && order.order_type == 1
+                    && order.side == side
+                    && order.price == prices[k];
+
+                if at_level && volumes[k] > 0 {
+                    count = count + 1;
+                }
+            }
+            counts[k] = count;
+        }
+
+        counts
+    }
+
+    // Get volume and order count per price level, best first: bids in 0..10,
+    // asks in 10..20 (the sorted ladder's levels; unused levels are 0)
+    #[instruction]
+    pub fn get_depth_counts(
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, ([u64; DEPTH_BUCKETS], [u64; DEPTH_BUCKETS])> {
+        let ob = orderbook_ctxt.to_arcis();
+        let (bid_prices, bid_volumes) = ladder_side(ob, 0);
+        let (ask_prices, ask_volumes) = ladder_side(ob, 1);
+        let bid_counts = ladder_counts(ob, 0, bid_prices, bid_volumes);
+        let ask_counts = ladder_counts(ob, 1, ask_prices, ask_volumes);
+
+        let mut volumes: [u64; DEPTH_BUCKETS] = [0; DEPTH_BUCKETS];
+        let mut counts: [u64; DEPTH_BUCKETS] = [0; DEPTH_BUCKETS];
+        for k in 0..LADDER_LEVELS {
+            volumes[k] = bid_volumes[k];
+            volumes[LADDER_LEVELS + k] = ask_volumes[k];
+            counts[k] = bid_counts[k];
+            counts[LADDER_LEVELS + k] = ask_counts[k];
+        }
+
+        receiver.from_arcis((volumes, counts))
+    }
+
+    // What an immediate-or-cancel order did; its remainder never rests
+    #[derive(Copy, Clone)]
+    pub struct IocReport {
+        pub filled_amount: u64,
+        pub cancelled_amount: u64,
+        pub avg_fill_price: u64, // volume-weighted over the fills, 0 when nothing filled
+    }
+
+    // Fee, tick, increment, cooldown and timing parameters for a sweep;
+    // matching policies that only apply to pairwise matching are left at
+    // their defaults
+    fn sweep_config(
+        current_slot: u64,
+        current_ts: u64,
+        maker_fee_bps: i64,
+        taker_fee_bps: u64,
+        fee_rounding: u8,
+        amount_unit: u64,
+        price_scale: u64,
+        price_offset: u64,
+        activation_delay_slots: u64,
+        fill_increment: u64,
+        match_cooldown_slots: u64,
+    ) -> MatchConfig {
+        MatchConfig {
+            oracle_price: 0,
+            oracle_fresh: 0,
+            rounding: 0,
+            fill_increment,
+            maker_fee_bps,
+            taker_fee_bps,
+            fee_rounding,
+            amount_unit,
+            priority_policy: 1,
+            max_take_per_maker: 0,
+            spread_policy: 2,
+            round_lot_size: 0,
+            current_slot,
+            match_cooldown_slots,
+            price_scale,
+            cross_tolerance: 0,
+            pair_rules: 0,
+            price_offset,
+            current_ts,
+            price_source_policy: 0,
+            max_match_fraction_bps: 0,
+            lock_policy: 0,
+            activation_delay_slots,
+            auction: false,
+        }
+    }
+
+    // Sweep `taker` through the resting limit orders it crosses, at their
+    // prices (a market taker crosses any price), up to MAX_IOC_FILLS fills.
+    // Each round takes the resting order with price-time priority, so a level
+    // is consumed oldest first, order by order, before the next level is
+    // touched. The taker is not in the book; the caller handles its remainder.
+    // As in match_once, fills are floored to the fill increment, users in
+    // their match cooldown neither take nor make, and the per-slot fill cap
+    // stops the sweep. Returns (book, unfilled amount, filled notional, fills)
+    fn sweep_book(
+        ob: OrderBook,
+        taker: Order,
+        config: MatchConfig,
+        max_fills_per_slot: u64,
+    ) -> (OrderBook, u64, u128, [MatchResult; MAX_IOC_FILLS]) {
+        let mut ob = roll_fill_window(ob, config.current_slot);
+        let mut remaining = taker.amount;
+        let mut notional = 0u128;
+        let mut results = [empty_match_result(); MAX_IOC_FILLS];
+        let cooling = cooling_down(ob, config);
+        let taker_cooling = user_cooling(ob, taker.user_id, config);
+
+        for r in 0..MAX_IOC_FILLS {
+            let top = top_of_book(ob);
+            let mut best = empty_order();
+            let mut best_slot = 0u64;
+            let mut found = false;
+            for i in 0..MAX_ORDERS {
+                let order = ob.orders[i];
+                let within_limit = taker.order_type == 0
+                    || (taker.side == 0 && order.price <= taker.price)
+                    || (taker.side == 1 && order.price >= taker.price);
+                // A maker left with less than one increment can't fill; pass it
+                // over rather than stall the sweep on it
+                let fillable = if remaining < order.amount { remaining } else { order.amount };
+                let eligible = order.active == 1
+                    && floor_to_increment(fillable, config.fill_increment) > 0
+                    && order.order_type == 1
+                    && order.side != taker.side
+                    && within_limit
+                    && !same_owner(order, taker)
+                    && !has_flag(order, FLAG_ALL_OR_NONE)
+                    && !has_flag(order, FLAG_AUCTION_ONLY)
+                    && is_activated(order, config)
+                    && !cooling[i]
+                    && has_min_edge(order, order.price, top, config.price_offset);
+
+                if eligible && (!found || has_priority(order, best)) {
+                    best = order;
+                    best_slot = i as u64;
+                    found = true;
+                }
+            }
+
+            let available = if remaining < best.amount { remaining } else { best.amount };
+            let fill = floor_to_increment(available, config.fill_increment);
+            let under_slot_cap = ob.fills_this_slot < max_fills_per_slot;
+            let mut result = empty_match_result();
+            if found && fill > 0 && !taker_cooling && under_slot_cap {
+                result.matched = 1;
+                result.match_price = best.price;
+                result.match_amount = fill;
+                result.buy_order_id = if taker.side == 0 { taker.order_id } else { best.order_id };
+                result.sell_order_id = if taker.side == 0 { best.order_id } else { taker.order_id };
+                result.taker_side = taker.side;
+                result.scaled_price = rescale_price(
+                    (best.price as u128) * (config.price_scale as u128),
+                    config.price_scale,
+                    config.price_offset,
+                );
+                result.price_scale = config.price_scale;
+
+                ob = apply_fill(ob, best_slot, best_slot, fill);
+                ob = record_user_fill(ob, taker.user_id, fill, config.current_slot);
+                ob = record_user_fill(ob, best.user_id, fill, config.current_slot);
+                remaining = remaining - fill;
+                notional = notional + (best.price as u128) * (fill as u128);
+            }
+            results[r] = result;
+
+            let buy_user_id = if taker.side == 0 { taker.user_id } else { best.user_id };
+            let sell_user_id = if taker.side == 0 { best.user_id } else { taker.user_id };
+            ob = record_fill(ob, result, config);
+            ob = record_trade(ob, result, buy_user_id, sell_user_id);
+        }
+
+        (ob, remaining, notional, results)
+    }
+
+    // Execute an immediate-or-cancel order against the resting book (see
+    // sweep_book), then drop whatever is left. The report goes back under the
+    // taker's key
+    #[instruction]
+    pub fn submit_ioc(
+        order_id: u64,
+        allowlist_enabled: u8,
+        current_slot: u64,
+        current_ts: u64,
+        maker_fee_bps: i64,
+        taker_fee_bps: u64,
+        fee_rounding: u8,
+        amount_unit: u64,
+        price_scale: u64,
+        price_offset: u64,
+        activation_delay_slots: u64,
+        fill_increment: u64,
+        match_cooldown_slots: u64,
+        max_fills_per_slot: u64,
+        order_ctxt: Enc<Shared, Order>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Shared, IocReport>) {
+        let config = sweep_config(
+            current_slot,
+            current_ts,
+            maker_fee_bps,
+            taker_fee_bps,
+            fee_rounding,
+            amount_unit,
+            price_scale,
+            price_offset,
+            activation_delay_slots,
+            fill_increment,
+            match_cooldown_slots,
+        );
+        let mut taker = order_ctxt.to_arcis();
+        taker.order_id = order_id;
+        let prev = orderbook_ctxt.to_arcis();
+
+        // A taker outside the allowlist sweeps nothing and is cancelled whole
+        let allowed = allowlist_enabled != 1 || is_allowlisted(prev, taker.user_id);
+        let mut sweeping = taker;
+        sweeping.amount = if allowed { taker.amount } else { 0 };
+        let (ob, remaining, notional, _) = sweep_book(prev, sweeping, config, max_fills_per_slot);
+        let remaining = if allowed { remaining } else { taker.amount };
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        let filled = taker.amount - remaining;
+        let divisor = if filled > 0 { filled as u128 } else { 1 };
+        let report = IocReport {
+            filled_amount: filled,
+            cancelled_amount: taker.amount - filled,
+            avg_fill_price: if filled > 0 { (notional / divisor) as u64 } else { 0 },
+        };
+
+        (orderbook_ctxt.owner.from_arcis(ob), order_ctxt.owner.from_arcis(report))
+    }
+
+    // Sweep the oldest resting market order through the opposite side of the
+    // book (see sweep_book): it fills the oldest order at the best level first
+    // and only moves on once that order is exhausted. Whatever it can't fill
+    // keeps resting. Returns the fills under the caller's key, so a taker
+    // sweeping its own order can read them
+    #[instruction]
+    pub fn match_market_sweep(
+        current_slot: u64,
+        current_ts: u64,
+        maker_fee_bps: i64,
+        taker_fee_bps: u64,
+        fee_rounding: u8,
+        amount_unit: u64,
+        price_scale: u64,
+        price_offset: u64,
+        activation_delay_slots: u64,
+        fill_increment: u64,
+        match_cooldown_slots: u64,
+        max_fills_per_slot: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Shared, [MatchResult; MAX_IOC_FILLS]>) {
+        let config = sweep_config(
+            current_slot,
+            current_ts,
+            maker_fee_bps,
+            taker_fee_bps,
+            fee_rounding,
+            amount_unit,
+            price_scale,
+            price_offset,
+            activation_delay_slots,
+            fill_increment,
+            match_cooldown_slots,
+        );
+        let prev = orderbook_ctxt.to_arcis();
+
+        let mut taker = empty_order();
+        let mut taker_slot = 0u64;
+        let mut found = false;
+        for i in 0..MAX_ORDERS {
+            let order = prev.orders[i];
+            let is_market = order.active == 1
+                && order.order_type == 0
+                && is_activated(order, config)
+                && !user_cooling(prev, order.user_id, config);
+            let older = !found || order.sequence < taker.sequence;
+
+            if is_market && older {
+                taker = order;
+                taker_slot = i as u64;
+                found = true;
+            }
+        }
+
+        let (mut ob, remaining, _, results) = sweep_book(prev, taker, config, max_fills_per_slot);
+        for i in 0..MAX_ORDERS {
+            if found && (i as u64) == taker_slot {
+                ob.orders[i].amount = remaining;
+                if remaining == 0 {
+                    ob.orders[i].active = 0;
+                    ob.order_count = ob.order_count - 1;
+                }
+            }
+        }
+        let ob = log_book_changes(prev, ob, current_slot);
+
+        (orderbook_ctxt.owner.from_arcis(ob), receiver.from_arcis(results))
+    }
+
+    // Get (buy_notional, sell_notional): price * amount / amount_unit summed
+    // over each side's resting limit orders, saturating at u64::MAX. Unlike
+    // depth this is in quote units; market orders carry no price and are left out
+    #[instruction]
+    pub fn get_open_interest(
+        amount_unit: u64,
+        price_offset: u64,
+        receiver: Shared,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+    ) -> Enc<Shared, (u64, u64)> {
+        let ob = orderbook_ctxt.to_arcis();
+
+        let mut buy_sum = 0u128;
+        let mut sell_sum = 0u128;
+        for i in 0..MAX_ORDERS {
+            let order = ob.orders[i];
+            let resting = order.active == 1 && order.order_type == 1;
+            let notional = mul_div(price_magnitude(order.price, price_offset), order.amount, amount_unit) as u128;
+            if resting && order.side == 0 {
+                buy_sum = buy_sum + notional;
+            }
+            if resting && order.side == 1 {
+                sell_sum = sell_sum + notional;
+            }
+        }
+        let buy_notional = if buy_sum > (u64::MAX as u128) { u64::MAX } else { buy_sum as u64 };
+        let sell_notional = if sell_sum > (u64::MAX as u128) { u64::MAX } else { sell_sum as u64 };
+
+        receiver.from_arcis((buy_notional, sell_notional))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn order(price: u64, side: u8, sequence: u64) -> Order {
+            Order { price, side, sequence, active: 1, ..empty_order() }
+        }
+
+        // A resting limit order owned by its own user, with order_id = sequence
+        fn limit(price: u64, amount: u64, side: u8, sequence: u64) -> Order {
+            Order {
+                amount,
+                order_type: 1,
+                user_id: sequence as u128 + 1,
+                order_id: sequence,
+                priority_price: price,
+                ..order(price, side, sequence)
+            }
+        }
+
+        fn config(cross_tolerance: u64, lock_policy: u8) -> MatchConfig {
+            MatchConfig {
+                oracle_price: 0,
+                oracle_fresh: 0,
+                rounding: 0,
+                fill_increment: 0,
+                maker_fee_bps: 0,
+                taker_fee_bps: 0,
+                fee_rounding: 0,
+                amount_unit: 1,
+                priority_policy: 0,
+                max_take_per_maker: 0,
+                spread_policy: 0,
+                round_lot_size: 0,
+                current_slot: 0,
+                match_cooldown_slots: 0,
+                price_scale: 1,
+                cross_tolerance,
+                pair_rules: 0,
+                price_offset: 0,
+                current_ts: 0,
+                price_source_policy: 0,
+                max_match_fraction_bps: 0,
+                lock_policy,
+                activation_delay_slots: 0,
+                auction: false,
+            }
+        }
+
+        fn book(orders: &[Order]) -> OrderBook {
+            let mut ob = OrderBook {
+                orders: [empty_order(); MAX_ORDERS],
+                order_count: orders.len() as u64,
+                next_sequence: 0,
+                throttle_slot: 0,
+                fills_this_slot: 0,
+                allowlist: [0; MAX_ALLOWLIST],
+                last_trade_price: 0,
+                last_trade_amount: 0,
+                fees_collected: 0,
+                rebates_owed: 0,
+                book_version: 0,
+                change_log: [empty_change(); CHANGE_LOG_LEN],
+                heartbeat_users: [0; MAX_HEARTBEAT_USERS],
+                heartbeat_ts: [0; MAX_HEARTBEAT_USERS],
+                user_stats: [UserStats { user_id: 0, lifetime_volume: 0, last_fill_slot: 0 }; MAX_TRACKED_USERS],
+                recent_trades: [TradeRecord { result: empty_match_result(), buy_user_id: 0, sell_user_id: 0 }; TRADE_LOG_LEN],
+                trade_count: 0,
+                volume_buckets: [0; VOLUME_BUCKETS],
+                volume_bucket_hours: [0; VOLUME_BUCKETS],
+                depth_base: [0; DEPTH_BUCKETS],
+                depth_base_version: 0,
+            };
+            for (i, o) in orders.iter().enumerate() {
+                ob.orders[i] = *o;
+            }
+            ob
+        }
+
+        // Circuit inputs as the host stand-in carries them
+        fn mxe<T>(value: T) -> Enc<Mxe, T> {
+            Mxe.from_arcis(value)
+        }
+
+        fn receiver() -> Shared {
+            Shared::new(ArcisX25519Pubkey::from_uint8(&[0; 32]))
+        }
+
+        #[test]
+        fn stale_oracle_blocks_market_order_matching() {
+            let market_buy = Order { order_type: 0, ..limit(0, 10, 0, 2) };
+            let ob = book(&[limit(100, 10, 1, 1), market_buy]);
+            let stale = MatchConfig { oracle_price: 100, oracle_fresh: 0, ..config(0, 0) };
+
+            let (after, result, _, _) = run_match(ob, stale, 0, 0);
+            assert_eq!(result.matched, 0);
+            assert_eq!(after.orders[1].amount, 10);
+
+            // The same pair fills at the ask once the oracle is fresh
+            let (_, result, _, _) = run_match(ob, MatchConfig { oracle_fresh: 1, ..stale }, 0, 0);
+            assert_eq!((result.match_amount, result.match_price), (10, 100));
+        }
+
+        #[test]
+        fn queue_position_counts_better_priced_orders_ahead() {
+            let ob = book(&[
+                limit(100, 5, 0, 1),
+                limit(102, 5, 0, 2),
+                limit(101, 5, 0, 3),
+                // Same price but later, a worse bid and an ask: none of them are ahead
+                limit(100, 5, 0, 4),
+                limit(99, 5, 0, 5),
+                limit(98, 5, 1, 6),
+            ]);
+            let position = |user: u128| get_queue_position(1, receiver().from_arcis(user), mxe(ob)).to_arcis();
+
+            assert_eq!(position(2), 2);
+            // Someone else's order id reports as not found
+            assert_eq!(position(3), u64::MAX);
+        }
+
+        // Add `order` under its own order_id with every add_order check off
+        fn add(ob: OrderBook, order: Order) -> OrderBook {
+            let key = ArcisX25519Pubkey::from_uint8(&[0; 32]);
+            let (ob, _, _, _) =
+                add_order(order.order_id, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, key, receiver().from_arcis(order), mxe(ob));
+            ob.to_arcis()
+        }
+
+        fn status(ob: OrderBook, order_id: u64, user: u128) -> OrderStatus {
+            get_order_status(order_id, receiver().from_arcis(user), mxe(ob)).to_arcis()
+        }
+
+        #[test]
+        fn metadata_survives_add_and_a_partial_fill() {
+            let tagged = Order { metadata: 0xabc, ..limit(100, 10, 0, 1) };
+            let ob = add(book(&[]), tagged);
+            assert_eq!(status(ob, 1, 2).metadata, 0xabc);
+
+            let ob = add(ob, limit(100, 4, 1, 2));
+            let (ob, result, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!(result.match_amount, 4);
+
+            let after = status(ob, 1, 2);
+            assert_eq!((after.active, after.amount, after.metadata), (1, 6, 0xabc));
+        }
+
+        // Run match_orders_batch under `cfg` with the given call and slot caps;
+        // returns the book, the number of fills and the netted settlements
+        fn batch(
+            ob: OrderBook,
+            cfg: MatchConfig,
+            call_cap: u64,
+            slot_cap: u64,
+        ) -> (OrderBook, BatchSummary, [NetFill; MAX_NET_FILLS]) {
+            let (ob, _, net, summary) = match_orders_batch(
+                mxe(ob),
+                cfg.oracle_price,
+                cfg.oracle_fresh,
+                cfg.current_slot,
+                call_cap,
+                slot_cap,
+                cfg.rounding,
+                cfg.fill_increment,
+                cfg.maker_fee_bps,
+                cfg.taker_fee_bps,
+                cfg.fee_rounding,
+                cfg.amount_unit,
+                cfg.priority_policy,
+                cfg.max_take_per_maker,
+                cfg.spread_policy,
+                cfg.round_lot_size,
+                cfg.match_cooldown_slots,
+                cfg.price_scale,
+                cfg.cross_tolerance,
+                cfg.pair_rules,
+                cfg.price_offset,
+                cfg.current_ts,
+                cfg.price_source_policy,
+                cfg.max_match_fraction_bps,
+                cfg.lock_policy,
+                cfg.activation_delay_slots,
+            );
+            (ob.to_arcis(), summary.to_arcis(), net.to_arcis())
+        }
+
+        #[test]
+        fn slot_fill_cap_holds_across_calls_and_resets_next_slot() {
+            // Five crossing pairs, each fill completing one of them
+            let orders: Vec<Order> = (0..10u64).map(|k| limit(100, 10, (k / 5) as u8, k)).collect();
+            let slot_1 = MatchConfig { current_slot: 1, ..config(0, 0) };
+
+            let (ob, summary, _) = batch(book(&orders), slot_1, 8, 3);
+            assert_eq!((summary.fills, ob.fills_this_slot), (3, 3));
+
+            // A second call in the same slot finds the cap used up
+            let (ob, summary, _) = batch(ob, slot_1, 8, 3);
+            assert_eq!(summary.fills, 0);
+
+            let (ob, summary, _) = batch(ob, MatchConfig { current_slot: 2, ..slot_1 }, 8, 3);
+            assert_eq!((summary.fills, ob.fills_this_slot, ob.order_count), (2, 2, 0));
+        }
+
+        #[test]
+        fn is_crossed_only_when_the_best_bid_reaches_the_best_ask() {
+            let crossed = |orders: &[Order]| is_crossed(receiver(), mxe(book(orders))).to_arcis();
+
+            assert_eq!(crossed(&[limit(101, 5, 0, 1), limit(100, 5, 1, 2), limit(90, 5, 0, 3)]), 1);
+            assert_eq!(crossed(&[limit(100, 5, 0, 1), limit(100, 5, 1, 2)]), 1);
+            assert_eq!(crossed(&[limit(99, 5, 0, 1), limit(100, 5, 1, 2), limit(110, 5, 1, 3)]), 0);
+            // One empty side is never crossed
+            assert_eq!(crossed(&[limit(101, 5, 0, 1)]), 0);
+        }
+
+        #[test]
+        fn bulk_cancel_skips_ids_the_caller_does_not_own() {
+            let owned = |price: u64, seq: u64| Order { user_id: 7, ..limit(price, 5, 0, seq) };
+            let other = |price: u64, seq: u64| Order { user_id: 8, ..limit(price, 5, 1, seq) };
+            let ob = book(&[owned(90, 1), owned(91, 2), other(110, 3), owned(92, 4), other(111, 5), owned(93, 6)]);
+
+            let mut ids = [u64::MAX; MAX_CANCEL_IDS];
+            ids[..5].copy_from_slice(&[1, 2, 3, 4, 5]);
+            let ob = cancel_orders_by_ids(ids, 0, 0, receiver().from_arcis(7), mxe(ob)).to_arcis();
+
+            let active: Vec<u8> = ob.orders[..6].iter().map(|o| o.active).collect();
+            assert_eq!(active, [0, 0, 1, 0, 1, 1]);
+            assert_eq!(ob.order_count, 3);
+        }
+
+        #[test]
+        fn emergency_cancel_all_cancels_every_order_with_a_record() {
+            let mut cancelled = limit(95, 5, 0, 3);
+            cancelled.active = 0;
+            let ob = book(&[limit(100, 10, 0, 1), limit(105, 4, 1, 2), cancelled]);
+
+            let (ob, records) = emergency_cancel_all(mxe(ob), 0);
+            let (ob, records) = (ob.to_arcis(), records.to_arcis());
+
+            assert_eq!(ob.order_count, 0);
+            assert!(ob.orders.iter().all(|o| o.active == 0));
+            let record = |r: &CancelRecord| (r.cancelled, r.user_id, r.side, r.price, r.amount);
+            assert_eq!(record(&records[0]), (1, 2, 0, 100, 10));
+            assert_eq!(record(&records[1]), (1, 3, 1, 105, 4));
+            // Slots without a live order carry no record
+            assert!(records[2..].iter().all(|r| r.cancelled == 0));
+        }
+
+        #[test]
+        fn market_buy_fills_before_an_older_limit_buy() {
+            let limit_buy = limit(100, 10, 0, 1);
+            let market_buy = Order { order_type: 0, ..limit(0, 10, 0, 2) };
+            let ob = book(&[limit_buy, market_buy, limit(100, 10, 1, 3)]);
+            let cfg = MatchConfig { oracle_price: 100, oracle_fresh: 1, ..config(0, 0) };
+
+            let (ob, result, _, _) = run_match(ob, cfg, 0, 0);
+            assert_eq!((result.buy_order_id, result.match_amount), (2, 10));
+            assert_eq!((ob.orders[0].active, ob.orders[0].amount), (1, 10));
+        }
+
+        #[test]
+        fn allowlist_rejects_orders_from_unlisted_users() {
+            let ob = add_to_allowlist(receiver().from_arcis(5), mxe(book(&[]))).to_arcis();
+            let key = ArcisX25519Pubkey::from_uint8(&[0; 32]);
+            let add_listed = |ob: OrderBook, order: Order| {
+                let (ob, _, _, _) =
+                    add_order(order.order_id, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, key, receiver().from_arcis(order), mxe(ob));
+                ob.to_arcis()
+            };
+
+            let ob = add_listed(ob, Order { user_id: 6, ..limit(100, 5, 0, 1) });
+            assert_eq!(ob.order_count, 0);
+
+            let ob = add_listed(ob, Order { user_id: 5, ..limit(100, 5, 0, 2) });
+            assert_eq!(ob.order_count, 1);
+            assert_eq!(status(ob, 2, 5).active, 1);
+        }
+
+        #[test]
+        fn filling_one_oco_leg_cancels_its_sibling() {
+            let take_profit = Order { user_id: 9, oco_group: 1, ..limit(110, 10, 1, 1) };
+            let stop_loss = Order { user_id: 9, oco_group: 1, ..limit(90, 10, 1, 2) };
+            // Same user, other group: untouched
+            let unrelated = Order { user_id: 9, oco_group: 2, ..limit(120, 10, 1, 3) };
+            let ob = book(&[take_profit, stop_loss, unrelated, limit(95, 4, 0, 4)]);
+
+            let (ob, result, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!((result.sell_order_id, result.match_amount), (2, 4));
+            assert_eq!((ob.orders[1].active, ob.orders[1].amount), (1, 6));
+            assert_eq!(ob.orders[0].active, 0);
+            assert_eq!(ob.orders[2].active, 1);
+            assert_eq!(ob.order_count, 2);
+        }
+
+        #[test]
+        fn last_trade_follows_the_latest_match() {
+            let last = |ob: OrderBook| {
+                let trade = get_last_trade(receiver(), mxe(ob)).to_arcis();
+                (trade.price, trade.amount)
+            };
+            let ob = book(&[limit(100, 10, 1, 1), limit(100, 4, 0, 2)]);
+            assert_eq!(last(ob), (0, 0));
+
+            let (ob, _, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!(last(ob), (100, 4));
+
+            let mut ob = ob;
+            ob.orders[2] = limit(101, 6, 0, 3);
+            ob.order_count = 2;
+            let (ob, _, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!(last(ob), (100, 6));
+        }
+
+        #[test]
+        fn sub_lot_amounts_fill_exactly() {
+            // Three decimals: a 1.250 lot bid against a 0.750 lot ask
+            let scaled = MatchConfig { amount_unit: 1000, ..config(0, 0) };
+            let ob = book(&[limit(100, 1250, 0, 1), limit(100, 750, 1, 2)]);
+
+            let (ob, result, _, _) = run_match(ob, scaled, 0, 0);
+            assert_eq!(result.match_amount, 750);
+            assert_eq!((ob.orders[0].amount, ob.orders[1].active), (500, 0));
+            assert_eq!(depth_buckets(ob)[0], 500);
+            // 0.500 lots at 100
+            assert_eq!(quote_depth_buckets(ob, 1000, 0)[0], 50);
+        }
+
+        #[test]
+        fn fragmentation_reports_the_highest_live_slot() {
+            let orders: Vec<Order> = (0..6u64).map(|k| Order { user_id: 7, ..limit(90 + k, 5, 0, k) }).collect();
+            let cancel = |ob: OrderBook, order_id: u64| {
+                cancel_order(order_id, 0, 0, receiver().from_arcis(7), mxe(ob)).to_arcis()
+            };
+            let fragmentation = |ob: OrderBook| get_fragmentation(receiver(), mxe(ob)).to_arcis();
+
+            // Interleaved cancels, the last slot included
+            let mut ob = book(&orders);
+            for order_id in [1, 3, 5] {
+                ob = cancel(ob, order_id);
+            }
+            assert_eq!(fragmentation(ob), (3, 4));
+
+            assert_eq!(fragmentation(cancel(ob, 4)), (2, 2));
+        }
+
+        #[test]
+        fn price_at_volume_walks_a_two_level_book() {
+            let ob = book(&[
+                limit(101, 3, 1, 1),
+                limit(103, 10, 1, 2),
+                limit(101, 2, 1, 3),
+                limit(99, 4, 0, 4),
+                limit(97, 6, 0, 5),
+            ]);
+            let price = |volume: u64, side: u8| get_price_at_volume(volume, side, receiver(), mxe(ob)).to_arcis();
+
+            // A buy sweeps the asks upward: 5 lots at 101, then 10 at 103
+            assert_eq!(price(5, 0), 101);
+            assert_eq!(price(6, 0), 103);
+            assert_eq!(price(15, 0), 103);
+            assert_eq!(price(16, 0), 0);
+            // A sell sweeps the bids downward
+            assert_eq!(price(4, 1), 99);
+            assert_eq!(price(5, 1), 97);
+        }
+
+        #[test]
+        fn fill_likelihood_is_reachable_opposite_volume_over_size() {
+            let ob = book(&[
+                limit(100, 20, 0, 1),
+                limit(99, 5, 1, 2),
+                limit(100, 5, 1, 3),
+                // Priced above the bid: not reachable now
+                limit(101, 100, 1, 4),
+            ]);
+            let likelihood = |user: u128| get_fill_likelihood(1, receiver().from_arcis(user), mxe(ob)).to_arcis();
+
+            assert_eq!(likelihood(2), 5000);
+            assert_eq!(likelihood(3), 0);
+        }
+
+        #[test]
+        fn market_buy_without_asks_is_flagged_unfilled() {
+            let market_buy = Order { order_type: 0, ..limit(0, 10, 0, 1) };
+            let cfg = MatchConfig { oracle_price: 100, oracle_fresh: 1, ..config(0, 0) };
+            let ob = book(&[market_buy, limit(99, 10, 0, 2)]);
+
+            let (ob, result, _, _) = run_match(ob, cfg, 0, 0);
+            assert_eq!(result.matched, 0);
+            assert_eq!((ob.orders[0].active, ob.orders[0].amount), (1, 10));
+            assert!(has_unfilled_market_order(ob));
+
+            // Once an ask arrives the order has liquidity again
+            let mut ob = ob;
+            ob.orders[2] = limit(101, 10, 1, 3);
+            assert!(!has_unfilled_market_order(ob));
+        }
+
+        #[test]
+        fn price_caps_reject_orders_outside_the_bounds() {
+            let key = ArcisX25519Pubkey::from_uint8(&[0; 32]);
+            // Bids up to 105, asks down to 95
+            let add_capped = |ob: OrderBook, order: Order| {
+                let (ob, _, _, _) =
+                    add_order(order.order_id, 0, 105, 95, 0, 0, 0, 0, 0, 0, 1, 0, key, receiver().from_arcis(order), mxe(ob));
+                ob.to_arcis()
+            };
+
+            let mut ob = book(&[]);
+            for order in [limit(106, 5, 0, 1), limit(105, 5, 0, 2), limit(94, 5, 1, 3), limit(95, 5, 1, 4)] {
+                ob = add_capped(ob, order);
+            }
+
+            assert_eq!(ob.order_count, 2);
+            let resting: Vec<u64> = ob.orders.iter().filter(|o| o.active == 1).map(|o| o.order_id).collect();
+            assert_eq!(resting, [2, 4]);
+        }
+
+        #[test]
+        fn active_distribution_counts_orders_per_bucket() {
+            let mut cancelled = limit(101, 5, 0, 6);
+            cancelled.active = 0;
+            let ob = book(&[
+                limit(100, 5, 0, 1),
+                limit(103, 50, 0, 2),
+                limit(107, 5, 0, 3),
+                // Below the base: clamped into the first bucket
+                limit(50, 5, 0, 4),
+                limit(112, 5, 1, 5),
+                cancelled,
+                // Past the last bucket: clamped into it
+                limit(200, 5, 1, 7),
+            ]);
+
+            let counts = get_active_distribution(100, 5, receiver(), mxe(ob)).to_arcis();
+            let mut expected = [0u64; DEPTH_BUCKETS];
+            expected[0] = 3;
+            expected[1] = 1;
+            expected[12] = 1;
+            expected[19] = 1;
+            assert_eq!(counts, expected);
+        }
+
+        #[test]
+        fn book_diff_shows_an_add_then_cancel_since_a_version() {
+            let ob = add(book(&[]), limit(100, 5, 0, 1));
+            let since = ob.book_version;
+
+            let ob = add(ob, limit(101, 5, 0, 2));
+            let ob = cancel_order(2, 0, 0, receiver().from_arcis(3), mxe(ob)).to_arcis();
+            let diff = get_book_diff(since, receiver(), mxe(ob)).to_arcis();
+
+            let changes: Vec<(u64, u8)> =
+                diff.entries.iter().filter(|e| e.kind != 0).map(|e| (e.order_id, e.kind)).collect();
+            assert_eq!(changes, [(2, 1), (2, 2)]);
+            assert_eq!((diff.current_version, diff.complete), (since + 2, 1));
+        }
+
+        #[test]
+        fn per_maker_cap_spreads_a_taker_across_two_makers() {
+            let ob = book(&[limit(100, 10, 1, 1), limit(100, 10, 1, 2), limit(100, 8, 0, 3)]);
+            let capped = MatchConfig { max_take_per_maker: 4, ..config(0, 0) };
+
+            let (ob, summary, _) = batch(ob, capped, 8, 16);
+            assert_eq!(summary.fills, 2);
+            assert_eq!((ob.orders[0].amount, ob.orders[1].amount), (6, 6));
+            assert_eq!(ob.orders[2].active, 0);
+        }
+
+        #[test]
+        fn third_oldest_buy_has_time_rank_2() {
+            // Prices don't matter, only arrival; the ask doesn't count either
+            let ob = book(&[
+                limit(90, 5, 0, 3),
+                limit(99, 5, 0, 1),
+                limit(95, 5, 1, 0),
+                limit(80, 5, 0, 2),
+                limit(100, 5, 0, 4),
+            ]);
+            let rank = |order_id: u64, user: u128| {
+                get_time_rank(order_id, receiver().from_arcis(user), mxe(ob)).to_arcis()
+            };
+
+            assert_eq!(rank(3, 4), 2);
+            assert_eq!(rank(1, 2), 0);
+            assert_eq!(rank(3, 2), u64::MAX);
+        }
+
+        #[test]
+        fn spread_policy_prices_the_same_crossing_pair() {
+            // The resting bid is the maker, the incoming ask the taker
+            let ob = book(&[limit(104, 10, 0, 1), limit(100, 10, 1, 2)]);
+            let price = |spread_policy: u8| {
+                let (_, result, _, _) = run_match(ob, MatchConfig { spread_policy, ..config(0, 0) }, 0, 0);
+                result.match_price
+            };
+
+            assert_eq!(price(0), 102);
+            // Maker-favorable: the maker buys at the taker's limit
+            assert_eq!(price(1), 100);
+            // Taker-favorable: the taker sells at the maker's limit
+            assert_eq!(price(2), 104);
+        }
+
+        #[test]
+        fn taker_side_is_the_later_order() {
+            let (_, result, _, _) = run_match(book(&[limit(100, 10, 1, 1), limit(101, 10, 0, 2)]), config(0, 0), 0, 0);
+            assert_eq!((result.buy_order_id, result.sell_order_id, result.taker_side), (2, 1, 0));
+
+            let (_, result, _, _) = run_match(book(&[limit(101, 10, 0, 1), limit(100, 10, 1, 2)]), config(0, 0), 0, 0);
+            assert_eq!((result.buy_order_id, result.sell_order_id, result.taker_side), (1, 2, 1));
+        }
+
+        #[test]
+        fn cancel_waits_for_the_minimum_resting_time() {
+            let placed = Order { created_slot: 10, ..limit(100, 5, 0, 1) };
+            let cancel = |slot: u64| cancel_order(1, slot, 5, receiver().from_arcis(2), mxe(book(&[placed]))).to_arcis();
+
+            assert_eq!(cancel(14).orders[0].active, 1);
+            let ob = cancel(15);
+            assert_eq!((ob.orders[0].active, ob.order_count), (0, 0));
+        }
+
+        #[test]
+        fn fees_accrue_at_the_rates_each_match_runs_with() {
+            let pair = [limit(100, 10, 0, 1), limit(100, 10, 1, 2)];
+            let one_percent = MatchConfig { taker_fee_bps: 100, ..config(0, 0) };
+            let (ob, _, _, _) = run_match(book(&pair), one_percent, 0, 0);
+            assert_eq!(ob.fees_collected, 10);
+
+            // New rates apply from the next match on
+            let mut ob = ob;
+            ob.orders[2] = limit(100, 10, 0, 3);
+            ob.orders[3] = limit(100, 10, 1, 4);
+            ob.order_count = 2;
+            let updated = MatchConfig { maker_fee_bps: 50, taker_fee_bps: 200, ..one_percent };
+            let (ob, _, _, _) = run_match(ob, updated, 0, 0);
+            assert_eq!(ob.fees_collected, 10 + 5 + 20);
+        }
+
+        #[test]
+        fn user_volume_grows_on_both_sides_of_a_fill() {
+            let volume = |ob: OrderBook, user: u128| get_user_volume(receiver().from_arcis(user), mxe(ob)).to_arcis();
+            let ob = book(&[limit(100, 10, 0, 1), limit(100, 4, 1, 2)]);
+            assert_eq!(volume(ob, 2), 0);
+
+            let (mut ob, _, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!((volume(ob, 2), volume(ob, 3)), (4, 4));
+
+            ob.orders[1] = limit(100, 6, 1, 3);
+            ob.order_count = 2;
+            let (ob, _, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!((volume(ob, 2), volume(ob, 3), volume(ob, 4)), (10, 4, 6));
+        }
+
+        #[test]
+        fn round_lots_match_each_other_before_an_odd_lot() {
+            // The odd-lot ask is older and first in slot order
+            let ob = book(&[limit(100, 7, 1, 1), limit(100, 10, 1, 2), limit(100, 10, 0, 3)]);
+            let segregated = MatchConfig { round_lot_size: 10, ..config(0, 0) };
+
+            let (after, result, _, _) = run_match(ob, segregated, 0, 0);
+            assert_eq!((result.sell_order_id, result.match_amount), (2, 10));
+            assert_eq!((after.orders[0].active, after.orders[0].amount), (1, 7));
+
+            // Without segregation the odd lot goes first
+            let (_, result, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!(result.sell_order_id, 1);
+        }
+
+        #[test]
+        fn age_histogram_counts_orders_of_known_ages() {
+            let aged = |seconds: u64, sequence: u64| Order { created_ts: 10_000 - seconds, ..limit(100, 5, 0, sequence) };
+            let mut expired = aged(30, 6);
+            expired.active = 0;
+            let ob = book(&[aged(10, 1), aged(59, 2), aged(200, 3), aged(2000, 4), aged(9000, 5), expired]);
+
+            let counts = get_age_histogram(10_000, receiver(), mxe(ob)).to_arcis();
+            assert_eq!(counts, [2, 1, 1, 1]);
+        }
+
+        #[test]
+        fn amendments_keep_priority_on_a_reduce_and_lose_it_on_an_increase() {
+            let bid = limit(100, 10, 0, 1);
+
+            assert_eq!(amend(bid, 100, 6, 0), (100, 6, 1));
+            assert_eq!(amend(bid, 99, 10, 0), (99, 10, 1));
+            assert_eq!(amend(bid, 100, 12, 0), (100, 12, 10));
+        }
+
+        #[test]
+        fn scaled_price_keeps_the_half_tick_of_a_100_101_cross() {
+            let ob = book(&[limit(101, 10, 0, 1), limit(100, 10, 1, 2)]);
+            let tenths = MatchConfig { price_scale: 10, ..config(0, 0) };
+
+            let (_, result, _, _) = run_match(ob, tenths, 0, 0);
+            assert_eq!((result.match_price, result.scaled_price, result.price_scale), (100, 1005, 10));
+        }
+
+        #[test]
+        fn maker_rebate_accrues_apart_from_taker_fees() {
+            // -10 bps to the maker, 30 bps from the taker, on a notional of 1000
+            let ob = book(&[limit(100, 10, 1, 1), limit(100, 10, 0, 2)]);
+            let rebating = MatchConfig { maker_fee_bps: -10, taker_fee_bps: 30, ..config(0, 0) };
+
+            let (ob, _, _, _) = run_match(ob, rebating, 0, 0);
+            assert_eq!((ob.fees_collected, ob.rebates_owed), (3, 1));
+            // The rebate is owed to the maker on top of its proceeds, so the
+            // venue keeps only the difference
+            assert_eq!(get_fee_accounting(mxe(ob)), (3, 1, 2));
+        }
+
+        #[test]
+        fn user_fills_returns_only_that_users_trades() {
+            let trade = |amount: u64, buy_user_id: u128, sell_user_id: u128| TradeRecord {
+                result: MatchResult { matched: 1, match_price: 100, match_amount: amount, ..empty_match_result() },
+                buy_user_id,
+                sell_user_id,
+            };
+            let mut ob = book(&[]);
+            ob.recent_trades[0] = trade(1, 5, 6);
+            ob.recent_trades[1] = trade(2, 7, 8);
+            ob.recent_trades[2] = trade(3, 8, 5);
+            ob.recent_trades[3] = trade(4, 6, 7);
+            ob.trade_count = 4;
+
+            let fills = get_user_fills(receiver().from_arcis(5), mxe(ob)).to_arcis();
+            let amounts: Vec<u64> = fills.iter().filter(|f| f.matched == 1).map(|f| f.match_amount).collect();
+            assert_eq!(amounts, [1, 3]);
+        }
+
+        #[test]
+        fn flags_round_trip_through_add_and_status() {
+            let flags = FLAG_POST_ONLY | FLAG_ALL_OR_NONE | FLAG_AUCTION_ONLY;
+            let ob = add(book(&[]), Order { flags, ..limit(100, 10, 0, 1) });
+
+            let status = status(ob, 1, 2);
+            assert_eq!((status.active, status.flags), (1, flags));
+        }
+
+        #[test]
+        fn sorted_ladder_orders_and_aggregates_levels() {
+            let ob = book(&[
+                limit(99, 3, 0, 1),
+                limit(101, 2, 0, 2),
+                limit(105, 5, 1, 3),
+                limit(99, 4, 0, 4),
+                limit(103, 2, 1, 5),
+                limit(100, 1, 0, 6),
+                limit(103, 1, 1, 7),
+            ]);
+
+            let (bid_prices, bid_volumes, ask_prices, ask_volumes) = get_sorted_ladder(receiver(), mxe(ob)).to_arcis();
+            assert_eq!(bid_prices[..4], [101, 100, 99, 0]);
+            assert_eq!(bid_volumes[..4], [2, 1, 7, 0]);
+            assert_eq!(ask_prices[..3], [103, 105, 0]);
+            assert_eq!(ask_volumes[..3], [3, 5, 0]);
+        }
+
+        #[test]
+        fn modified_since_returns_only_the_recently_touched_order() {
+            let old = Order { user_id: 7, modified_slot: 5, ..limit(100, 10, 0, 1) };
+            let recent = Order { user_id: 7, modified_slot: 5, ..limit(99, 10, 0, 2) };
+            let other_user = Order { modified_slot: 5, ..limit(98, 10, 0, 3) };
+            let reduce_at_slot_20 = |ob: Enc<Mxe, OrderBook>, user_id: u128, order_id: u64| {
+                let amendment = Amendment { user_id, price: 98, amount: 6 };
+                modify_order(order_id, 0, 20, 0, receiver().from_arcis(amendment), ob)
+            };
+
+            let ob = reduce_at_slot_20(mxe(book(&[old, recent, other_user])), 7, 2);
+            let ob = reduce_at_slot_20(ob, 4, 3);
+
+            let updates = get_orders_modified_since(10, receiver().from_arcis(7), ob).to_arcis();
+            let update = |u: &OrderUpdate| (u.present, u.order_id, u.price, u.amount, u.modified_slot);
+            assert_eq!(update(&updates[0]), (1, 2, 98, 6, 20));
+            assert!(updates[1..].iter().all(|u| u.present == 0));
+        }
+
+        #[test]
+        fn pair_rules_matrix_prices_and_gates_each_cell() {
+            let market = |side: u8, sequence: u64| Order { order_type: 0, ..limit(0, 10, side, sequence) };
+            let fresh = MatchConfig { oracle_price: 100, oracle_fresh: 1, ..config(0, 0) };
+            let fill = |orders: &[Order], cfg: MatchConfig| {
+                let (_, result, _, _) = run_match(book(orders), cfg, 0, 0);
+                (result.matched, result.match_price)
+            };
+
+            // market x market: the oracle, unless market pairs are barred
+            assert_eq!(fill(&[market(0, 1), market(1, 2)], fresh), (1, 100));
+            let no_market_pairs = MatchConfig { pair_rules: PAIR_RULE_NO_MARKET_PAIRS, ..fresh };
+            assert_eq!(fill(&[market(0, 1), market(1, 2)], no_market_pairs), (0, 0));
+
+            // market x limit and limit x market: the limit price
+            assert_eq!(fill(&[market(0, 1), limit(102, 10, 1, 2)], fresh), (1, 102));
+            assert_eq!(fill(&[limit(98, 10, 0, 1), market(1, 2)], fresh), (1, 98));
+            // The oracle rule only applies when the limit accepts the oracle price
+            let oracle_rule = MatchConfig { pair_rules: PAIR_RULE_ORACLE_MARKET_LIMIT, ..fresh };
+            assert_eq!(fill(&[market(0, 1), limit(102, 10, 1, 2)], oracle_rule), (1, 102));
+
+            // limit x limit: crossing limits only, at the midpoint
+            assert_eq!(fill(&[limit(104, 10, 0, 1), limit(100, 10, 1, 2)], fresh), (1, 102));
+            assert_eq!(fill(&[limit(99, 10, 0, 1), limit(100, 10, 1, 2)], fresh), (0, 0));
+
+            // all-or-none x partial: only against a counterparty at least as large
+            let aon_buy = Order { flags: FLAG_ALL_OR_NONE, ..limit(100, 10, 0, 1) };
+            assert_eq!(fill(&[aon_buy, limit(100, 6, 1, 2)], fresh), (0, 0));
+            assert_eq!(fill(&[aon_buy, limit(100, 12, 1, 2)], fresh), (1, 100));
+        }
+
+        #[test]
+        fn full_book_evicts_its_worst_order_for_a_better_one() {
+            // A full book of bids from 1 to 100
+            let orders: Vec<Order> = (0..MAX_ORDERS as u64).map(|k| limit(k + 1, 5, 0, k)).collect();
+            let mut ob = book(&orders);
+            ob.next_sequence = MAX_ORDERS as u64;
+            let key = ArcisX25519Pubkey::from_uint8(&[0; 32]);
+            let add_evicting = |eviction_policy: u8, order: Order| {
+                let (ob, evicted, _, _) = add_order(
+                    order.order_id, 0, 0, 0, 0, 0, 0, eviction_policy, 0, 0, 1, 0, key, receiver().from_arcis(order), mxe(ob),
+                );
+                (ob.to_arcis(), evicted.to_arcis())
+            };
+            let better = limit(120, 5, 0, 200);
+
+            // Rejected without eviction
+            let (after, evicted) = add_evicting(0, better);
+            assert_eq!((evicted.cancelled, after.orders[0].order_id), (0, 0));
+
+            let (after, evicted) = add_evicting(1, better);
+            assert_eq!((evicted.cancelled, evicted.user_id, evicted.price), (1, 1, 1));
+            assert_eq!((after.orders[0].order_id, after.orders[0].price), (200, 120));
+            assert_eq!(after.order_count, MAX_ORDERS as u64);
+
+            // An order behind the worst one doesn't get in
+            let (_, evicted) = add_evicting(1, limit(1, 5, 0, 201));
+            assert_eq!(evicted.cancelled, 0);
+        }
+
+        #[test]
+        fn negative_prices_match_at_the_signed_midpoint() {
+            // Offset-binary around 1000: a bid at -4 crosses an ask at -8
+            let ob = book(&[limit(996, 100, 0, 1), limit(992, 100, 1, 2)]);
+            let signed = MatchConfig { price_offset: 1000, price_scale: 10, taker_fee_bps: 100, ..config(0, 0) };
+
+            let (ob, result, _, _) = run_match(ob, signed, 0, 0);
+            // -6, and -6.0 at scale 10
+            assert_eq!((result.match_price, result.scaled_price), (994, 940));
+            // Fees are charged on the magnitude: 1% of 6 * 100
+            assert_eq!(ob.fees_collected, 6);
+        }
+
+        #[test]
+        fn volume_rolls_off_after_24_hours() {
+            let hour = |h: u64| h * 3600;
+            let volume = |ob: OrderBook, now: u64| get_volume_24h(now, receiver(), mxe(ob)).to_arcis();
+            let pair = |ob: &mut OrderBook, sequence: u64| {
+                ob.orders[0] = limit(100, 10, 0, sequence);
+                ob.orders[1] = limit(100, 10, 1, sequence + 1);
+                ob.order_count = 2;
+            };
+
+            let mut ob = book(&[]);
+            pair(&mut ob, 1);
+            let (mut ob, _, _, _) = run_match(ob, MatchConfig { current_ts: hour(1), ..config(0, 0) }, 0, 0);
+            assert_eq!(volume(ob, hour(1)), 1000);
+            assert_eq!(volume(ob, hour(24)), 1000);
+            assert_eq!(volume(ob, hour(25)), 0);
+
+            // A trade a day later reuses the bucket without carrying the old hour
+            pair(&mut ob, 3);
+            let (ob, _, _, _) = run_match(ob, MatchConfig { current_ts: hour(25), ..config(0, 0) }, 0, 0);
+            assert_eq!(volume(ob, hour(25)), 1000);
+        }
+
+        #[test]
+        fn batch_nets_a_taker_crossing_three_makers() {
+            let ob = book(&[limit(100, 10, 1, 1), limit(100, 10, 1, 2), limit(100, 10, 1, 3), limit(100, 30, 0, 4)]);
+
+            let (_, summary, net) = batch(ob, config(0, 0), 8, 16);
+            assert_eq!(summary.fills, 3);
+
+            let entries: Vec<(u64, u8, u64, u64)> =
+                net.iter().filter(|e| e.present == 1).map(|e| (e.order_id, e.side, e.amount, e.notional)).collect();
+            assert_eq!(entries.len(), 4);
+            let taker: Vec<_> = entries.iter().filter(|e| e.1 == 0).collect();
+            assert_eq!(taker, [&(4, 0, 30, 3000)]);
+            let mut makers: Vec<_> = entries.iter().filter(|e| e.1 == 1).map(|e| (e.0, e.2, e.3)).collect();
+            makers.sort();
+            assert_eq!(makers, [(1, 10, 1000), (2, 10, 1000), (3, 10, 1000)]);
+        }
+
+        #[test]
+        fn worst_orders_are_the_furthest_from_the_touch() {
+            let mut cancelled_bid = limit(90, 5, 0, 7);
+            cancelled_bid.active = 0;
+            let market_sell = Order { order_type: 0, ..limit(500, 5, 1, 8) };
+            let ob = book(&[
+                limit(99, 5, 0, 1),
+                limit(95, 5, 0, 2),
+                limit(97, 5, 0, 3),
+                limit(101, 5, 1, 4),
+                limit(108, 5, 1, 5),
+                limit(104, 5, 1, 6),
+                cancelled_bid,
+                market_sell,
+            ]);
+
+            assert_eq!(get_worst_orders(receiver(), mxe(ob)).to_arcis(), (95, 2, 108, 5));
+            assert_eq!(get_worst_orders(receiver(), mxe(book(&[]))).to_arcis(), (0, 0, 0, 0));
+        }
+
+        #[test]
+        fn capacity_alert_fires_once_when_the_threshold_is_crossed() {
+            let key = ArcisX25519Pubkey::from_uint8(&[0; 32]);
+            // Alert at 3 resting orders
+            let add_watched = |ob: OrderBook, order: Order| {
+                let (ob, _, _, near_capacity) =
+                    add_order(order.order_id, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1, 0, key, receiver().from_arcis(order), mxe(ob));
+                (ob.to_arcis(), near_capacity)
+            };
+
+            let mut ob = book(&[]);
+            let mut alerts = Vec::new();
+            for sequence in 1..=5 {
+                let (next, near_capacity) = add_watched(ob, limit(100, 5, 0, sequence));
+                ob = next;
+                alerts.push(near_capacity);
+            }
+            assert_eq!(alerts, [false, false, true, false, false]);
+        }
+
+        #[test]
+        fn price_source_falls_back_when_the_preferred_one_is_missing() {
+            let market = |side: u8, sequence: u64| Order { order_type: 0, ..limit(0, 5, side, sequence) };
+            let pair = [market(0, 1), market(1, 2)];
+            // The same pair with a 98 / 102 touch, so the book midpoint is 100
+            let with_touch = [market(0, 1), market(1, 2), limit(98, 5, 0, 3), limit(102, 5, 1, 4)];
+            let fill = |orders: &[Order], price_source_policy: u8, oracle_fresh: u8| {
+                let cfg = MatchConfig { oracle_price: 101, oracle_fresh, price_source_policy, ..config(0, 0) };
+                let (_, result, _, _) = run_match(book(orders), cfg, 0, 0);
+                (result.matched, result.match_price)
+            };
+
+            // Oracle preferred: the book midpoint stands in for a stale oracle
+            assert_eq!(fill(&with_touch, 1, 1), (1, 101));
+            assert_eq!(fill(&with_touch, 1, 0), (1, 100));
+            // Book preferred: the oracle stands in for a missing touch
+            assert_eq!(fill(&with_touch, 2, 1), (1, 100));
+            assert_eq!(fill(&pair, 2, 1), (1, 101));
+            // With neither source, or oracle only (no fallback), nothing fills
+            assert_eq!(fill(&pair, 1, 0), (0, 0));
+            assert_eq!(fill(&pair, 2, 0), (0, 0));
+            assert_eq!(fill(&with_touch, 0, 0), (0, 0));
+        }
+
+        #[test]
+        fn user_avg_price_weights_by_size() {
+            let own = |price: u64, amount: u64, sequence: u64| Order { user_id: 7, ..limit(price, amount, 0, sequence) };
+            let ob = book(&[own(100, 10, 1), own(110, 30, 2), limit(50, 100, 0, 3)]);
+            let average = |side: u8| {
+                let query = SideQuery { user_id: 7, side };
+                get_user_avg_price(receiver().from_arcis(query), mxe(ob)).to_arcis()
+            };
+
+            // (100 * 10 + 110 * 30) / 40 = 107.5; the other user's bid doesn't count
+            assert_eq!(average(0), 107);
+            assert_eq!(average(1), 0);
+        }
+
+        #[test]
+        fn swapped_priority_swaps_which_order_fills_first() {
+            let ob = book(&[limit(100, 10, 0, 1), limit(100, 10, 0, 2), limit(100, 10, 1, 3)]);
+            let price_time = MatchConfig { priority_policy: 1, ..config(0, 0) };
+
+            let (_, result, _, _) = run_match(ob, price_time, 0, 0);
+            assert_eq!(result.buy_order_id, 1);
+
+            let swapped = swap_priority(1, 2, 0, mxe(ob)).to_arcis();
+            assert_eq!((swapped.orders[0].sequence, swapped.orders[1].sequence), (2, 1));
+            let (_, result, _, _) = run_match(swapped, price_time, 0, 0);
+            assert_eq!(result.buy_order_id, 2);
+        }
+
+        #[test]
+        fn maker_is_skipped_without_its_minimum_edge() {
+            // A resting bid wanting 1% of edge against the 98 touch midpoint
+            let maker = Order { min_edge_bps: 100, ..limit(100, 10, 0, 1) };
+            let ob = book(&[maker, limit(96, 10, 1, 2)]);
+            let fill = |spread_policy: u8| {
+                let (_, result, _, _) = run_match(ob, MatchConfig { spread_policy, ..config(0, 0) }, 0, 0);
+                (result.matched, result.match_price)
+            };
+
+            // At the midpoint the maker gains nothing over the mid
+            assert_eq!(fill(0), (0, 0));
+            // At the taker's limit it buys 2 under the mid, above the 0.98 it needs
+            assert_eq!(fill(1), (1, 96));
+        }
+
+        #[test]
+        fn locked_market_matches_or_holds_per_policy() {
+            let ob = book(&[limit(100, 10, 0, 1), limit(100, 10, 1, 2)]);
+
+            let (_, result, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!((result.matched, result.match_price), (1, 100));
+
+            let (after, result, _, _) = run_match(ob, config(0, 1), 0, 0);
+            assert_eq!(result.matched, 0);
+            assert_eq!(after.order_count, 2);
+        }
+
+        #[test]
+        fn auction_only_order_waits_for_the_crank() {
+            let auction_ask = Order { flags: FLAG_AUCTION_ONLY, ..limit(100, 10, 1, 1) };
+            let ob = book(&[auction_ask, limit(100, 10, 0, 2)]);
+
+            let (_, result, _, _) = run_match(ob, config(0, 0), 0, 0);
+            assert_eq!(result.matched, 0);
+
+            let (ob, summary) = crank_match(mxe(ob), 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0);
+            assert_eq!((summary.to_arcis().fills, ob.to_arcis().order_count), (1, 0));
+        }
+
+        #[test]
+        fn cumulative_depth_is_the_running_sum_of_level_depth() {
+            let ob = book(&[
+                limit(100, 2, 0, 1),
+                limit(99, 7, 0, 2),
+                limit(100, 3, 0, 3),
+                limit(97, 1, 0, 4),
+                limit(101, 3, 1, 5),
+                limit(103, 2, 1, 6),
+            ]);
+            let cumulative = get_depth_cumulative(receiver(), mxe(ob)).to_arcis();
+            assert_eq!(cumulative[..4], [5, 12, 13, 13]);
+            assert_eq!(cumulative[10..13], [3, 5, 5]);
+
+            let depth = depth_buckets(ob);
+            for side in [0, LADDER_LEVELS] {
+                let mut running = 0;
+                for k in side..side + LADDER_LEVELS {
+                    running += depth[k];
+                    assert_eq!(cumulative[k], running);
+                    assert!(k == side || cumulative[k] >= cumulative[k - 1]);
+                }
+            }
+        }
+
+        #[test]
+        fn sub_accounts_in_one_trade_group_do_not_cross() {
+            let bid = Order { trade_group_id: 9, ..limit(100, 10, 0, 1) };
+            let ask = Order { trade_group_id: 9, ..limit(100, 10, 1, 2) };
+
+            let (_, result, _, _) = run_match(book(&[bid, ask]), config(0, 0), 0, 0);
+            assert_eq!(result.matched, 0);
+
+            // An ask from outside the group still takes the bid
+            let (_, result, _, _) = run_match(book(&[bid, ask, limit(100, 10, 1, 3)]), config(0, 0), 0, 0);
+            assert_eq!((result.buy_order_id, result.sell_order_id), (1, 3));
+        }
+
+        #[test]
+        fn batch_summary_aggregates_three_fills() {
+            // A sell of 30 @ 100 sweeps bids of 10 @ 106, 102 and 100, filling at the midpoints
+            let ob = book(&[limit(106, 10, 0, 1), limit(102, 10, 0, 2), limit(100, 10, 0, 3), limit(100, 30, 1, 4)]);
+
+            let (ob, summary, _) = batch(ob, config(0, 0), 8, 16);
+
+            // (103 + 101 + 100) * 10 / 30 = 101.33
+            assert_eq!((summary.fills, summary.volume, summary.vwap), (3, 30, 101));
+            assert_eq!(ob.order_count, 0);
+        }
+
+        #[test]
+        fn order_is_not_matchable_within_its_activation_delay() {
+            let fresh_ask = Order { created_slot: 10, ..limit(100, 10, 1, 2) };
+            let ob = book(&[limit(100, 10, 0, 1), fresh_ask]);
+            let at_slot = |current_slot: u64| MatchConfig { current_slot, activation_delay_slots: 5, ..config(0, 0) };
+
+            let (_, result, _, _) = run_match(ob, at_slot(14), 0, 0);
+            assert_eq!(result.matched, 0);
+
+            let (_, result, _, _) = run_match(ob, at_slot(15), 0, 0);
+            assert_eq!((result.matched, result.sell_order_id), (1, 2));
+        }
+
+        #[test]
+        fn depth_counts_orders_and_volume_per_level() {
+            let ob = book(&[
+                limit(100, 2, 0, 1),
+                limit(99, 7, 0, 2),
+                limit(100, 3, 0, 3),
+                limit(101, 1, 1, 4),
+                limit(101, 1, 1, 5),
+                limit(101, 1, 1, 6),
+            ]);
+
+            let (volumes, counts) = get_depth_counts(receiver(), mxe(ob)).to_arcis();
+            assert_eq!(volumes[..3], [5, 7, 0]);
+            assert_eq!(counts[..3], [2, 1, 0]);
+            // Asks start after the bid levels
+            assert_eq!(volumes[LADDER_LEVELS..LADDER_LEVELS + 2], [3, 0]);
+            assert_eq!(counts[LADDER_LEVELS..LADDER_LEVELS + 2], [3, 0]);
+        }
+
+        #[test]
+        fn half_filled_ioc_reports_the_fill_and_the_cancelled_rest() {
+            let ob = book(&[limit(99, 4, 1, 1), limit(101, 6, 1, 2), limit(102, 50, 1, 3)]);
+            let taker = limit(101, 20, 0, 4);
+
+            let (ob, report) = submit_ioc(
+                4, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 16, receiver().from_arcis(taker), mxe(ob),
+            );
+            let report = report.to_arcis();
+
+            // (99 * 4 + 101 * 6) / 10 = 100.2
+            assert_eq!((report.filled_amount, report.cancelled_amount, report.avg_fill_price), (10, 10, 100));
+            // Nothing of the taker rests
+            assert!(ob.to_arcis().orders.iter().all(|o| o.side == 1 || o.active == 0));
+        }
+
+        #[test]
+        fn migrate_book_keeps_every_order_and_zeroes_the_new_field() {
+            let current = book(&[limit(100, 10, 0, 1), limit(101, 4, 1, 2), Order { metadata: 7, ..limit(99, 3, 0, 3) }]);
+            let legacy = |o: Order| LegacyOrder {
+                price: o.price,
+                amount: o.amount,
+                side: o.side,
+                order_type: o.order_type,
+                user_id: o.user_id,
+                metadata: o.metadata,
+                oco_group: o.oco_group,
+                flags: o.flags,
+                min_edge_bps: o.min_edge_bps,
+                trade_group_id: o.trade_group_id,
+                active: o.active,
+                sequence: o.sequence,
+                order_id: o.order_id,
+                created_slot: o.created_slot,
+                created_ts: o.created_ts,
+                priority_price: o.priority_price,
+                modified_slot: o.modified_slot,
+            };
+            let old = LegacyOrderBook {
+                orders: current.orders.map(legacy),
+                order_count: current.order_count,
+                next_sequence: 3,
+                throttle_slot: current.throttle_slot,
+                fills_this_slot: current.fills_this_slot,
+                allowlist: current.allowlist,
+                last_trade_price: 100,
+                last_trade_amount: current.last_trade_amount,
+                fees_collected: 12,
+                rebates_owed: current.rebates_owed,
+                book_version: current.book_version,
+                change_log: current.change_log,
+                heartbeat_users: current.heartbeat_users,
+                heartbeat_ts: current.heartbeat_ts,
+                user_stats: current.user_stats,
+                recent_trades: current.recent_trades,
+                trade_count: current.trade_count,
+                volume_buckets: current.volume_buckets,
+                volume_bucket_hours: current.volume_bucket_hours,
+                depth_base: current.depth_base,
+                depth_base_version: current.depth_base_version,
+            };
+
+            let migrated = migrate_book(mxe(old)).to_arcis();
+
+            let key = |o: &Order| (o.order_id, o.price, o.amount, o.side, o.user_id, o.active, o.sequence, o.metadata);
+            assert_eq!(migrated.orders.iter().map(key).collect::<Vec<_>>(), current.orders.iter().map(key).collect::<Vec<_>>());
+            assert!(migrated.orders.iter().all(|o| o.notify_key == no_notify_key()));
+            assert_eq!((migrated.order_count, migrated.next_sequence), (3, 3));
+            assert_eq!((migrated.last_trade_price, migrated.fees_collected), (100, 12));
+        }
+
+        #[test]
+        fn min_notional_rejects_a_small_order() {
+            let key = ArcisX25519Pubkey::from_uint8(&[0; 32]);
+            // At least 1000 in quote
+            let add_checked = |ob: OrderBook, order: Order| {
+                let (ob, _, _, _) =
+                    add_order(order.order_id, 0, 0, 0, 0, 0, 0, 0, 0, 1000, 1, 0, key, receiver().from_arcis(order), mxe(ob));
+                ob.to_arcis()
+            };
+
+            // Five lots is a fine size, but only 500 in quote at 100
+            let ob = add_checked(book(&[]), limit(100, 5, 0, 1));
+            assert_eq!(ob.order_count, 0);
+            let ob = add_checked(ob, limit(100, 10, 0, 2));
+            assert_eq!(ob.order_count, 1);
+        }
+
+        #[test]
+        fn dust_remainder_is_cancelled_only_under_the_dust_policy() {
+            // Filling 10 leaves the ask 2 short of a lot of 5
+            let ob = book(&[limit(100, 10, 0, 1), limit(100, 12, 1, 2)]);
+
+            let (kept, _, _, _) = run_match_with_dust(ob, config(0, 0), 0, 0, 0, 5, 0);
+            assert_eq!((kept.order_count, kept.orders[1].active, kept.orders[1].amount), (1, 1, 2));
+
+            let (cancelled, _, _, _) = run_match_with_dust(ob, config(0, 0), 0, 0, 1, 5, 0);
+            assert_eq!((cancelled.order_count, cancelled.orders[1].active), (0, 0));
+
+            // A remainder worth less than the minimum notional is dust too
+            let (cancelled, _, _, _) = run_match_with_dust(ob, config(0, 0), 0, 0, 1, 0, 300);
+            assert_eq!(cancelled.order_count, 0);
+        }
+
+        #[test]
+        fn open_interest_sums_resting_notional_per_side() {
+            let market_buy = Order { order_type: 0, ..limit(500, 100, 0, 4) };
+            let ob = book(&[limit(100, 10, 0, 1), limit(99, 5, 0, 2), limit(101, 3, 1, 3), market_buy]);
+            let interest = |amount_unit: u64| get_open_interest(amount_unit, 0, receiver(), mxe(ob)).to_arcis();
+
+            assert_eq!(interest(1), (1000 + 495, 303));
+            // In tenths of a lot each order's notional is floored on its own
+            assert_eq!(interest(10), (100 + 49, 30));
+        }
+
+        #[test]
+        fn midpoint_rounds_odd_sums_by_mode() {
+            let buy = order(101, 0, 1);
+            let sell = order(100, 1, 2);
+            assert_eq!(midpoint(buy, sell, 0), 100);
+            assert_eq!(midpoint(buy, sell, 1), 101);
+            // The buy order is older, so it is the maker and rounding goes down
+            assert_eq!(midpoint(buy, sell, 2), 100);
+            assert_eq!(midpoint(order(101, 0, 3), sell, 2), 101);
+        }
+
+        #[test]
+        fn midpoint_is_exact_for_even_sums() {
+            let buy = order(102, 0, 1);
+            let sell = order(100, 1, 2);
+            for rounding in 0..3 {
+                assert_eq!(midpoint(buy, sell, rounding), 101);
+            }
+        }
+
+        #[test]
+        fn midpoint_does_not_overflow_near_u64_max() {
+            let buy = order(u64::MAX, 0, 1);
+            let sell = order(u64::MAX - 1, 1, 2);
+            assert_eq!(midpoint(buy, sell, 0), u64::MAX - 1);
+            assert_eq!(midpoint(buy, sell, 1), u64::MAX);
+        }
+
+        #[test]
+        fn depth_buckets_hold_volume_per_price_level() {
+            let mut filled_buy = limit(100, 100, 0, 3);
+            filled_buy.active = 0;
+            let ob = book(&[
+                limit(100, 5, 0, 1),
+                limit(99, 7, 0, 2),
+                filled_buy,
+                limit(100, 4, 0, 4),
+                limit(101, 3, 1, 5),
+                limit(103, 2, 1, 6),
+            ]);
+            let depth = depth_buckets(ob);
+
+            // Bids best (highest) first, asks best (lowest) first
+            assert_eq!(depth[..3], [9, 7, 0]);
+            assert_eq!(depth[10..13], [3, 2, 0]);
+        }
+
+        #[test]
+        fn depth_range_is_a_slice_of_the_full_depth() {
+            let ob = book(&[
+                limit(105, 1, 0, 1),
+                limit(104, 2, 0, 2),
+                limit(103, 3, 0, 3),
+                limit(102, 4, 0, 4),
+                limit(101, 5, 0, 5),
+                limit(100, 6, 0, 6),
+            ]);
+            let depth = depth_buckets(ob);
+            let range = get_depth_range(2, 5, receiver(), mxe(ob)).to_arcis();
+
+            assert_eq!(range[..4], depth[2..6]);
+            assert_eq!(range[..4], [3, 4, 5, 6]);
+            assert_eq!(range[4..], [0; MAX_DEPTH_RANGE - 4]);
+        }
+
+        #[test]
+        fn depth_delta_lists_only_the_changed_bucket() {
+            let ob = book(&[limit(100, 5, 0, 1), limit(99, 7, 0, 2), limit(101, 3, 1, 3)]);
+            let (ob, snapshot) = snapshot_depth(receiver(), mxe(ob));
+            let mut ob = ob.to_arcis();
+            let version = snapshot.to_arcis().book_version;
+
+            // One more bid joins the second level
+            ob.orders[3] = limit(99, 4, 0, 4);
+            ob.order_count = 4;
+            let delta = get_depth_delta(version, receiver(), mxe(ob)).to_arcis();
+
+            assert_eq!(delta.valid, 1);
+            assert_eq!(delta.count, 1);
+            assert_eq!(delta.bucket_indices[0], 1);
+            assert_eq!(delta.volumes[0], 11);
+        }
+
+        #[test]
+        fn quote_depth_prices_each_level() {
+            let ob = book(&[limit(100, 5, 0, 1), limit(99, 10, 0, 2), limit(101, 3, 1, 3)]);
+
+            // Amounts in hundredths of a lot: 5 * 100 / 100 and 10 * 99 / 100
+            let depth = quote_depth_buckets(ob, 100, 0);
+            assert_eq!(depth[..3], [5, 9, 0]);
+            assert_eq!(depth[10..12], [3, 0]);
+        }
+
+        #[test]
+        fn depth_buckets_are_zero_for_an_empty_book() {
+            assert_eq!(depth_buckets(book(&[])), [0; DEPTH_BUCKETS]);
+        }
+
+        #[test]
+        fn mul_div_uses_a_wide_intermediate() {
+            assert_eq!(mul_div(6, 7, 2), 21);
+            assert_eq!(mul_div(u64::MAX, 4, 8), u64::MAX / 2);
+        }
+
+        #[test]
+        fn mul_div_saturates_and_handles_zero_divisor() {
+            assert_eq!(mul_div(u64::MAX, u64::MAX, 1), u64::MAX);
+            assert_eq!(mul_div(5, 5, 0), 0);
+        }
+
+        #[test]
+        fn fee_on_applies_the_rounding_mode() {
+            // 12345 * 30 bps = 37.035
+            assert_eq!(fee_on(12345, 30, 0), 37);
+            assert_eq!(fee_on(12345, 30, 1), 38);
+            assert_eq!(fee_on(12345, 30, 2), 37);
+            // 15 * 1000 bps = 1.5 rounds half up
+            assert_eq!(fee_on(15, 1000, 2), 2);
+        }
+
+        #[test]
+        fn fee_on_exact_fees_never_round_up() {
+            assert_eq!(fee_on(10000, 25, 1), 25);
+            assert_eq!(fee_on(0, 25, 1), 0);
+            assert_eq!(fee_on(u64::MAX, 10000, 0), u64::MAX as u128);
+        }
+
+        #[test]
+        fn imbalance_bps_is_the_buy_share() {
+            assert_eq!(imbalance_bps(3, 1), 7500);
+            assert_eq!(imbalance_bps(0, 9), 0);
+            assert_eq!(imbalance_bps(0, 0), 5000);
+        }
+
+        #[test]
+        fn imbalance_bps_handles_volumes_past_u64() {
+            let side = (u64::MAX as u128) * MAX_ORDERS as u128;
+            assert_eq!(imbalance_bps(side, side), 5000);
+            assert_eq!(imbalance_bps(side, 0), 10000);
+        }
+
+        #[test]
+        fn priority_price_snaps_to_the_passive_tick() {
+            assert_eq!(priority_price(1007, 0, 5), 1005);
+            assert_eq!(priority_price(1007, 1, 5), 1010);
+            assert_eq!(priority_price(1005, 0, 5), 1005);
+            assert_eq!(priority_price(1005, 1, 5), 1005);
+        }
+
+        #[test]
+        fn priority_price_is_unchanged_without_a_tick() {
+            assert_eq!(priority_price(1007, 0, 0), 1007);
+            assert_eq!(priority_price(1007, 1, 0), 1007);
+        }
+
+        #[test]
+        fn crosses_by_requires_the_tolerance() {
+            assert!(crosses_by(105, 100, config(5, 0)));
+            assert!(!crosses_by(104, 100, config(5, 0)));
+            assert!(!crosses_by(99, 100, config(0, 0)));
+        }
+
+        #[test]
+        fn crosses_by_holds_locked_pairs_under_lock_policy() {
+            assert!(crosses_by(100, 100, config(0, 0)));
+            assert!(!crosses_by(100, 100, config(0, 1)));
+            assert!(crosses_by(101, 100, config(0, 1)));
+        }
+        #[test]
+        fn match_fraction_caps_a_large_taker() {
+            let ob = book(&[limit(100, 10, 1, 1), limit(101, 10, 1, 2), limit(101, 50, 0, 3)]);
+            let cfg = MatchConfig { max_match_fraction_bps: 2500, ..config(0, 0) };
+
+            let (ob, result, _) = match_once(ob, cfg, [0; MAX_ORDERS], true);
+            assert_eq!(result.matched, 1);
+            assert_eq!(result.match_amount, 5);
+            assert_eq!(ob.orders[2].amount, 45);
+
+            let ob = book(&[limit(100, 10, 1, 1), limit(100, 10, 1, 2), limit(101, 50, 0, 3)]);
+            let (ob, result) = match_pro_rata(ob, cfg, 0, true);
+            assert_eq!(result.matched, 1);
+            assert_eq!(result.match_amount, 5);
+            assert_eq!(ob.orders[2].amount, 45);
+        }
+
+        #[test]
+        fn match_fraction_rounding_to_zero_reports_no_fill() {
+            let ob = book(&[limit(100, 3, 1, 1), limit(101, 50, 0, 2)]);
+            let cfg = MatchConfig { max_match_fraction_bps: 1000, ..config(0, 0) };
+
+            let (after, result, _) = match_once(ob, cfg, [0; MAX_ORDERS], true);
+            assert_eq!(result.matched, 0);
+            assert_eq!(after.orders[1].amount, 50);
+
+            let (after, result) = match_pro_rata(ob, cfg, 0, true);
+            assert_eq!(result.matched, 0);
+            assert_eq!(after.fills_this_slot, 0);
+            assert_eq!(after.orders[0].amount, 3);
+            assert_eq!(after.orders[1].amount, 50);
+        }
+
+        // Run the match_orders circuit under `cfg` (no dust policy or keeper
+        // reward). The fill notices keep the key they are encrypted to
+        fn run_match(
+            ob: OrderBook,
+            cfg: MatchConfig,
+            match_mode: u8,
+            seed: u64,
+        ) -> (OrderBook, MatchResult, Enc<Shared, FillNotice>, Enc<Shared, FillNotice>) {
+            run_match_with_dust(ob, cfg, match_mode, seed, 0, 0, 0)
+        }
+
+        // run_match with a dust policy, lot size and minimum notional
+        fn run_match_with_dust(
+            ob: OrderBook,
+            cfg: MatchConfig,
+            match_mode: u8,
+            seed: u64,
+            dust_policy: u8,
+            lot_size: u64,
+            min_notional: u64,
+        ) -> (OrderBook, MatchResult, Enc<Shared, FillNotice>, Enc<Shared, FillNotice>) {
+            let (ob, result, buy_notice, sell_notice, _, _, _) = match_orders(
+                mxe(ob),
+                cfg.oracle_price,
+                cfg.oracle_fresh,
+                cfg.current_slot,
+                16,
+                cfg.rounding,
+                match_mode,
+                seed,
+                cfg.fill_increment,
+                cfg.maker_fee_bps,
+                cfg.taker_fee_bps,
+                cfg.fee_rounding,
+                cfg.amount_unit,
+                cfg.priority_policy,
+                cfg.max_take_per_maker,
+                cfg.spread_policy,
+                cfg.round_lot_size,
+                cfg.match_cooldown_slots,
+                cfg.price_scale,
+                cfg.cross_tolerance,
+                cfg.pair_rules,
+                cfg.price_offset,
+                cfg.current_ts,
+                cfg.price_source_policy,
+                cfg.max_match_fraction_bps,
+                cfg.lock_policy,
+                cfg.activation_delay_slots,
+                dust_policy,
+                lot_size,
+                min_notional,
+                0,
+            );
+            (ob.to_arcis(), result.to_arcis(), buy_notice, sell_notice)
+        }
+
+        // Replay a recorded result against `ob` under `cfg`
+        fn replay(ob: OrderBook, cfg: MatchConfig, match_mode: u8, seed: u64, recorded: MatchResult) -> bool {
+            replay_match(
+                mxe(ob),
+                cfg.oracle_price,
+                cfg.oracle_fresh,
+                cfg.current_slot,
+                cfg.rounding,
+                match_mode,
+                seed,
+                cfg.fill_increment,
+                cfg.priority_policy,
+                cfg.max_take_per_maker,
+                cfg.spread_policy,
+                cfg.round_lot_size,
+                cfg.match_cooldown_slots,
+                cfg.price_scale,
+                cfg.cross_tolerance,
+                cfg.pair_rules,
+                cfg.price_offset,
+                cfg.price_source_policy,
+                cfg.max_match_fraction_bps,
+                cfg.lock_policy,
+                cfg.activation_delay_slots,
+                mxe(recorded),
+            )
+        }
+
+        #[test]
+        fn replay_reproduces_a_match_from_the_pre_trade_book() {
+            let pre_trade = book(&[limit(100, 10, 1, 1), limit(100, 6, 1, 2), limit(101, 12, 0, 3)]);
+            let cfg = config(0, 0);
+            let (post_trade, recorded, _, _) = run_match(pre_trade, cfg, 0, 0);
+            assert_eq!(recorded.matched, 1);
+
+            assert!(replay(pre_trade, cfg, 0, 0, recorded));
+
+            // Against the post-trade book (all a replay had before the
+            // snapshot) the same match can't be reproduced
+            assert!(!replay(post_trade, cfg, 0, 0, recorded));
+
+            // Nor can a doctored result
+            let inflated = MatchResult { match_amount: recorded.match_amount + 1, ..recorded };
+            assert!(!replay(pre_trade, cfg, 0, 0, inflated));
+        }
+
+        #[test]
+        fn replay_reproduces_a_pro_rata_match_with_its_seed() {
+            let pre_trade = book(&[
+                limit(100, 10, 1, 1),
+                limit(100, 10, 1, 2),
+                limit(100, 10, 1, 3),
+                limit(100, 10, 0, 4),
+            ]);
+            let cfg = config(0, 0);
+            let (_, recorded, _, _) = run_match(pre_trade, cfg, 1, 5);
+
+            assert!(replay(pre_trade, cfg, 1, 5, recorded));
+            assert!(!replay(pre_trade, cfg, 1, 6, recorded));
+        }
+
+        #[test]
+        fn fill_notices_go_to_each_party_under_its_own_key() {
+            let buyer_key = ArcisX25519Pubkey::from_uint8(&[7; 32]);
+            let seller_key = ArcisX25519Pubkey::from_uint8(&[9; 32]);
+            let ask = Order { notify_key: seller_key, ..limit(100, 10, 1, 1) };
+            let bid = Order { notify_key: buyer_key, ..limit(100, 4, 0, 2) };
+            let (_, result, buy_notice, sell_notice) = run_match(book(&[ask, bid]), config(0, 0), 0, 0);
+            assert_eq!(result.match_amount, 4);
+
+            // Each notice is encrypted to its own party and describes only its order
+            assert_eq!(buy_notice.owner.key, buyer_key);
+            assert_eq!(sell_notice.owner.key, seller_key);
+            let (buy, sell) = (buy_notice.to_arcis(), sell_notice.to_arcis());
+            assert_eq!((buy.filled, buy.order_id, buy.fill_amount, buy.remaining), (1, 2, 4, 0));
+            assert_eq!((sell.filled, sell.order_id, sell.fill_amount, sell.remaining), (1, 1, 4, 6));
+            assert_eq!((buy.fill_price, sell.fill_price), (100, 100));
+        }
+
+        #[test]
+        fn fill_notice_is_empty_for_an_order_without_a_key() {
+            let ask = Order { notify_key: ArcisX25519Pubkey::from_uint8(&[9; 32]), ..limit(100, 10, 1, 1) };
+            let (_, _, buy_notice, sell_notice) = run_match(book(&[ask, limit(100, 4, 0, 2)]), config(0, 0), 0, 0);
+
+            let buy = buy_notice.to_arcis();
+            assert_eq!((buy.filled, buy.order_id, buy.fill_amount), (0, 0, 0));
+            assert_eq!(sell_notice.to_arcis().filled, 1);
+        }
+
+        #[test]
+        fn crank_clears_several_crossing_orders_at_once() {
+            let ob = book(&[
+                limit(101, 10, 0, 1),
+                limit(102, 10, 0, 2),
+                limit(103, 10, 0, 3),
+                limit(99, 10, 1, 4),
+                limit(100, 10, 1, 5),
+                limit(100, 10, 1, 6),
+                limit(110, 10, 1, 7),
+            ]);
+            let (ob, summary) = crank_match(
+                mxe(ob), 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0,
+            );
+            let (ob, summary) = (ob.to_arcis(), summary.to_arcis());
+
+            assert_eq!(summary.fills, 3);
+            assert_eq!(summary.volume, 30);
+            assert_eq!(ob.order_count, 1);
+            assert_eq!(ob.orders[6].active, 1);
+            assert!(ob.orders[..6].iter().all(|o| o.active == 0));
+        }
+
+        // Sweep the oldest market order under the given increment, cooldown and slot cap
+        fn sweep(ob: OrderBook, slot: u64, increment: u64, cooldown: u64, cap: u64) -> (OrderBook, [MatchResult; MAX_IOC_FILLS]) {
+            let (ob, fills) = match_market_sweep(slot, 0, 0, 0, 0, 1, 1, 0, 0, increment, cooldown, cap, receiver(), mxe(ob));
+            (ob.to_arcis(), fills.to_arcis())
+        }
+
+        #[test]
+        fn market_sweep_fills_a_level_oldest_first_then_the_next() {
+            let market_buy = Order { order_type: 0, ..limit(0, 25, 0, 4) };
+            let ob = book(&[limit(100, 10, 1, 2), limit(100, 10, 1, 1), limit(101, 10, 1, 3), market_buy]);
+
+            let (ob, fills) = sweep(ob, 0, 0, 0, 16);
+            let sellers: Vec<u64> = fills[..3].iter().map(|f| f.sell_order_id).collect();
+            let amounts: Vec<u64> = fills[..3].iter().map(|f| f.match_amount).collect();
+            assert_eq!(sellers, vec![1, 2, 3]);
+            assert_eq!(amounts, vec![10, 10, 5]);
+            assert_eq!(fills[3].matched, 0);
+            assert_eq!(ob.orders[2].amount, 5);
+            assert_eq!(ob.orders[3].active, 0);
+        }
+
+        #[test]
+        fn market_sweep_honours_increment_cooldown_and_slot_cap() {
+            let market_buy = Order { order_type: 0, ..limit(0, 25, 0, 3) };
+            let ob = book(&[limit(100, 13, 1, 1), limit(100, 20, 1, 2), market_buy]);
+
+            // 13 floors to 10, then the remaining 15 floors to 10
+            let (after, fills) = sweep(ob, 0, 5, 0, 16);
+            assert_eq!((fills[0].match_amount, fills[1].match_amount), (10, 15));
+            assert_eq!(after.orders[2].amount, 0);
+            let (after, fills) = sweep(ob, 0, 10, 0, 16);
+            assert_eq!((fills[0].match_amount, fills[1].match_amount), (10, 10));
+            assert_eq!(after.orders[2].amount, 5);
+
+            // The first maker is cooling, so the sweep starts at the second
+            let cooling = with_fill(ob, ob.orders[0].user_id, 10);
+            let (_, fills) = sweep(cooling, 12, 0, 5, 16);
+            assert_eq!(fills[0].sell_order_id, 2);
+
+            // One fill left in this slot's budget
+            let (after, fills) = sweep(ob, 0, 0, 0, 1);
+            assert_eq!(fills[0].matched, 1);
+            assert_eq!(fills[1].matched, 0);
+            assert_eq!(after.orders[2].amount, 12);
+        }
+
+        #[test]
+        fn fill_increment_floors_a_fill_and_leaves_the_rest() {
+            let ob = book(&[limit(100, 103, 1, 1), limit(100, 200, 0, 2)]);
+            let cfg = MatchConfig { fill_increment: 10, ..config(0, 0) };
+
+            let (ob, result, _) = match_once(ob, cfg, [0; MAX_ORDERS], true);
+            assert_eq!(result.match_amount, 100);
+            assert_eq!(ob.orders[0].amount, 3);
+            assert_eq!(ob.orders[1].amount, 100);
+        }
+
+        #[test]
+        fn fill_increment_applies_to_every_pro_rata_share() {
+            // The fill of 45 floors to 40, whose 2:1 shares of 26 and 13 floor
+            // to 20 and 10; the remaining 10 goes to the seeded maker and the
+            // 5 that never filled stays with the taker
+            let ob = book(&[limit(100, 60, 1, 1), limit(100, 30, 1, 2), limit(100, 45, 0, 3)]);
+            let cfg = MatchConfig { fill_increment: 10, ..config(0, 0) };
+
+            let (ob, result) = match_pro_rata(ob, cfg, 0, true);
+            assert_eq!(result.match_amount, 40);
+            assert_eq!(ob.orders[0].amount, 30);
+            assert_eq!(ob.orders[1].amount, 20);
+            assert_eq!(ob.orders[2].amount, 5);
+        }
+
+        #[test]
+        fn remainder_seed_is_deterministic_and_rotates_across_makers() {
+            // Three equal makers split a taker of 10 as 3/3/3 plus one lot of
+            // remainder, which goes to the maker at rank seed % 3
+            let ob = book(&[
+                limit(100, 10, 1, 1),
+                limit(100, 10, 1, 2),
+                limit(100, 10, 1, 3),
+                limit(100, 10, 0, 4),
+            ]);
+
+            let mut winners = Vec::new();
+            for seed in 0..3 {
+                let (first, result) = match_pro_rata(ob, config(0, 0), seed, true);
+                let (second, again) = match_pro_rata(ob, config(0, 0), seed, true);
+                assert_eq!(result.sell_order_id, again.sell_order_id);
+                assert_eq!(first.orders[0].amount, second.orders[0].amount);
+
+                let winner = result.sell_order_id as usize - 1;
+                assert_eq!(first.orders[winner].amount, 6);
+                winners.push(winner);
+            }
+            winners.sort();
+            assert_eq!(winners, vec![0, 1, 2]);
+        }
+
+        // A book where `user` last filled at `slot`
+        fn with_fill(mut ob: OrderBook, user: u128, slot: u64) -> OrderBook {
+            ob.user_stats[0] = UserStats { user_id: user, lifetime_volume: 1, last_fill_slot: slot };
+            ob
+        }
+
+        #[test]
+        fn cooldown_skips_a_recent_filler_until_it_expires() {
+            let maker = limit(100, 10, 1, 1);
+            let ob = with_fill(book(&[maker, limit(100, 10, 0, 2)]), maker.user_id, 10);
+            let cooling = MatchConfig { match_cooldown_slots: 5, current_slot: 14, ..config(0, 0) };
+            let expired = MatchConfig { current_slot: 15, ..cooling };
+
+            let (_, result, _) = match_once(ob, cooling, [0; MAX_ORDERS], true);
+            assert_eq!(result.matched, 0);
+            let (_, result, _) = match_once(ob, expired, [0; MAX_ORDERS], true);
+            assert_eq!(result.matched, 1);
+        }
+
+        #[test]
+        fn cooldown_excludes_pro_rata_makers_and_takers() {
+            let cooling_maker = limit(100, 10, 1, 1);
+            let ob = book(&[cooling_maker, limit(100, 10, 1, 2), limit(100, 10, 0, 3)]);
+            let ob = with_fill(ob, cooling_maker.user_id, 10);
+            let cfg = MatchConfig { match_cooldown_slots: 5, current_slot: 12, ..config(0, 0) };
+
+            let (after, result) = match_pro_rata(ob, cfg, 0, true);
+            assert_eq!(result.sell_order_id, 2);
+            assert_eq!(after.orders[0].amount, 10);
+            assert_eq!(after.orders[1].active, 0);
+
+            let taker = limit(100, 10, 0, 3);
+            let ob = with_fill(book(&[limit(100, 10, 1, 1), taker]), taker.user_id, 10);
+            let (_, result) = match_pro_rata(ob, cfg, 0, true);
+            assert_eq!(result.matched, 0);
+        }
+
+        #[test]
+        fn pro_rata_does_not_trade_through_an_ineligible_touch() {
+            // The ask at 100 belongs to the taker's own user, so the next
+            // eligible ask is 101: filling there would trade through 100
+            let taker = limit(105, 10, 0, 3);
+            let own_ask = Order { user_id: taker.user_id, ..limit(100, 10, 1, 1) };
+            let ob = book(&[own_ask, limit(101, 10, 1, 2), taker]);
+
+            let (after, result) = match_pro_rata(ob, config(0, 0), 0, true);
+            assert_eq!(result.matched, 0);
+            assert_eq!(after.orders[1].amount, 10);
+            assert_eq!(after.orders[2].amount, 10);
+
+            // Without the blocked touch, 101 is the best ask and fills
+            let ob = book(&[limit(101, 10, 1, 2), taker]);
+            let (_, result) = match_pro_rata(ob, config(0, 0), 0, true);
+            assert_eq!(result.match_amount, 10);
+            assert_eq!(result.match_price, 101);
+        }
+
+        // Amend `order` to price @ amount on a book holding only it and return
+        // its (price, amount, sequence) afterwards
+        fn amend(order: Order, price: u64, amount: u64, reduce_only: u8) -> (u64, u64, u64) {
+            let mut ob = book(&[order]);
+            ob.next_sequence = 10;
+            let amendment = Amendment { user_id: order.user_id, price, amount };
+            let ob = modify_order(order.order_id, 0, 0, reduce_only, receiver().from_arcis(amendment), mxe(ob));
+            let after = ob.to_arcis().orders[0];
+            (after.price, after.amount, after.sequence)
+        }
+
+        #[test]
+        fn reduce_only_blocks_growing_or_more_aggressive_amendments() {
+            let bid = limit(100, 10, 0, 1);
+            let ask = limit(100, 10, 1, 2);
+
+            // A draining book ignores anything that adds risk
+            assert_eq!(amend(bid, 100, 12, 1), (100, 10, 1));
+            assert_eq!(amend(bid, 101, 10, 1), (100, 10, 1));
+            assert_eq!(amend(ask, 99, 10, 1), (100, 10, 2));
+
+            // Shrinking or backing away from the touch still goes through
+            assert_eq!(amend(bid, 99, 6, 1), (99, 6, 1));
+            assert_eq!(amend(ask, 101, 10, 1), (101, 10, 2));
+
+            // Outside draining an aggressive reprice is applied and requeued
+            assert_eq!(amend(bid, 101, 10, 0), (101, 10, 10));
+        }
+
+        #[test]
+        fn stale_users_have_their_orders_swept_after_the_ttl() {
+            let ob = book(&[limit(100, 10, 0, 1), limit(101, 10, 1, 2), limit(99, 5, 0, 3)]);
+            let (stale_user, live_user) = (ob.orders[0].user_id, ob.orders[1].user_id);
+
+            let (ob, recorded) = heartbeat(100, receiver().from_arcis(stale_user), mxe(ob));
+            assert_eq!(recorded.to_arcis(), 1);
+            let (ob, _) = heartbeat(170, receiver().from_arcis(live_user), mxe(ob.to_arcis()));
+
+            let ob = cancel_stale_users(200, 60, 0, ob).to_arcis();
+            assert_eq!(ob.orders[0].active, 0);
+            assert_eq!(ob.orders[1].active, 1);
+            // Users who never heartbeat aren't enrolled, so nothing is swept for them
+            assert_eq!(ob.orders[2].active, 1);
+            assert_eq!(ob.order_count, 2);
+            assert!(!ob.heartbeat_users.contains(&stale_user));
+            assert!(ob.heartbeat_users.contains(&live_user));
+        }
+
+        // Test-only corruptions of a consistent book, one per invariant bit
+        // check_invariants reports
+        fn corrupt(mut ob: OrderBook, violation: u8) -> OrderBook {
+            if violation & 1 != 0 {
+                ob.order_count = ob.order_count + 1;
+            }
+            if violation & 2 != 0 {
+                ob.orders[0].amount = 0;
+            }
+            if violation & 4 != 0 {
+                ob.orders[2].order_id = ob.orders[1].order_id;
+            }
+            ob
+        }
+
+        fn invariant_violations(ob: OrderBook) -> u8 {
+            check_invariants(receiver(), mxe(ob)).to_arcis()
+        }
+
+        #[test]
+        fn check_invariants_flags_each_corruption() {
+            let ob = book(&[limit(100, 10, 0, 1), limit(101, 10, 1, 2), limit(99, 10, 0, 3)]);
+            assert_eq!(invariant_violations(ob), 0);
+
+            for violation in [1, 2, 4, 7] {
+                assert_eq!(invariant_violations(corrupt(ob, violation)), violation);
+            }
+
+            // An inactive slot sharing an id with a live order is no violation
+            let cancelled = Order { active: 0, ..limit(98, 5, 0, 4) };
+            let mut ob = book(&[limit(100, 10, 0, 1), limit(101, 10, 1, 2), cancelled]);
+            ob.order_count = 2;
+            ob.orders[2].order_id = ob.orders[0].order_id;
+            assert_eq!(invariant_violations(ob), 0);
+        }
+
+        #[test]
+        fn book_pages_reassemble_into_the_full_book() {
+            let orders: Vec<Order> = (0..37u64).map(|k| limit(90 + k % 20, k + 1, (k % 2) as u8, k)).collect();
+            let mut ob = book(&orders);
+            // Leave a hole, so inactive slots are paged too
+            ob.orders[12].active = 0;
+
+            let mut pages = Vec::new();
+            for start in (0..MAX_ORDERS).step_by(BOOK_PAGE) {
+                pages.extend(get_book_page(start as u64, receiver(), mxe(ob)).to_arcis());
+            }
+
+            let key = |o: &Order| (o.order_id, o.price, o.amount, o.side, o.user_id, o.active, o.sequence);
+            assert_eq!(pages.len(), MAX_ORDERS);
+            assert_eq!(pages.iter().map(key).collect::<Vec<_>>(), ob.orders.iter().map(key).collect::<Vec<_>>());
+        }
     }
 }
@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
 use arcium_anchor::prelude::*;
 
 // Computation definition offsets for encrypted instructions
@@ -6,6 +7,248 @@ const COMP_DEF_OFFSET_ADD_ORDER: u32 = comp_def_offset("add_order");
 const COMP_DEF_OFFSET_MATCH_ORDERS: u32 = comp_def_offset("match_orders");
 const COMP_DEF_OFFSET_CANCEL_ORDER: u32 = comp_def_offset("cancel_order");
 const COMP_DEF_OFFSET_GET_ORDERBOOK_DEPTH: u32 = comp_def_offset("get_orderbook_depth");
+const COMP_DEF_OFFSET_GET_QUEUE_POSITION: u32 = comp_def_offset("get_queue_position");
+const COMP_DEF_OFFSET_GET_ORDER_STATUS: u32 = comp_def_offset("get_order_status");
+const COMP_DEF_OFFSET_MATCH_ORDERS_BATCH: u32 = comp_def_offset("match_orders_batch");
+const COMP_DEF_OFFSET_IS_CROSSED: u32 = comp_def_offset("is_crossed");
+const COMP_DEF_OFFSET_CANCEL_ORDERS_BY_IDS: u32 = comp_def_offset("cancel_orders_by_ids");
+const COMP_DEF_OFFSET_INIT_ORDERBOOK: u32 = comp_def_offset("init_orderbook");
+const COMP_DEF_OFFSET_WARM_BOOK: u32 = comp_def_offset("warm_book");
+const COMP_DEF_OFFSET_GET_DEPTH_RANGE: u32 = comp_def_offset("get_depth_range");
+const COMP_DEF_OFFSET_EMERGENCY_CANCEL_ALL: u32 = comp_def_offset("emergency_cancel_all");
+const COMP_DEF_OFFSET_GET_EFFECTIVE_SPREAD_BPS: u32 = comp_def_offset("get_effective_spread_bps");
+const COMP_DEF_OFFSET_GET_ORDERBOOK_DEPTH_QUOTE: u32 = comp_def_offset("get_orderbook_depth_quote");
+const COMP_DEF_OFFSET_ADD_TO_ALLOWLIST: u32 = comp_def_offset("add_to_allowlist");
+const COMP_DEF_OFFSET_REMOVE_FROM_ALLOWLIST: u32 = comp_def_offset("remove_from_allowlist");
+const COMP_DEF_OFFSET_GET_LAST_TRADE: u32 = comp_def_offset("get_last_trade");
+const COMP_DEF_OFFSET_GET_FRAGMENTATION: u32 = comp_def_offset("get_fragmentation");
+const COMP_DEF_OFFSET_GET_PRICE_AT_VOLUME: u32 = comp_def_offset("get_price_at_volume");
+const COMP_DEF_OFFSET_GET_FILL_LIKELIHOOD: u32 = comp_def_offset("get_fill_likelihood");
+const COMP_DEF_OFFSET_GET_ACTIVE_DISTRIBUTION: u32 = comp_def_offset("get_active_distribution");
+const COMP_DEF_OFFSET_GET_BOOK_DIFF: u32 = comp_def_offset("get_book_diff");
+const COMP_DEF_OFFSET_GET_TIME_RANK: u32 = comp_def_offset("get_time_rank");
+const COMP_DEF_OFFSET_HEARTBEAT: u32 = comp_def_offset("heartbeat");
+const COMP_DEF_OFFSET_CANCEL_STALE_USERS: u32 = comp_def_offset("cancel_stale_users");
+const COMP_DEF_OFFSET_GET_IMBALANCE_RATIO: u32 = comp_def_offset("get_imbalance_ratio");
+const COMP_DEF_OFFSET_GET_USER_VOLUME: u32 = comp_def_offset("get_user_volume");
+const COMP_DEF_OFFSET_GET_AGE_HISTOGRAM: u32 = comp_def_offset("get_age_histogram");
+const COMP_DEF_OFFSET_MODIFY_ORDER: u32 = comp_def_offset("modify_order");
+const COMP_DEF_OFFSET_SNAPSHOT_DEPTH: u32 = comp_def_offset("snapshot_depth");
+const COMP_DEF_OFFSET_GET_DEPTH_DELTA: u32 = comp_def_offset("get_depth_delta");
+const COMP_DEF_OFFSET_CRANK_MATCH: u32 = comp_def_offset("crank_match");
+const COMP_DEF_OFFSET_CHECK_INVARIANTS: u32 = comp_def_offset("check_invariants");
+const COMP_DEF_OFFSET_GET_USER_FILLS: u32 = comp_def_offset("get_user_fills");
+const COMP_DEF_OFFSET_GET_SORTED_LADDER: u32 = comp_def_offset("get_sorted_ladder");
+const COMP_DEF_OFFSET_GET_ORDERS_MODIFIED_SINCE: u32 = comp_def_offset("get_orders_modified_since");
+const COMP_DEF_OFFSET_GET_VOLUME_24H: u32 = comp_def_offset("get_volume_24h");
+const COMP_DEF_OFFSET_GET_WORST_ORDERS: u32 = comp_def_offset("get_worst_orders");
+const COMP_DEF_OFFSET_GET_USER_AVG_PRICE: u32 = comp_def_offset("get_user_avg_price");
+const COMP_DEF_OFFSET_SWAP_PRIORITY: u32 = comp_def_offset("swap_priority");
+const COMP_DEF_OFFSET_GET_FEE_ACCOUNTING: u32 = comp_def_offset("get_fee_accounting");
+const COMP_DEF_OFFSET_WITHDRAW_FEES: u32 = comp_def_offset("withdraw_fees");
+const COMP_DEF_OFFSET_GET_DEPTH_CUMULATIVE: u32 = comp_def_offset("get_depth_cumulative");
+const COMP_DEF_OFFSET_REPLAY_MATCH: u32 = comp_def_offset("replay_match");
+const COMP_DEF_OFFSET_GET_BOOK_PAGE: u32 = comp_def_offset("get_book_page");
+const COMP_DEF_OFFSET_GET_DEPTH_COUNTS: u32 = comp_def_offset("get_depth_counts");
+const COMP_DEF_OFFSET_SUBMIT_IOC: u32 = comp_def_offset("submit_ioc");
+const COMP_DEF_OFFSET_MATCH_MARKET_SWEEP: u32 = comp_def_offset("match_market_sweep");
+const COMP_DEF_OFFSET_GET_OPEN_INTEREST: u32 = comp_def_offset("get_open_interest");
+const COMP_DEF_OFFSET_MIGRATE_BOOK: u32 = comp_def_offset("migrate_book");
+
+// Upper bound on fills across all match calls within one slot
+const MAX_FILLS_PER_SLOT: u64 = 16;
+
+// Midpoint rounding modes (0 = floor, 1 = ceil, 2 = toward-maker)
+const ROUNDING_TOWARD_MAKER: u8 = 2;
+
+// Matching modes (0 = price-time pair, 1 = pro-rata at the touch)
+const MATCH_MODE_PRO_RATA: u8 = 1;
+
+// Pair selection policies (0 = market first then slot order,
+// 1 = market first then price-time)
+const PRIORITY_PRICE_TIME: u8 = 1;
+
+// Limit-vs-limit price policies (0 = midpoint, 1 = maker-favorable,
+// 2 = taker-favorable)
+const SPREAD_TAKER_FAVORABLE: u8 = 2;
+
+// Cross-order-type rule bits (1 = no market x market fills, 2 = market x limit
+// trades at the oracle price when the limit accepts it)
+const PAIR_RULES_MASK: u8 = 3;
+
+// Market-order price sources (0 = oracle only, 1 = oracle then book,
+// 2 = book then oracle); see market_price_source in the circuits
+const PRICE_SOURCE_BOOK_FIRST: u8 = 2;
+
+// Locked-market handling (0 = a limit bid equal to a limit ask matches,
+// 1 = the locked orders keep resting)
+const LOCK_POLICY_HOLD: u8 = 1;
+
+// Dust remainder handling after a match_orders fill (0 = keep resting,
+// 1 = cancel a remainder below the lot size or min_notional)
+const DUST_POLICY_CANCEL: u8 = 1;
+
+// Wind-down state (0 = open, 1 = draining: no new orders or size increases,
+// matching and cancels carry on until the book is empty)
+const DRAINING: u8 = 1;
+
+// Shortest cancel-on-disconnect TTL the admin can configure, in seconds
+const MIN_HEARTBEAT_TTL: u64 = 30;
+
+// Longest per-user match cooldown, in slots (about a day). The circuits add
+// it to a user's last fill slot, so it has to stay far from u64::MAX
+const MAX_MATCH_COOLDOWN_SLOTS: u64 = 216_000;
+
+// Largest tick size. priority_price rounds asks up to the next tick, so the
+// step has to leave headroom below u64::MAX
+const MAX_TICK_SIZE: u64 = 1 << 32;
+
+// Largest cross tolerance; past this no real pair of limit prices crosses and
+// the setting would silently halt limit matching
+const MAX_CROSS_TOLERANCE: u64 = 1 << 32;
+
+// Largest min_notional. add_order's notional saturates at u64::MAX, so a floor
+// near it would reject every limit order
+const MAX_MIN_NOTIONAL: u64 = 1 << 48;
+
+// Book-full policies (0 = reject the new order, 1 = evict the worst-priced
+// same-side order if the new one ranks ahead of it)
+const EVICTION_EVICT_WORST: u8 = 1;
+
+// Match event privacy levels (0 = private: no price or amount, 1 = report the
+// encrypted price and amount). Applies to every fill event: OrdersMatchedEvent,
+// OrdersBatchMatchedEvent, BatchMatchSummaryEvent, CrankMatchedEvent and
+// MarketSweptEvent. Netted settlement entries are always emitted since
+// settlement needs them; IocExecutedEvent only goes to the taker
+const REPORT_TRADES_FULL: u8 = 1;
+
+// Fee rounding modes (0 = floor, 1 = ceil, 2 = nearest) and the bps ceiling
+const FEE_ROUNDING_NEAREST: u8 = 2;
+const MAX_FEE_BPS: u16 = 10000;
+
+// Length of the id list accepted by cancel_orders_by_ids (pad with u64::MAX)
+const MAX_CANCEL_IDS: usize = 8;
+
+// Order amounts are fixed-point integers with `amount_scale` decimal places:
+// an amount of 1_500 at scale 3 is 1.5 lots. Matching, partial fills and depth
+// all run on the scaled integers, so fills are exact at that precision. The
+// largest representable amount is u64::MAX / 10^scale whole lots (about 1.8e10
+// lots at the maximum scale of 9); price * amount products are widened to u128.
+const MAX_AMOUNT_SCALE: u8 = 9;
+
+// Match prices are also reported at 10^price_decimals precision so a midpoint
+// like 100.5 survives until settlement; a scale of at least 10 is needed for
+// half ticks to be exact
+const MAX_PRICE_DECIMALS: u8 = 6;
+
+// Books for instruments that can trade below zero store prices offset-binary:
+// the encrypted u64 is real_price + 2^63, so -5 is 2^63 - 5 and +5 is 2^63 + 5.
+// The encoding keeps u64 ordering, so matching, priority and depth are
+// unchanged; representable prices are -2^63 ..= 2^63 - 1. Notionals and fees
+// use the magnitude, and MatchResult.scaled_price is real * scale + 2^63
+const SIGNED_PRICE_OFFSET: u64 = 1 << 63;
+
+// Depth buckets returned by get_orderbook_depth, and the widest slice
+// get_depth_range can return
+const DEPTH_BUCKETS: u64 = 20;
+const MAX_DEPTH_RANGE: u64 = 8;
+
+// get_depth_delta output: bucket indices, volumes, count and valid
+const DEPTH_DELTA_CIPHERTEXTS: usize = 2 * DEPTH_BUCKETS as usize + 2;
+
+// Fills returned by get_user_fills, zero-padded
+const MAX_USER_FILLS: usize = 8;
+
+// Order updates returned by get_orders_modified_since, padded
+const MAX_MODIFIED_ORDERS: usize = 8;
+const ORDER_UPDATE_FIELDS: usize = 6;
+
+// Price levels per side returned by get_sorted_ladder
+const LADDER_LEVELS: usize = 10;
+
+// Orders per get_book_page export
+const BOOK_PAGE: usize = 10;
+
+// Fills one sweep (submit_ioc, match_market_sweep) can produce
+const MAX_IOC_FILLS: usize = 8;
+
+// Buckets returned by get_age_histogram
+const AGE_BUCKETS: usize = 4;
+
+// Encrypted order book layout; must match the Order / OrderBook structs in
+// encrypted-ixs/match_orders.rs (one 32-byte ciphertext per field)
+const MAX_ORDERS: usize = 100;
+const ORDER_FIELDS: usize = 18;
+const MAX_ALLOWLIST: usize = 16;
+const CHANGE_LOG_LEN: usize = 8;
+const CHANGE_ENTRY_FIELDS: usize = 3;
+const MAX_HEARTBEAT_USERS: usize = 16;
+const MAX_TRACKED_USERS: usize = 16;
+const USER_STATS_FIELDS: usize = 3;
+const MATCH_RESULT_FIELDS: usize = 8;
+const TRADE_LOG_LEN: usize = 16;
+const TRADE_RECORD_FIELDS: usize = MATCH_RESULT_FIELDS + 2;
+const VOLUME_BUCKETS: usize = 24;
+const ORDERBOOK_HEADER_FIELDS: usize = 9
+    + MAX_ALLOWLIST
+    + CHANGE_LOG_LEN * CHANGE_ENTRY_FIELDS
+    + 2 * MAX_HEARTBEAT_USERS
+    + MAX_TRACKED_USERS * USER_STATS_FIELDS
+    + DEPTH_BUCKETS as usize
+    + 1
+    + TRADE_LOG_LEN * TRADE_RECORD_FIELDS
+    + 1
+    + 2 * VOLUME_BUCKETS;
+const ORDERBOOK_CIPHERTEXTS: usize = MAX_ORDERS * ORDER_FIELDS + ORDERBOOK_HEADER_FIELDS;
+
+// Fields of the per-party FillNotice emitted by match_orders
+const FILL_NOTICE_FIELDS: usize = 5;
+
+// get_book_diff output: the change entries plus current_version and complete
+const BOOK_DIFF_CIPHERTEXTS: usize = CHANGE_LOG_LEN * CHANGE_ENTRY_FIELDS + 2;
+
+// Encrypted cancellation records queued for settlement, one per order slot
+const CANCEL_RECORD_FIELDS: usize = 5;
+const CANCEL_RECORD_CIPHERTEXTS: usize = MAX_ORDERS * CANCEL_RECORD_FIELDS;
+
+// Match computation offsets remembered for resubmission dedup
+const PROCESSED_OFFSETS: usize = 32;
+
+// Outstanding reserve_order_id reservations, and how many slots one stays
+// valid before its entry can be reused
+const MAX_RESERVED_IDS: usize = 16;
+const RESERVATION_TTL_SLOTS: u64 = 1500;
+
+// Version of the OrderBookState layout, stored in its header. Bump it on any
+// change to OrderBookState or the encrypted Order/OrderBook structs and teach
+// migrate_book to upgrade books written under the previous version
+const ORDERBOOK_LAYOUT_VERSION: u16 = 12;
+
+// First layout whose encrypted Order carries a notify_key. Books written
+// before it are re-encrypted by the migrate_book circuit
+const NOTIFY_KEY_LAYOUT_VERSION: u16 = 11;
+
+// Offset of layout_version (discriminator, bump, authority and nonce come first)
+const LAYOUT_VERSION_OFFSET: usize = 8 + 1 + 32 + 16;
+
+// Byte range of the ciphertexts inside OrderBookState (the header above plus
+// layout_version come first)
+const ORDERBOOK_OFFSET: u32 = (LAYOUT_VERSION_OFFSET + 2) as u32;
+const ORDERBOOK_LEN: u32 = (ORDERBOOK_CIPHERTEXTS * 32) as u32;
+
+// Byte offset of the book ciphertexts inside MatchSnapshot (discriminator,
+// bump and nonce come first)
+const MATCH_SNAPSHOT_BOOK_OFFSET: u32 = (8 + 1 + 16) as u32;
+
+// Ciphertext bytes of a book written before NOTIFY_KEY_LAYOUT_VERSION, when
+// an Order had one field fewer
+const LEGACY_ORDER_FIELDS: usize = 17;
+const LEGACY_ORDERBOOK_LEN: u32 =
+    ((MAX_ORDERS * LEGACY_ORDER_FIELDS + ORDERBOOK_HEADER_FIELDS) * 32) as u32;
+
+// Account size under layout v1 (406 plaintext bytes follow the ciphertexts);
+// a v0 book is two bytes shorter since it has no layout_version
+const ORDERBOOK_V1_SIZE: usize = ORDERBOOK_OFFSET as usize + LEGACY_ORDERBOOK_LEN as usize + 406;
 
 declare_id!("DarkPoo1111111111111111111111111111111111111");
 
@@ -34,272 +277,6952 @@ pub mod darkpool {
         Ok(())
     }
 
-    // Add order to encrypted order book
-    pub fn add_order(
-        ctx: Context<AddOrder>,
-        computation_offset: u64,
-        order_price: [u8; 32],
-        order_amount: [u8; 32],
-        order_side: [u8; 32],
-        order_type: [u8; 32],
-        user_id: [u8; 32],
-        pub_key: [u8; 32],
-        nonce: u128,
-    ) -> Result<()> {
-        let args = ArgBuilder::new()
-            .x25519_pubkey(pub_key)
-            .plaintext_u128(nonce)
-            .encrypted_u64(order_price)
-            .encrypted_u64(order_amount)
-            .encrypted_u8(order_side)
-            .encrypted_u8(order_type)
-            .encrypted_u128(user_id)
-            .build();
+    pub fn init_get_queue_position_comp_def(ctx: Context<InitGetQueuePositionCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
 
-        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+    pub fn init_get_order_status_comp_def(ctx: Context<InitGetOrderStatusCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
 
-        queue_computation(
-            ctx.accounts,
-            computation_offset,
-            args,
-            None,
-            vec![AddOrderCallback::callback_ix(
-                computation_offset,
-                &ctx.accounts.mxe_account,
-                &[]
-            )?],
-            1,
-            0,
-        )?;
+    pub fn init_match_orders_batch_comp_def(ctx: Context<InitMatchOrdersBatchCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
         Ok(())
     }
 
-    #[arcium_callback(encrypted_ix = "add_order")]
-    pub fn add_order_callback(
-        ctx: Context<AddOrderCallback>,
-        output: SignedComputationOutputs<AddOrderOutput>,
-    ) -> Result<()> {
-        let _o = match output.verify_output(
-            &ctx.accounts.cluster_account,
-            &ctx.accounts.computation_account
-        ) {
-            Ok(AddOrderOutput { field_0 }) => field_0,
-            Err(e) => {
-                msg!("Error: {}", e);
-                return Err(ErrorCode::AbortedComputation.into())
-            },
-        };
+    pub fn init_is_crossed_comp_def(ctx: Context<InitIsCrossedCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
 
-        emit!(OrderAddedEvent {
-            computation_offset: ctx.accounts.computation_account.computation_offset,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
+    pub fn init_cancel_orders_by_ids_comp_def(ctx: Context<InitCancelOrdersByIdsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
         Ok(())
     }
 
-    // Match orders in encrypted order book
-    pub fn match_orders(
-        ctx: Context<MatchOrders>,
-        computation_offset: u64,
-    ) -> Result<()> {
-        let args = ArgBuilder::new().build();
+    pub fn init_init_orderbook_comp_def(ctx: Context<InitInitOrderbookCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_warm_book_comp_def(ctx: Context<InitWarmBookCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_migrate_book_comp_def(ctx: Context<InitMigrateBookCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_depth_range_comp_def(ctx: Context<InitGetDepthRangeCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_emergency_cancel_all_comp_def(ctx: Context<InitEmergencyCancelAllCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_effective_spread_bps_comp_def(ctx: Context<InitGetEffectiveSpreadBpsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_orderbook_depth_quote_comp_def(ctx: Context<InitGetOrderbookDepthQuoteCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_add_to_allowlist_comp_def(ctx: Context<InitAddToAllowlistCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_remove_from_allowlist_comp_def(ctx: Context<InitRemoveFromAllowlistCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_last_trade_comp_def(ctx: Context<InitGetLastTradeCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_fragmentation_comp_def(ctx: Context<InitGetFragmentationCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_price_at_volume_comp_def(ctx: Context<InitGetPriceAtVolumeCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_fill_likelihood_comp_def(ctx: Context<InitGetFillLikelihoodCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_active_distribution_comp_def(ctx: Context<InitGetActiveDistributionCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_book_diff_comp_def(ctx: Context<InitGetBookDiffCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_time_rank_comp_def(ctx: Context<InitGetTimeRankCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_heartbeat_comp_def(ctx: Context<InitHeartbeatCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_cancel_stale_users_comp_def(ctx: Context<InitCancelStaleUsersCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_imbalance_ratio_comp_def(ctx: Context<InitGetImbalanceRatioCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_user_volume_comp_def(ctx: Context<InitGetUserVolumeCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_age_histogram_comp_def(ctx: Context<InitGetAgeHistogramCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_modify_order_comp_def(ctx: Context<InitModifyOrderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_snapshot_depth_comp_def(ctx: Context<InitSnapshotDepthCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_depth_delta_comp_def(ctx: Context<InitGetDepthDeltaCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_crank_match_comp_def(ctx: Context<InitCrankMatchCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_check_invariants_comp_def(ctx: Context<InitCheckInvariantsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_user_fills_comp_def(ctx: Context<InitGetUserFillsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_sorted_ladder_comp_def(ctx: Context<InitGetSortedLadderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_orders_modified_since_comp_def(ctx: Context<InitGetOrdersModifiedSinceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_volume_24h_comp_def(ctx: Context<InitGetVolume24hCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_worst_orders_comp_def(ctx: Context<InitGetWorstOrdersCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_user_avg_price_comp_def(ctx: Context<InitGetUserAvgPriceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_swap_priority_comp_def(ctx: Context<InitSwapPriorityCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_fee_accounting_comp_def(ctx: Context<InitGetFeeAccountingCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_withdraw_fees_comp_def(ctx: Context<InitWithdrawFeesCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_depth_cumulative_comp_def(ctx: Context<InitGetDepthCumulativeCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_replay_match_comp_def(ctx: Context<InitReplayMatchCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_book_page_comp_def(ctx: Context<InitGetBookPageCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_depth_counts_comp_def(ctx: Context<InitGetDepthCountsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_submit_ioc_comp_def(ctx: Context<InitSubmitIocCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_match_market_sweep_comp_def(ctx: Context<InitMatchMarketSweepCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_get_open_interest_comp_def(ctx: Context<InitGetOpenInterestCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    // Initialize the reference price oracle
+    pub fn init_oracle_price(
+        ctx: Context<InitOraclePrice>,
+        updater: Pubkey,
+        max_staleness_slots: u64,
+    ) -> Result<()> {
+        let oracle = &mut ctx.accounts.oracle_price;
+        oracle.authority = ctx.accounts.payer.key();
+        oracle.updater = updater;
+        oracle.price = 0;
+        oracle.last_update_slot = 0;
+        oracle.max_staleness_slots = max_staleness_slots;
+        oracle.bump = ctx.bumps.oracle_price;
+        Ok(())
+    }
+
+    // Publish a new reference price (trusted updater only)
+    pub fn update_oracle_price(ctx: Context<UpdateOraclePrice>, price: u64) -> Result<()> {
+        let oracle = &mut ctx.accounts.oracle_price;
+        oracle.price = price;
+        oracle.last_update_slot = Clock::get()?.slot;
+
+        emit!(OraclePriceUpdatedEvent {
+            price,
+            slot: oracle.last_update_slot,
+        });
+        Ok(())
+    }
+
+    // Initialize the settlement queue fed by emergency_cancel_all
+    pub fn init_settlement_queue(ctx: Context<InitSettlementQueue>) -> Result<()> {
+        let queue = &mut ctx.accounts.settlement_queue;
+        queue.bump = ctx.bumps.settlement_queue;
+        queue.pending = false;
+        Ok(())
+    }
+
+    // Initialize the trading gate match_orders checks; `compliance_authority`
+    // may switch trading on and off. Trading starts enabled
+    pub fn init_trading_gate(ctx: Context<InitTradingGate>, compliance_authority: Pubkey) -> Result<()> {
+        let gate = &mut ctx.accounts.trading_gate;
+        gate.authority = ctx.accounts.payer.key();
+        gate.compliance_authority = compliance_authority;
+        gate.trading_enabled = true;
+        gate.bump = ctx.bumps.trading_gate;
+        Ok(())
+    }
+
+    // Create the account that keeps the last match_orders call's inputs for replay_match
+    pub fn init_match_snapshot(ctx: Context<InitMatchSnapshot>) -> Result<()> {
+        ctx.accounts.match_snapshot.bump = ctx.bumps.match_snapshot;
+        Ok(())
+    }
+
+    // Enable or block matching (compliance authority only); cancels are unaffected
+    pub fn set_trading_enabled(ctx: Context<SetTradingEnabled>, trading_enabled: bool) -> Result<()> {
+        ctx.accounts.trading_gate.trading_enabled = trading_enabled;
+
+        emit!(TradingGateUpdatedEvent {
+            trading_enabled,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Create the vault that pays out venue fees (keeper rewards, withdrawals)
+    pub fn init_fee_vault(ctx: Context<InitFeeVault>) -> Result<()> {
+        ctx.accounts.fee_vault.bump = ctx.bumps.fee_vault;
+        Ok(())
+    }
+
+    // Top up the fee vault with lamports, e.g. by the settlement layer as it
+    // collects the fees the book has accrued
+    pub fn fund_fee_vault(ctx: Context<FundFeeVault>, amount: u64) -> Result<()> {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.funder.to_account_info(),
+                    to: ctx.accounts.fee_vault.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        Ok(())
+    }
+
+    // Set the reward, in fee units, paid out of collected fees to whoever
+    // triggers a match_orders call that fills (0 = no reward). The fee vault
+    // pays it, one lamport per fee unit
+    pub fn set_keeper_reward(ctx: Context<UpdateOrderBookConfig>, keeper_reward: u64) -> Result<()> {
+        ctx.accounts.orderbook.keeper_reward = keeper_reward;
+        Ok(())
+    }
+
+    // Upgrade an order book written by an older program build to the current
+    // layout (admin only). Books from before NOTIFY_KEY_LAYOUT_VERSION hold
+    // orders with one field fewer, so they are re-encrypted by the
+    // migrate_book circuit and the callback finishes the upgrade; until then
+    // the account keeps its old size and no other instruction can load it.
+    // Later versions that only append plaintext fields are upgraded here.
+    // Either way, plaintext fields appended since the book's version start
+    // zeroed, which is their "off" value
+    pub fn migrate_book(ctx: Context<MigrateBook>, computation_offset: u64) -> Result<()> {
+        let book = ctx.accounts.orderbook.to_account_info();
+        let old_len = book.data_len();
+        let new_len = 8 + OrderBookState::INIT_SPACE;
+        let (from_version, book_nonce) = {
+            let data = book.try_borrow_data()?;
+            require!(
+                data.len() >= LAYOUT_VERSION_OFFSET + 2 && &data[..8] == OrderBookState::DISCRIMINATOR,
+                ErrorCode::UnknownLayoutVersion
+            );
+            require!(
+                data[9..41] == ctx.accounts.authority.key().to_bytes(),
+                ErrorCode::Unauthorized
+            );
+            let mut nonce = [0u8; 16];
+            nonce.copy_from_slice(&data[41..LAYOUT_VERSION_OFFSET]);
+            (OrderBookState::stored_layout_version(&data), u128::from_le_bytes(nonce))
+        };
+        require!(from_version != ORDERBOOK_LAYOUT_VERSION, ErrorCode::LayoutUpToDate);
+        require!(
+            from_version < ORDERBOOK_LAYOUT_VERSION && old_len < new_len,
+            ErrorCode::UnknownLayoutVersion
+        );
+
+        let rent = Rent::get()?.minimum_balance(new_len);
+        let top_up = rent.saturating_sub(book.lamports());
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: book.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+
+        if from_version < NOTIFY_KEY_LAYOUT_VERSION {
+            let args = ArgBuilder::new()
+                .plaintext_u128(book_nonce)
+                .account(
+                    book.key(),
+                    OrderBookState::legacy_ciphertext_offset(from_version) as u32,
+                    LEGACY_ORDERBOOK_LEN,
+                )
+                .build();
+
+            ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+            queue_computation(
+                ctx.accounts,
+                computation_offset,
+                args,
+                None,
+                vec![MigrateBookCallback::callback_ix(
+                    computation_offset,
+                    &ctx.accounts.mxe_account,
+                    &[CallbackAccount {
+                        pubkey: book.key(),
+                        is_writable: true,
+                    }]
+                )?],
+                1,
+                0,
+            )?;
+            return Ok(());
+        }
+
+        book.resize(new_len)?;
+
+        let mut data = book.try_borrow_mut_data()?;
+        data[old_len..].fill(0);
+        data[LAYOUT_VERSION_OFFSET..LAYOUT_VERSION_OFFSET + 2]
+            .copy_from_slice(&ORDERBOOK_LAYOUT_VERSION.to_le_bytes());
+
+        emit!(BookMigratedEvent {
+            from_version,
+            to_version: ORDERBOOK_LAYOUT_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "migrate_book")]
+    pub fn migrate_book_callback(
+        ctx: Context<MigrateBookCallback>,
+        output: SignedComputationOutputs<MigrateBookOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(MigrateBookOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let book = ctx.accounts.orderbook.to_account_info();
+        let old_len = book.data_len();
+        let from_version = OrderBookState::stored_layout_version(&book.try_borrow_data()?);
+        require!(from_version < NOTIFY_KEY_LAYOUT_VERSION, ErrorCode::LayoutUpToDate);
+
+        // Move the plaintext fields that follow the old ciphertexts to where
+        // the wider block now ends; the ciphertexts themselves are replaced
+        let tail_start = OrderBookState::legacy_ciphertext_offset(from_version) + LEGACY_ORDERBOOK_LEN as usize;
+        let new_tail_start = ORDERBOOK_OFFSET as usize + ORDERBOOK_LEN as usize;
+        book.resize(8 + OrderBookState::INIT_SPACE)?;
+        {
+            let mut data = book.try_borrow_mut_data()?;
+            data.copy_within(tail_start..old_len, new_tail_start);
+            data[new_tail_start + old_len - tail_start..].fill(0);
+            data[LAYOUT_VERSION_OFFSET..LAYOUT_VERSION_OFFSET + 2]
+                .copy_from_slice(&ORDERBOOK_LAYOUT_VERSION.to_le_bytes());
+        }
+
+        let mut orderbook = OrderBookState::try_deserialize(&mut &book.try_borrow_data()?[..])?;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::MigrateBook, computation_offset);
+        orderbook.try_serialize(&mut &mut book.try_borrow_mut_data()?[..])?;
+
+        emit!(BookMigratedEvent {
+            from_version,
+            to_version: ORDERBOOK_LAYOUT_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Report how many slots have passed since the last match_orders call that
+    // filled, so keepers can tell a market has gone quiet. Reads plaintext
+    // bookkeeping only; a book that never filled reports last_match_slot 0
+    pub fn get_book_staleness(ctx: Context<GetBookStaleness>) -> Result<()> {
+        let clock = Clock::get()?;
+        let last_match_slot = ctx.accounts.orderbook.last_match_slot;
+
+        emit!(BookStalenessEvent {
+            last_match_slot,
+            staleness_slots: clock.slot.saturating_sub(last_match_slot),
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Reserve the next order id so clients can show a pending order before it lands
+    // Only the reserving payer can place an order under it, once, within
+    // RESERVATION_TTL_SLOTS; unused reservations leave a gap in the id sequence
+    pub fn reserve_order_id(ctx: Context<ReserveOrderId>) -> Result<()> {
+        let current_slot = Clock::get()?.slot;
+        let order_id = ctx.accounts.orderbook.reserve_order_id(ctx.accounts.payer.key(), current_slot)?;
+
+        emit!(OrderIdReservedEvent {
+            order_id,
+            reserved_by: ctx.accounts.payer.key(),
+        });
+        Ok(())
+    }
+
+    // Set the minimum fill increment; fills are floored to a multiple of it (0 disables)
+    // It must be a whole number of lots, or floored fills would leave odd lots
+    pub fn set_fill_increment(ctx: Context<UpdateOrderBookConfig>, fill_increment: u64) -> Result<()> {
+        require!(
+            fits_lot_size(fill_increment, ctx.accounts.orderbook.lot_size),
+            ErrorCode::InvalidFillIncrement
+        );
+        ctx.accounts.orderbook.fill_increment = fill_increment;
+        Ok(())
+    }
+
+    // Set the fixed-point scale of order amounts; only before the first order
+    pub fn set_amount_scale(ctx: Context<UpdateOrderBookConfig>, amount_scale: u8) -> Result<()> {
+        require!(amount_scale <= MAX_AMOUNT_SCALE, ErrorCode::InvalidAmountScale);
+        require!(ctx.accounts.orderbook.next_order_id == 0, ErrorCode::OrderBookNotEmpty);
+        ctx.accounts.orderbook.amount_scale = amount_scale;
+        Ok(())
+    }
+
+    // Switch the book to offset-binary signed prices; only before the first order
+    pub fn set_signed_prices(ctx: Context<UpdateOrderBookConfig>, signed_prices: bool) -> Result<()> {
+        require!(ctx.accounts.orderbook.next_order_id == 0, ErrorCode::OrderBookNotEmpty);
+        ctx.accounts.orderbook.signed_prices = signed_prices;
+        Ok(())
+    }
+
+    // Enforce (or stop enforcing) the encrypted user allow-list on add_order
+    pub fn set_allowlist_enabled(ctx: Context<UpdateOrderBookConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.orderbook.allowlist_enabled = enabled;
+        Ok(())
+    }
+
+    // Set the per-side price caps enforced by add_order (0 disables a side)
+    pub fn set_price_caps(
+        ctx: Context<UpdateOrderBookConfig>,
+        max_bid_price: u64,
+        min_ask_price: u64,
+    ) -> Result<()> {
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.max_bid_price = max_bid_price;
+        orderbook.min_ask_price = min_ask_price;
+        Ok(())
+    }
+
+    // Set the maker and taker fees charged by match_orders (authority only)
+    // A negative maker fee is a rebate; it can't exceed the taker fee, so the
+    // venue never pays out more than it collects on a fill
+    pub fn update_fees(ctx: Context<UpdateOrderBookConfig>, maker_fee_bps: i16, taker_fee_bps: u16) -> Result<()> {
+        require!(taker_fee_bps <= MAX_FEE_BPS, ErrorCode::InvalidFeeBps);
+        require!(
+            maker_fee_bps as i32 <= MAX_FEE_BPS as i32 && -(maker_fee_bps as i32) <= taker_fee_bps as i32,
+            ErrorCode::InvalidFeeBps
+        );
+
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.maker_fee_bps = maker_fee_bps;
+        orderbook.taker_fee_bps = taker_fee_bps;
+
+        emit!(FeesUpdatedEvent {
+            maker_fee_bps,
+            taker_fee_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Configure round-lot / odd-lot segregation in match_orders
+    pub fn set_lot_segregation(
+        ctx: Context<UpdateOrderBookConfig>,
+        lot_size: u64,
+        enabled: bool,
+    ) -> Result<()> {
+        require!(!enabled || lot_size > 0, ErrorCode::InvalidLotSize);
+        require!(
+            fits_lot_size(ctx.accounts.orderbook.fill_increment, lot_size),
+            ErrorCode::InvalidFillIncrement
+        );
+
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.lot_size = lot_size;
+        orderbook.lot_segregation = enabled;
+        Ok(())
+    }
+
+    // Set how many slots a user's orders are skipped by match_orders after a fill
+    pub fn set_match_cooldown(ctx: Context<UpdateOrderBookConfig>, match_cooldown_slots: u64) -> Result<()> {
+        require!(match_cooldown_slots <= MAX_MATCH_COOLDOWN_SLOTS, ErrorCode::InvalidMatchCooldown);
+        ctx.accounts.orderbook.match_cooldown_slots = match_cooldown_slots;
+        Ok(())
+    }
+
+    // Set whether a locked market (best limit bid == best limit ask) matches
+    // or is left to persist
+    pub fn set_lock_policy(ctx: Context<UpdateOrderBookConfig>, lock_policy: u8) -> Result<()> {
+        require!(lock_policy <= LOCK_POLICY_HOLD, ErrorCode::InvalidLockPolicy);
+        ctx.accounts.orderbook.lock_policy = lock_policy;
+        Ok(())
+    }
+
+    // Restrict adds and matching to [session_open_ts, session_close_ts); cancels
+    // stay allowed. Both 0 keeps the market open around the clock
+    pub fn set_session(
+        ctx: Context<UpdateOrderBookConfig>,
+        session_open_ts: i64,
+        session_close_ts: i64,
+    ) -> Result<()> {
+        let always_open = session_open_ts == 0 && session_close_ts == 0;
+        require!(always_open || session_open_ts < session_close_ts, ErrorCode::InvalidSession);
+
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.session_open_ts = session_open_ts;
+        orderbook.session_close_ts = session_close_ts;
+        Ok(())
+    }
+
+    // Put the book into (or take it out of) draining: add_order and submit_ioc
+    // are rejected and modify_order may only shrink orders, while matching and
+    // cancels proceed, so the book can wind down before a migration
+    pub fn set_draining(ctx: Context<UpdateOrderBookConfig>, draining: u8) -> Result<()> {
+        require!(draining <= DRAINING, ErrorCode::InvalidDrainingState);
+        ctx.accounts.orderbook.draining = draining;
+        Ok(())
+    }
+
+    // Set how long (seconds) an enrolled user may go without a heartbeat before
+    // cancel_stale_users pulls their orders
+    pub fn set_heartbeat_ttl(ctx: Context<UpdateOrderBookConfig>, heartbeat_ttl: u64) -> Result<()> {
+        require!(heartbeat_ttl >= MIN_HEARTBEAT_TTL, ErrorCode::InvalidHeartbeatTtl);
+        ctx.accounts.orderbook.heartbeat_ttl = heartbeat_ttl;
+        Ok(())
+    }
+
+    // Set whether match_orders cancels dust left behind by a partial fill
+    pub fn set_dust_policy(ctx: Context<UpdateOrderBookConfig>, dust_policy: u8) -> Result<()> {
+        require!(dust_policy <= DUST_POLICY_CANCEL, ErrorCode::InvalidDustPolicy);
+        ctx.accounts.orderbook.dust_policy = dust_policy;
+        Ok(())
+    }
+
+    // Set how many slots a newly added order must wait before it can be
+    // matched, to blunt latency arbitrage on fresh quotes. Orders can still be
+    // cancelled during the delay
+    pub fn set_activation_delay(ctx: Context<UpdateOrderBookConfig>, activation_delay_slots: u64) -> Result<()> {
+        ctx.accounts.orderbook.activation_delay_slots = activation_delay_slots;
+        Ok(())
+    }
+
+    // Cap any single fill at a fraction (in bps) of the resting side's visible
+    // volume, so one taker can't sweep the book in a single match; 0 = no cap
+    pub fn set_max_match_fraction(ctx: Context<UpdateOrderBookConfig>, max_match_fraction_bps: u64) -> Result<()> {
+        require!(max_match_fraction_bps <= 10_000, ErrorCode::InvalidMatchFraction);
+        ctx.accounts.orderbook.max_match_fraction_bps = max_match_fraction_bps;
+        Ok(())
+    }
+
+    // Set the order count at which add_order emits BookNearCapacityEvent
+    // (e.g. 80 for 80% of MAX_ORDERS); 0 disables the alert
+    pub fn set_capacity_threshold(ctx: Context<UpdateOrderBookConfig>, capacity_threshold: u64) -> Result<()> {
+        require!(capacity_threshold <= MAX_ORDERS as u64, ErrorCode::InvalidCapacityThreshold);
+        ctx.accounts.orderbook.capacity_threshold = capacity_threshold;
+        Ok(())
+    }
+
+    // Set the smallest limit order add_order accepts, in quote notional
+    // (price * amount / 10^amount_scale); 0 disables the check
+    pub fn set_min_notional(ctx: Context<UpdateOrderBookConfig>, min_notional: u64) -> Result<()> {
+        require!(min_notional <= MAX_MIN_NOTIONAL, ErrorCode::InvalidMinNotional);
+        ctx.accounts.orderbook.min_notional = min_notional;
+        Ok(())
+    }
+
+    // Set whether match events carry the encrypted price and amount
+    pub fn set_report_trades(ctx: Context<UpdateOrderBookConfig>, report_trades: u8) -> Result<()> {
+        require!(report_trades <= REPORT_TRADES_FULL, ErrorCode::InvalidReportTrades);
+        ctx.accounts.orderbook.report_trades = report_trades;
+        Ok(())
+    }
+
+    // Set what add_order does when the book is full
+    pub fn set_eviction_policy(ctx: Context<UpdateOrderBookConfig>, eviction_policy: u8) -> Result<()> {
+        require!(eviction_policy <= EVICTION_EVICT_WORST, ErrorCode::InvalidEvictionPolicy);
+        ctx.accounts.orderbook.eviction_policy = eviction_policy;
+        Ok(())
+    }
+
+    // Set the cross-order-type matching rules (see can_cross in the circuits)
+    pub fn set_pair_rules(ctx: Context<UpdateOrderBookConfig>, pair_rules: u8) -> Result<()> {
+        require!(pair_rules & !PAIR_RULES_MASK == 0, ErrorCode::InvalidPairRules);
+        ctx.accounts.orderbook.pair_rules = pair_rules;
+        Ok(())
+    }
+
+    // Set how far a limit bid must exceed a limit ask before the pair matches,
+    // so near-touching orders don't trade on noise (0 = touching orders match)
+    pub fn set_cross_tolerance(ctx: Context<UpdateOrderBookConfig>, cross_tolerance: u64) -> Result<()> {
+        require!(cross_tolerance <= MAX_CROSS_TOLERANCE, ErrorCode::InvalidCrossTolerance);
+        ctx.accounts.orderbook.cross_tolerance = cross_tolerance;
+        Ok(())
+    }
+
+    // Set how many extra decimals match prices are reported with
+    pub fn set_price_decimals(ctx: Context<UpdateOrderBookConfig>, price_decimals: u8) -> Result<()> {
+        require!(price_decimals <= MAX_PRICE_DECIMALS, ErrorCode::InvalidPriceDecimals);
+        ctx.accounts.orderbook.price_decimals = price_decimals;
+        Ok(())
+    }
+
+    // Set the matching window length; while non-zero, orders accumulate and
+    // are cleared by crank_match once per window instead of per match call
+    pub fn set_match_window(ctx: Context<UpdateOrderBookConfig>, match_window_slots: u64) -> Result<()> {
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.match_window_slots = match_window_slots;
+        orderbook.window_start_slot = Clock::get()?.slot;
+        Ok(())
+    }
+
+    // Set the tick size: a new order must improve on a resting same-side order
+    // by at least one tick to rank ahead of it (0 disables the grid)
+    pub fn set_tick_size(ctx: Context<UpdateOrderBookConfig>, tick_size: u64) -> Result<()> {
+        require!(tick_size <= MAX_TICK_SIZE, ErrorCode::InvalidTickSize);
+        ctx.accounts.orderbook.tick_size = tick_size;
+        Ok(())
+    }
+
+    // Set the minimum number of slots an order must rest before it can be
+    // cancelled (0 disables the check)
+    pub fn set_min_rest_slots(ctx: Context<UpdateOrderBookConfig>, min_rest_slots: u64) -> Result<()> {
+        ctx.accounts.orderbook.min_rest_slots = min_rest_slots;
+        Ok(())
+    }
+
+    // Set how match_orders rounds an odd-sum midpoint (see ROUNDING_TOWARD_MAKER)
+    pub fn set_rounding(ctx: Context<UpdateOrderBookConfig>, rounding: u8) -> Result<()> {
+        require!(rounding <= ROUNDING_TOWARD_MAKER, ErrorCode::InvalidRounding);
+        ctx.accounts.orderbook.rounding = rounding;
+        Ok(())
+    }
+
+    // Set whether match_orders fills one price-time pair or pro-rata at the touch
+    pub fn set_match_mode(ctx: Context<UpdateOrderBookConfig>, match_mode: u8) -> Result<()> {
+        require!(match_mode <= MATCH_MODE_PRO_RATA, ErrorCode::InvalidMatchMode);
+        ctx.accounts.orderbook.match_mode = match_mode;
+        Ok(())
+    }
+
+    // Set how maker and taker fees are rounded on every matching path
+    pub fn set_fee_rounding(ctx: Context<UpdateOrderBookConfig>, fee_rounding: u8) -> Result<()> {
+        require!(fee_rounding <= FEE_ROUNDING_NEAREST, ErrorCode::InvalidRounding);
+        ctx.accounts.orderbook.fee_rounding = fee_rounding;
+        Ok(())
+    }
+
+    // Set how pairs of the same class are picked (see PRIORITY_PRICE_TIME)
+    pub fn set_priority_policy(ctx: Context<UpdateOrderBookConfig>, priority_policy: u8) -> Result<()> {
+        require!(priority_policy <= PRIORITY_PRICE_TIME, ErrorCode::InvalidPriorityPolicy);
+        ctx.accounts.orderbook.priority_policy = priority_policy;
+        Ok(())
+    }
+
+    // Set the most one match call may take from a single resting order (0 = no cap)
+    pub fn set_max_take_per_maker(ctx: Context<UpdateOrderBookConfig>, max_take_per_maker: u64) -> Result<()> {
+        ctx.accounts.orderbook.max_take_per_maker = max_take_per_maker;
+        Ok(())
+    }
+
+    // Set who gets the spread when two limit orders cross
+    pub fn set_spread_policy(ctx: Context<UpdateOrderBookConfig>, spread_policy: u8) -> Result<()> {
+        require!(spread_policy <= SPREAD_TAKER_FAVORABLE, ErrorCode::InvalidSpreadPolicy);
+        ctx.accounts.orderbook.spread_policy = spread_policy;
+        Ok(())
+    }
+
+    // Set where market orders take their price from, and the fallback
+    pub fn set_price_source_policy(ctx: Context<UpdateOrderBookConfig>, price_source_policy: u8) -> Result<()> {
+        require!(price_source_policy <= PRICE_SOURCE_BOOK_FIRST, ErrorCode::InvalidPriceSourcePolicy);
+        ctx.accounts.orderbook.price_source_policy = price_source_policy;
+        Ok(())
+    }
+
+    // Add order to encrypted order book
+    // Pass a previously reserved order_id, or None to allocate one now.
+    // notify_key is the x25519 key match_orders encrypts this order's fill
+    // notices to (all zeros for none)
+    pub fn add_order(
+        ctx: Context<AddOrder>,
+        computation_offset: u64,
+        order_id: Option<u64>,
+        order_price: [u8; 32],
+        order_amount: [u8; 32],
+        order_side: [u8; 32],
+        order_type: [u8; 32],
+        user_id: [u8; 32],
+        metadata: [u8; 32],
+        oco_group: [u8; 32],
+        order_flags: [u8; 32],
+        min_edge_bps: [u8; 32],
+        trade_group_id: [u8; 32],
+        notify_key: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let order_id = match order_id {
+            Some(id) => {
+                let payer = ctx.accounts.payer.key();
+                ctx.accounts.orderbook.take_reservation(id, payer, clock.slot)?;
+                id
+            }
+            None => ctx.accounts.orderbook.allocate_order_id()?,
+        };
+        require!(ctx.accounts.orderbook.in_session(clock.unix_timestamp), ErrorCode::MarketClosed);
+        require!(ctx.accounts.orderbook.draining != DRAINING, ErrorCode::BookDraining);
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .plaintext_u8(ctx.accounts.orderbook.allowlist_enabled as u8)
+            .plaintext_u64(ctx.accounts.orderbook.max_bid_price)
+            .plaintext_u64(ctx.accounts.orderbook.min_ask_price)
+            .plaintext_u64(clock.slot)
+            .plaintext_u64(clock.unix_timestamp as u64)
+            .plaintext_u64(ctx.accounts.orderbook.tick_size)
+            .plaintext_u8(ctx.accounts.orderbook.eviction_policy)
+            .plaintext_u64(ctx.accounts.orderbook.capacity_threshold)
+            .plaintext_u64(ctx.accounts.orderbook.min_notional)
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .x25519_pubkey(notify_key)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u64(order_price)
+            .encrypted_u64(order_amount)
+            .encrypted_u8(order_side)
+            .encrypted_u8(order_type)
+            .encrypted_u128(user_id)
+            .encrypted_u128(metadata)
+            .encrypted_u128(oco_group)
+            .encrypted_u64(order_flags)
+            .encrypted_u64(min_edge_bps)
+            .encrypted_u128(trade_group_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![AddOrderCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "add_order")]
+    pub fn add_order_callback(
+        ctx: Context<AddOrderCallback>,
+        output: SignedComputationOutputs<AddOrderOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(AddOrderOutput { field_0, field_1, field_2, field_3 }) => (field_0, field_1, field_2, field_3),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.0.ciphertexts, o.0.nonce, BookOp::AddOrder, computation_offset);
+
+        emit!(OrderAddedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        // The book was full and the new order displaced the worst resting one
+        if o.2 {
+            emit!(OrderEvictedEvent {
+                computation_offset,
+                record: o.1.ciphertexts,
+                nonce: o.1.nonce.to_le_bytes(),
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+
+        // This order took the book to the high-water mark
+        if o.3 {
+            emit!(BookNearCapacityEvent {
+                computation_offset,
+                threshold: ctx.accounts.orderbook.capacity_threshold,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+        Ok(())
+    }
+
+    // Match orders in encrypted order book
+    pub fn match_orders(
+        ctx: Context<MatchOrders>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        require!(ctx.accounts.orderbook.match_window_slots == 0, ErrorCode::MatchingWindowActive);
+        require!(
+            !ctx.accounts.orderbook.is_processed(computation_offset),
+            ErrorCode::ComputationAlreadyProcessed
+        );
+        require!(
+            ctx.accounts.orderbook.in_session(Clock::get()?.unix_timestamp),
+            ErrorCode::MarketClosed
+        );
+        require!(ctx.accounts.trading_gate.trading_enabled, ErrorCode::TradingDisabled);
+
+        let current_slot = Clock::get()?.slot;
+        let oracle = &ctx.accounts.oracle_price;
+        let oracle_fresh = oracle.is_fresh(current_slot);
+
+        // Only take a keeper reward out of the fees if the vault can pay it
+        let vault_spare = fee_vault_spare(&ctx.accounts.fee_vault.to_account_info())?;
+        let keeper_reward = if vault_spare >= ctx.accounts.orderbook.keeper_reward {
+            ctx.accounts.orderbook.keeper_reward
+        } else {
+            0
+        };
+
+        // Keep the book and parameters this match runs against, for replay_match
+        ctx.accounts.match_snapshot.capture(
+            &ctx.accounts.orderbook,
+            computation_offset,
+            oracle.price,
+            oracle_fresh,
+            current_slot,
+        );
+
+        // The pro-rata remainder seed comes from the book's state_hash rather
+        // than the keeper-chosen computation offset, so the keeper can't grind
+        // offsets to steer the remainder lot; anyone can still recompute it
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .plaintext_u64(oracle.price)
+            .plaintext_u8(oracle_fresh as u8)
+            .plaintext_u64(current_slot)
+            .plaintext_u64(MAX_FILLS_PER_SLOT)
+            .plaintext_u8(ctx.accounts.orderbook.rounding)
+            .plaintext_u8(ctx.accounts.orderbook.match_mode)
+            .plaintext_u64(ctx.accounts.orderbook.remainder_seed())
+            .plaintext_u64(ctx.accounts.orderbook.fill_increment)
+            .plaintext_i64(ctx.accounts.orderbook.maker_fee_bps as i64)
+            .plaintext_u64(ctx.accounts.orderbook.taker_fee_bps as u64)
+            .plaintext_u8(ctx.accounts.orderbook.fee_rounding)
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u8(ctx.accounts.orderbook.priority_policy)
+            .plaintext_u64(ctx.accounts.orderbook.max_take_per_maker)
+            .plaintext_u8(ctx.accounts.orderbook.spread_policy)
+            .plaintext_u64(ctx.accounts.orderbook.round_lot_size())
+            .plaintext_u64(ctx.accounts.orderbook.match_cooldown_slots)
+            .plaintext_u64(ctx.accounts.orderbook.price_scale())
+            .plaintext_u64(ctx.accounts.orderbook.cross_tolerance)
+            .plaintext_u8(ctx.accounts.orderbook.pair_rules)
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .plaintext_u64(Clock::get()?.unix_timestamp as u64)
+            .plaintext_u8(ctx.accounts.orderbook.price_source_policy)
+            .plaintext_u64(ctx.accounts.orderbook.max_match_fraction_bps)
+            .plaintext_u8(ctx.accounts.orderbook.lock_policy)
+            .plaintext_u64(ctx.accounts.orderbook.activation_delay_slots)
+            .plaintext_u8(ctx.accounts.orderbook.dust_policy)
+            .plaintext_u64(ctx.accounts.orderbook.lot_size)
+            .plaintext_u64(ctx.accounts.orderbook.min_notional)
+            .plaintext_u64(keeper_reward)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![MatchOrdersCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.orderbook.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.payer.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.match_snapshot.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.fee_vault.key(),
+                        is_writable: true,
+                    },
+                ]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "match_orders")]
+    pub fn match_orders_callback(
+        ctx: Context<MatchOrdersCallback>,
+        output: SignedComputationOutputs<MatchOrdersOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(MatchOrdersOutput { field_0, field_1, field_2, field_3, field_4, field_5, field_6 }) => {
+                (field_0, field_1, field_2, field_3, field_4, field_5, field_6)
+            }
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+
+        // A redelivered callback must not be applied twice
+        let pre_match_hash = orderbook.state_hash;
+        if !orderbook.store_once(o.0.ciphertexts, o.0.nonce, BookOp::MatchOrders, computation_offset) {
+            msg!("Match {} already processed, ignoring", computation_offset);
+            return Ok(());
+        }
+        ctx.accounts.match_snapshot.settle(computation_offset, pre_match_hash);
+        if o.5 {
+            orderbook.last_match_slot = Clock::get()?.slot;
+        }
+
+        emit!(orders_matched_event(
+            ctx.accounts.orderbook.report_trades,
+            computation_offset,
+            ctx.accounts.orderbook.state_hash,
+            &o.1.ciphertexts,
+            o.1.nonce,
+            Clock::get()?.unix_timestamp,
+        ));
+
+        // Each party's notice is encrypted to its order's notify_key, so it is
+        // emitted whatever report_trades says
+        if o.5 {
+            for notice in [o.2, o.3] {
+                emit!(FillNoticeEvent {
+                    computation_offset,
+                    notice: notice.ciphertexts,
+                    nonce: notice.nonce.to_le_bytes(),
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+            }
+        }
+
+        // A market order is resting against an empty opposite side
+        if o.4 {
+            emit!(OrderUnfilledEvent {
+                computation_offset,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+
+        // Reward the keeper for a call that filled. The circuit took the reward
+        // (o.6) out of the venue's net fees and the fee vault pays it out
+        let reward = ctx.accounts.orderbook.keeper_reward;
+        let vault = ctx.accounts.fee_vault.to_account_info();
+        if o.6 > 0 && fee_vault_spare(&vault)? >= o.6 {
+            pay_from_fee_vault(&vault, &ctx.accounts.keeper, o.6)?;
+            let orderbook = &mut ctx.accounts.orderbook;
+            orderbook.keeper_rewards_paid = orderbook.keeper_rewards_paid.saturating_add(o.6 as u128);
+
+            emit!(KeeperRewardedEvent {
+                computation_offset,
+                keeper: ctx.accounts.keeper.key(),
+                amount: o.6,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        } else if o.5 && reward > 0 {
+            emit!(KeeperRewardUnpaidEvent {
+                computation_offset,
+                keeper: ctx.accounts.keeper.key(),
+                amount: reward,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+        Ok(())
+    }
+
+    // Cancel order
+    pub fn cancel_order(
+        ctx: Context<CancelOrder>,
+        computation_offset: u64,
+        order_id: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .plaintext_u64(Clock::get()?.slot)
+            .plaintext_u64(ctx.accounts.orderbook.min_rest_slots)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CancelOrderCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "cancel_order")]
+    pub fn cancel_order_callback(
+        ctx: Context<CancelOrderCallback>,
+        output: SignedComputationOutputs<CancelOrderOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(CancelOrderOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::CancelOrder, computation_offset);
+
+        emit!(OrderCancelledEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get queue position of a user's order
+    pub fn get_queue_position(
+        ctx: Context<GetQueuePosition>,
+        computation_offset: u64,
+        order_id: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetQueuePositionCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_queue_position")]
+    pub fn get_queue_position_callback(
+        ctx: Context<GetQueuePositionCallback>,
+        output: SignedComputationOutputs<GetQueuePositionOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetQueuePositionOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(QueuePositionEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            position: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get status of a user's order
+    pub fn get_order_status(
+        ctx: Context<GetOrderStatus>,
+        computation_offset: u64,
+        order_id: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetOrderStatusCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_order_status")]
+    pub fn get_order_status_callback(
+        ctx: Context<GetOrderStatusCallback>,
+        output: SignedComputationOutputs<GetOrderStatusOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetOrderStatusOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(OrderStatusEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            status: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Match several pairs in one computation, throttled per call and per slot
+    pub fn match_orders_batch(
+        ctx: Context<MatchOrdersBatch>,
+        computation_offset: u64,
+        max_fills_this_call: u64,
+    ) -> Result<()> {
+        require!(ctx.accounts.orderbook.match_window_slots == 0, ErrorCode::MatchingWindowActive);
+        require!(
+            !ctx.accounts.orderbook.is_processed(computation_offset),
+            ErrorCode::ComputationAlreadyProcessed
+        );
+        require!(
+            ctx.accounts.orderbook.in_session(Clock::get()?.unix_timestamp),
+            ErrorCode::MarketClosed
+        );
+        require!(ctx.accounts.trading_gate.trading_enabled, ErrorCode::TradingDisabled);
+
+        let current_slot = Clock::get()?.slot;
+        let oracle = &ctx.accounts.oracle_price;
+        let oracle_fresh = oracle.is_fresh(current_slot);
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .plaintext_u64(oracle.price)
+            .plaintext_u8(oracle_fresh as u8)
+            .plaintext_u64(current_slot)
+            .plaintext_u64(max_fills_this_call)
+            .plaintext_u64(MAX_FILLS_PER_SLOT)
+            .plaintext_u8(ctx.accounts.orderbook.rounding)
+            .plaintext_u64(ctx.accounts.orderbook.fill_increment)
+            .plaintext_i64(ctx.accounts.orderbook.maker_fee_bps as i64)
+            .plaintext_u64(ctx.accounts.orderbook.taker_fee_bps as u64)
+            .plaintext_u8(ctx.accounts.orderbook.fee_rounding)
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u8(ctx.accounts.orderbook.priority_policy)
+            .plaintext_u64(ctx.accounts.orderbook.max_take_per_maker)
+            .plaintext_u8(ctx.accounts.orderbook.spread_policy)
+            .plaintext_u64(ctx.accounts.orderbook.round_lot_size())
+            .plaintext_u64(ctx.accounts.orderbook.match_cooldown_slots)
+            .plaintext_u64(ctx.accounts.orderbook.price_scale())
+            .plaintext_u64(ctx.accounts.orderbook.cross_tolerance)
+            .plaintext_u8(ctx.accounts.orderbook.pair_rules)
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .plaintext_u64(Clock::get()?.unix_timestamp as u64)
+            .plaintext_u8(ctx.accounts.orderbook.price_source_policy)
+            .plaintext_u64(ctx.accounts.orderbook.max_match_fraction_bps)
+            .plaintext_u8(ctx.accounts.orderbook.lock_policy)
+            .plaintext_u64(ctx.accounts.orderbook.activation_delay_slots)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![MatchOrdersBatchCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "match_orders_batch")]
+    pub fn match_orders_batch_callback(
+        ctx: Context<MatchOrdersBatchCallback>,
+        output: SignedComputationOutputs<MatchOrdersBatchOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(MatchOrdersBatchOutput { field_0, field_1, field_2, field_3 }) => (field_0, field_1, field_2, field_3),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+
+        // A redelivered callback must not be applied twice
+        if !orderbook.store_once(o.0.ciphertexts, o.0.nonce, BookOp::MatchOrdersBatch, computation_offset) {
+            msg!("Match {} already processed, ignoring", computation_offset);
+            return Ok(());
+        }
+
+        let report = ctx.accounts.orderbook.report_trades == REPORT_TRADES_FULL;
+
+        emit!(OrdersBatchMatchedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            match_results: report.then(|| o.1.ciphertexts.to_vec()),
+            nonce: o.1.nonce.to_le_bytes(),
+            net_settlements: o.2.ciphertexts.to_vec(),
+            net_nonce: o.2.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        // One compact event for consumers that only need the batch totals
+        emit!(BatchMatchSummaryEvent {
+            computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            summary: report.then(|| o.3.ciphertexts),
+            nonce: o.3.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Check whether the book is crossed
+    pub fn is_crossed(
+        ctx: Context<IsCrossed>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![IsCrossedCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "is_crossed")]
+    pub fn is_crossed_callback(
+        ctx: Context<IsCrossedCallback>,
+        output: SignedComputationOutputs<IsCrossedOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(IsCrossedOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(BookCrossedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            crossed: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Cancel several orders by id
+    pub fn cancel_orders_by_ids(
+        ctx: Context<CancelOrdersByIds>,
+        computation_offset: u64,
+        order_ids: [u64; MAX_CANCEL_IDS],
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let mut builder = ArgBuilder::new();
+        for order_id in order_ids {
+            builder = builder.plaintext_u64(order_id);
+        }
+        let args = builder
+            .plaintext_u64(Clock::get()?.slot)
+            .plaintext_u64(ctx.accounts.orderbook.min_rest_slots)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CancelOrdersByIdsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "cancel_orders_by_ids")]
+    pub fn cancel_orders_by_ids_callback(
+        ctx: Context<CancelOrdersByIdsCallback>,
+        output: SignedComputationOutputs<CancelOrdersByIdsOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(CancelOrdersByIdsOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::CancelOrdersByIds, computation_offset);
+
+        emit!(OrdersCancelledEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Create the order book account and its empty encrypted state
+    pub fn init_orderbook(
+        ctx: Context<InitOrderbook>,
+        computation_offset: u64,
+        nonce: u128,
+    ) -> Result<()> {
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.bump = ctx.bumps.orderbook;
+        orderbook.authority = ctx.accounts.payer.key();
+        orderbook.layout_version = ORDERBOOK_LAYOUT_VERSION;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(nonce)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![InitOrderbookCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "init_orderbook")]
+    pub fn init_orderbook_callback(
+        ctx: Context<InitOrderbookCallback>,
+        output: SignedComputationOutputs<InitOrderbookOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(InitOrderbookOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::InitOrderbook, computation_offset);
+
+        emit!(OrderBookInitializedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Touch the freshly initialized book once so the first match has steady-state latency
+    pub fn warm_book(
+        ctx: Context<WarmBook>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![WarmBookCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "warm_book")]
+    pub fn warm_book_callback(
+        ctx: Context<WarmBookCallback>,
+        output: SignedComputationOutputs<WarmBookOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(WarmBookOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::WarmBook, computation_offset);
+
+        emit!(OrderBookWarmedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get a contiguous range of depth buckets
+    pub fn get_depth_range(
+        ctx: Context<GetDepthRange>,
+        computation_offset: u64,
+        start: u64,
+        end: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            start <= end && end < DEPTH_BUCKETS && end - start < MAX_DEPTH_RANGE,
+            ErrorCode::InvalidDepthRange
+        );
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(start)
+            .plaintext_u64(end)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetDepthRangeCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_depth_range")]
+    pub fn get_depth_range_callback(
+        ctx: Context<GetDepthRangeCallback>,
+        output: SignedComputationOutputs<GetDepthRangeOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetDepthRangeOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(DepthRangeEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            depth: o.ciphertexts.to_vec(),
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Emergency kill-switch: cancel all orders and queue them for settlement (authority only)
+    pub fn emergency_cancel_all(
+        ctx: Context<EmergencyCancelAll>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.settlement_queue.pending, ErrorCode::SettlementQueueBusy);
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .plaintext_u64(Clock::get()?.slot)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![EmergencyCancelAllCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.orderbook.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.settlement_queue.key(),
+                        is_writable: true,
+                    },
+                ]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "emergency_cancel_all")]
+    pub fn emergency_cancel_all_callback(
+        ctx: Context<EmergencyCancelAllCallback>,
+        output: SignedComputationOutputs<EmergencyCancelAllOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(EmergencyCancelAllOutput { field_0, field_1 }) => (field_0, field_1),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.0.ciphertexts, o.0.nonce, BookOp::EmergencyCancelAll, computation_offset);
+
+        let queue = &mut ctx.accounts.settlement_queue;
+        queue.cancellations = o.1.ciphertexts;
+        queue.nonce = o.1.nonce;
+        queue.pending = true;
+
+        emit!(EmergencyCancelAllEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the quoted bid/ask spread in basis points of mid
+    pub fn get_effective_spread_bps(
+        ctx: Context<GetEffectiveSpreadBps>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetEffectiveSpreadBpsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_effective_spread_bps")]
+    pub fn get_effective_spread_bps_callback(
+        ctx: Context<GetEffectiveSpreadBpsCallback>,
+        output: SignedComputationOutputs<GetEffectiveSpreadBpsOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetEffectiveSpreadBpsOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(EffectiveSpreadEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            spread_bps: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get quote-denominated depth (price * amount per bucket)
+    pub fn get_orderbook_depth_quote(
+        ctx: Context<GetOrderbookDepthQuote>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetOrderbookDepthQuoteCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_orderbook_depth_quote")]
+    pub fn get_orderbook_depth_quote_callback(
+        ctx: Context<GetOrderbookDepthQuoteCallback>,
+        output: SignedComputationOutputs<GetOrderbookDepthQuoteOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetOrderbookDepthQuoteOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(QuoteDepthEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            depth: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Add an encrypted user_id to the allow-list (authority only)
+    pub fn add_to_allowlist(
+        ctx: Context<AddToAllowlist>,
+        computation_offset: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![AddToAllowlistCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "add_to_allowlist")]
+    pub fn add_to_allowlist_callback(
+        ctx: Context<AddToAllowlistCallback>,
+        output: SignedComputationOutputs<AddToAllowlistOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(AddToAllowlistOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::AddToAllowlist, computation_offset);
+
+        emit!(AllowlistUpdatedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Remove an encrypted user_id from the allow-list (authority only)
+    pub fn remove_from_allowlist(
+        ctx: Context<RemoveFromAllowlist>,
+        computation_offset: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![RemoveFromAllowlistCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "remove_from_allowlist")]
+    pub fn remove_from_allowlist_callback(
+        ctx: Context<RemoveFromAllowlistCallback>,
+        output: SignedComputationOutputs<RemoveFromAllowlistOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(RemoveFromAllowlistOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::RemoveFromAllowlist, computation_offset);
+
+        emit!(AllowlistUpdatedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the last trade price and amount
+    pub fn get_last_trade(
+        ctx: Context<GetLastTrade>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetLastTradeCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_last_trade")]
+    pub fn get_last_trade_callback(
+        ctx: Context<GetLastTradeCallback>,
+        output: SignedComputationOutputs<GetLastTradeOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetLastTradeOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(LastTradeEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            last_trade: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get active slot count and highest active slot index (compaction watchdog)
+    pub fn get_fragmentation(
+        ctx: Context<GetFragmentation>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetFragmentationCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_fragmentation")]
+    pub fn get_fragmentation_callback(
+        ctx: Context<GetFragmentationCallback>,
+        output: SignedComputationOutputs<GetFragmentationOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetFragmentationOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(FragmentationEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            fragmentation: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Estimate the execution price of sweeping a volume from one side of the book
+    pub fn get_price_at_volume(
+        ctx: Context<GetPriceAtVolume>,
+        computation_offset: u64,
+        target_volume: u64,
+        side: u8,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(side <= 1, ErrorCode::InvalidSide);
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(target_volume)
+            .plaintext_u8(side)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetPriceAtVolumeCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_price_at_volume")]
+    pub fn get_price_at_volume_callback(
+        ctx: Context<GetPriceAtVolumeCallback>,
+        output: SignedComputationOutputs<GetPriceAtVolumeOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetPriceAtVolumeOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(PriceAtVolumeEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            price: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Estimate how fillable a user's resting order is right now (bps)
+    pub fn get_fill_likelihood(
+        ctx: Context<GetFillLikelihood>,
+        computation_offset: u64,
+        order_id: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetFillLikelihoodCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_fill_likelihood")]
+    pub fn get_fill_likelihood_callback(
+        ctx: Context<GetFillLikelihoodCallback>,
+        output: SignedComputationOutputs<GetFillLikelihoodOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetFillLikelihoodOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(FillLikelihoodEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            ratio_bps: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get active order counts per price bucket (anonymity set sizes per level)
+    pub fn get_active_distribution(
+        ctx: Context<GetActiveDistribution>,
+        computation_offset: u64,
+        base_price: u64,
+        bucket_width: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(bucket_width > 0, ErrorCode::InvalidDepthRange);
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(base_price)
+            .plaintext_u64(bucket_width)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetActiveDistributionCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_active_distribution")]
+    pub fn get_active_distribution_callback(
+        ctx: Context<GetActiveDistributionCallback>,
+        output: SignedComputationOutputs<GetActiveDistributionOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetActiveDistributionOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(ActiveDistributionEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            counts: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the order ids added, removed or changed since a client-held book version
+    pub fn get_book_diff(
+        ctx: Context<GetBookDiff>,
+        computation_offset: u64,
+        book_version: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(book_version)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetBookDiffCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_book_diff")]
+    pub fn get_book_diff_callback(
+        ctx: Context<GetBookDiffCallback>,
+        output: SignedComputationOutputs<GetBookDiffOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetBookDiffOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(BookDiffEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            diff: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get a user's order's global time-priority rank on its side
+    pub fn get_time_rank(
+        ctx: Context<GetTimeRank>,
+        computation_offset: u64,
+        order_id: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetTimeRankCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_time_rank")]
+    pub fn get_time_rank_callback(
+        ctx: Context<GetTimeRankCallback>,
+        output: SignedComputationOutputs<GetTimeRankOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetTimeRankOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(TimeRankEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            rank: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Refresh the caller's cancel-on-disconnect heartbeat
+    pub fn heartbeat(
+        ctx: Context<Heartbeat>,
+        computation_offset: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(now)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![HeartbeatCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "heartbeat")]
+    pub fn heartbeat_callback(
+        ctx: Context<HeartbeatCallback>,
+        output: SignedComputationOutputs<HeartbeatOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(HeartbeatOutput { field_0, field_1 }) => (field_0, field_1),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.0.ciphertexts, o.0.nonce, BookOp::Heartbeat, computation_offset);
+
+        emit!(HeartbeatEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            recorded: o.1.ciphertexts[0],
+            nonce: o.1.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Keeper: cancel all orders of users whose heartbeat is older than the
+    // book's heartbeat_ttl. The TTL is admin-set, so a keeper only chooses when
+    // to run the sweep, not how aggressive it is
+    pub fn cancel_stale_users(
+        ctx: Context<CancelStaleUsers>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let ttl = ctx.accounts.orderbook.heartbeat_ttl;
+        require!(ttl >= MIN_HEARTBEAT_TTL, ErrorCode::InvalidHeartbeatTtl);
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp as u64;
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(now)
+            .plaintext_u64(ttl)
+            .plaintext_u64(clock.slot)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CancelStaleUsersCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "cancel_stale_users")]
+    pub fn cancel_stale_users_callback(
+        ctx: Context<CancelStaleUsersCallback>,
+        output: SignedComputationOutputs<CancelStaleUsersOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(CancelStaleUsersOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::CancelStaleUsers, computation_offset);
+
+        emit!(StaleUsersCancelledEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the buy-side share of resting volume in bps
+    pub fn get_imbalance_ratio(
+        ctx: Context<GetImbalanceRatio>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetImbalanceRatioCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_imbalance_ratio")]
+    pub fn get_imbalance_ratio_callback(
+        ctx: Context<GetImbalanceRatioCallback>,
+        output: SignedComputationOutputs<GetImbalanceRatioOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetImbalanceRatioOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(ImbalanceRatioEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            ratio_bps: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the caller's lifetime filled volume (fee-tier tracking)
+    pub fn get_user_volume(
+        ctx: Context<GetUserVolume>,
+        computation_offset: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetUserVolumeCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_user_volume")]
+    pub fn get_user_volume_callback(
+        ctx: Context<GetUserVolumeCallback>,
+        output: SignedComputationOutputs<GetUserVolumeOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetUserVolumeOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(UserVolumeEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            volume: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get active order counts per age bucket (<1m, <5m, <1h, older)
+    pub fn get_age_histogram(
+        ctx: Context<GetAgeHistogram>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(now)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetAgeHistogramCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_age_histogram")]
+    pub fn get_age_histogram_callback(
+        ctx: Context<GetAgeHistogramCallback>,
+        output: SignedComputationOutputs<GetAgeHistogramOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetAgeHistogramOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(AgeHistogramEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            counts: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Amend the price and size of an order (priority kept on reductions). While
+    // the book is draining only size reductions go through
+    pub fn modify_order(
+        ctx: Context<ModifyOrder>,
+        computation_offset: u64,
+        order_id: u64,
+        user_id: [u8; 32],
+        new_price: [u8; 32],
+        new_amount: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .plaintext_u64(ctx.accounts.orderbook.tick_size)
+            .plaintext_u64(Clock::get()?.slot)
+            .plaintext_u8((ctx.accounts.orderbook.draining == DRAINING) as u8)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .encrypted_u64(new_price)
+            .encrypted_u64(new_amount)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![ModifyOrderCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "modify_order")]
+    pub fn modify_order_callback(
+        ctx: Context<ModifyOrderCallback>,
+        output: SignedComputationOutputs<ModifyOrderOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(ModifyOrderOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::ModifyOrder, computation_offset);
+
+        emit!(OrderModifiedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Take a depth snapshot and make it the baseline for get_depth_delta
+    pub fn snapshot_depth(
+        ctx: Context<SnapshotDepth>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![SnapshotDepthCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "snapshot_depth")]
+    pub fn snapshot_depth_callback(
+        ctx: Context<SnapshotDepthCallback>,
+        output: SignedComputationOutputs<SnapshotDepthOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(SnapshotDepthOutput { field_0, field_1 }) => (field_0, field_1),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.0.ciphertexts, o.0.nonce, BookOp::SnapshotDepth, computation_offset);
+
+        emit!(DepthSnapshotEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            snapshot: o.1.ciphertexts,
+            nonce: o.1.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the depth buckets that changed since the snapshot at book_version
+    pub fn get_depth_delta(
+        ctx: Context<GetDepthDelta>,
+        computation_offset: u64,
+        book_version: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(book_version)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetDepthDeltaCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_depth_delta")]
+    pub fn get_depth_delta_callback(
+        ctx: Context<GetDepthDeltaCallback>,
+        output: SignedComputationOutputs<GetDepthDeltaOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetDepthDeltaOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(DepthDeltaEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            delta: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Clear crossing orders accumulated in the matching window, up to eight
+    // fills per crank (MAX_CRANK_FILLS in the circuit); anything still crossed
+    // is cleared by the next window's crank
+    // Anyone can crank once the window has closed; the crank prices and ranks
+    // fills with the book's matching policies, like match_orders, but always
+    // pairs orders one at a time (no pro-rata) and without a per-maker cap.
+    // This is the only path that fills auction-only orders
+    pub fn crank_match(
+        ctx: Context<CrankMatch>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let orderbook = &mut ctx.accounts.orderbook;
+        require!(orderbook.match_window_slots > 0, ErrorCode::NoMatchingWindow);
+        require!(!orderbook.is_processed(computation_offset), ErrorCode::ComputationAlreadyProcessed);
+        require!(orderbook.in_session(Clock::get()?.unix_timestamp), ErrorCode::MarketClosed);
+        require!(ctx.accounts.trading_gate.trading_enabled, ErrorCode::TradingDisabled);
+
+        let current_slot = Clock::get()?.slot;
+        require!(
+            current_slot >= orderbook.window_start_slot + orderbook.match_window_slots,
+            ErrorCode::MatchingWindowOpen
+        );
+        // Open the next window now so a second crank can't clear this one again
+        orderbook.window_start_slot = current_slot;
+
+        let oracle = &ctx.accounts.oracle_price;
+        let oracle_fresh = oracle.is_fresh(current_slot);
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .plaintext_u64(oracle.price)
+            .plaintext_u8(oracle_fresh as u8)
+            .plaintext_u64(current_slot)
+            .plaintext_u8(ctx.accounts.orderbook.rounding)
+            .plaintext_u64(ctx.accounts.orderbook.fill_increment)
+            .plaintext_i64(ctx.accounts.orderbook.maker_fee_bps as i64)
+            .plaintext_u64(ctx.accounts.orderbook.taker_fee_bps as u64)
+            .plaintext_u8(ctx.accounts.orderbook.fee_rounding)
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u8(ctx.accounts.orderbook.priority_policy)
+            .plaintext_u8(ctx.accounts.orderbook.spread_policy)
+            .plaintext_u64(ctx.accounts.orderbook.round_lot_size())
+            .plaintext_u64(ctx.accounts.orderbook.price_scale())
+            .plaintext_u64(ctx.accounts.orderbook.cross_tolerance)
+            .plaintext_u8(ctx.accounts.orderbook.pair_rules)
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .plaintext_u64(Clock::get()?.unix_timestamp as u64)
+            .plaintext_u8(ctx.accounts.orderbook.price_source_policy)
+            .plaintext_u64(ctx.accounts.orderbook.max_match_fraction_bps)
+            .plaintext_u8(ctx.accounts.orderbook.lock_policy)
+            .plaintext_u64(ctx.accounts.orderbook.activation_delay_slots)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CrankMatchCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "crank_match")]
+    pub fn crank_match_callback(
+        ctx: Context<CrankMatchCallback>,
+        output: SignedComputationOutputs<CrankMatchOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(CrankMatchOutput { field_0, field_1 }) => (field_0, field_1),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+
+        if !orderbook.store_once(o.0.ciphertexts, o.0.nonce, BookOp::CrankMatch, computation_offset) {
+            msg!("Crank {} already processed, ignoring", computation_offset);
+            return Ok(());
+        }
+
+        let report = ctx.accounts.orderbook.report_trades == REPORT_TRADES_FULL;
+
+        emit!(CrankMatchedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            summary: report.then(|| o.1.ciphertexts),
+            nonce: o.1.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Check the book invariants (bitmask: 1 = order count, 2 = zero-amount
+    // order, 4 = duplicate order id)
+    pub fn check_invariants(
+        ctx: Context<CheckInvariants>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CheckInvariantsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "check_invariants")]
+    pub fn check_invariants_callback(
+        ctx: Context<CheckInvariantsCallback>,
+        output: SignedComputationOutputs<CheckInvariantsOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(CheckInvariantsOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(InvariantsCheckedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            violations: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the caller's fills among the recent trades
+    pub fn get_user_fills(
+        ctx: Context<GetUserFills>,
+        computation_offset: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetUserFillsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_user_fills")]
+    pub fn get_user_fills_callback(
+        ctx: Context<GetUserFillsCallback>,
+        output: SignedComputationOutputs<GetUserFillsOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetUserFillsOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(UserFillsEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            fills: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the best price levels per side as a sorted ladder
+    pub fn get_sorted_ladder(
+        ctx: Context<GetSortedLadder>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetSortedLadderCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_sorted_ladder")]
+    pub fn get_sorted_ladder_callback(
+        ctx: Context<GetSortedLadderCallback>,
+        output: SignedComputationOutputs<GetSortedLadderOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetSortedLadderOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(SortedLadderEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            ladder: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the caller's orders modified after since_slot
+    pub fn get_orders_modified_since(
+        ctx: Context<GetOrdersModifiedSince>,
+        computation_offset: u64,
+        since_slot: u64,
+        user_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(since_slot)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetOrdersModifiedSinceCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_orders_modified_since")]
+    pub fn get_orders_modified_since_callback(
+        ctx: Context<GetOrdersModifiedSinceCallback>,
+        output: SignedComputationOutputs<GetOrdersModifiedSinceOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetOrdersModifiedSinceOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(OrdersModifiedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            updates: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the notional traded over the rolling last 24 hours
+    pub fn get_volume_24h(
+        ctx: Context<GetVolume24h>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(Clock::get()?.unix_timestamp as u64)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetVolume24hCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_volume_24h")]
+    pub fn get_volume_24h_callback(
+        ctx: Context<GetVolume24hCallback>,
+        output: SignedComputationOutputs<GetVolume24hOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetVolume24hOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(Volume24hEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            volume: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the worst-priced resting order on each side
+    pub fn get_worst_orders(
+        ctx: Context<GetWorstOrders>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetWorstOrdersCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_worst_orders")]
+    pub fn get_worst_orders_callback(
+        ctx: Context<GetWorstOrdersCallback>,
+        output: SignedComputationOutputs<GetWorstOrdersOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetWorstOrdersOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(WorstOrdersEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            worst: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the volume-weighted average price of the caller's orders on a side
+    pub fn get_user_avg_price(
+        ctx: Context<GetUserAvgPrice>,
+        computation_offset: u64,
+        user_id: [u8; 32],
+        side: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u128(user_id)
+            .encrypted_u8(side)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetUserAvgPriceCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_user_avg_price")]
+    pub fn get_user_avg_price_callback(
+        ctx: Context<GetUserAvgPriceCallback>,
+        output: SignedComputationOutputs<GetUserAvgPriceOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetUserAvgPriceOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(UserAvgPriceEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            avg_price: o.ciphertexts[0],
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Swap the time priority of two orders (admin only, for priority corrections)
+    pub fn swap_priority(
+        ctx: Context<SwapPriority>,
+        computation_offset: u64,
+        order_a: u64,
+        order_b: u64,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_a)
+            .plaintext_u64(order_b)
+            .plaintext_u64(Clock::get()?.slot)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![SwapPriorityCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "swap_priority")]
+    pub fn swap_priority_callback(
+        ctx: Context<SwapPriorityCallback>,
+        output: SignedComputationOutputs<SwapPriorityOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(SwapPriorityOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.ciphertexts, o.nonce, BookOp::SwapPriority, computation_offset);
+
+        emit!(PrioritySwappedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Reveal accrued fees and rebates owed (admin only, the values are public once revealed)
+    pub fn get_fee_accounting(
+        ctx: Context<GetFeeAccounting>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetFeeAccountingCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_fee_accounting")]
+    pub fn get_fee_accounting_callback(
+        ctx: Context<GetFeeAccountingCallback>,
+        output: SignedComputationOutputs<GetFeeAccountingOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetFeeAccountingOutput { field_0, field_1, field_2 }) => (field_0, field_1, field_2),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(FeeAccountingEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            fees_collected: o.0,
+            rebates_owed: o.1,
+            fees_net: o.2,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Withdraw the net fees accrued by the venue to `destination` (admin only)
+    // The callback pays the revealed amount out of the fee vault; if the vault
+    // can't cover it the callback fails and the fees stay on the book
+    pub fn withdraw_fees(
+        ctx: Context<WithdrawFees>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![WithdrawFeesCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.orderbook.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.fee_vault.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.destination.key(),
+                        is_writable: true,
+                    },
+                ]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "withdraw_fees")]
+    pub fn withdraw_fees_callback(
+        ctx: Context<WithdrawFeesCallback>,
+        output: SignedComputationOutputs<WithdrawFeesOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(WithdrawFeesOutput { field_0, field_1 }) => (field_0, field_1),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let amount = u64::try_from(o.1).map_err(|_| ErrorCode::FeeVaultInsufficient)?;
+        pay_from_fee_vault(&ctx.accounts.fee_vault.to_account_info(), &ctx.accounts.destination, amount)?;
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.0.ciphertexts, o.0.nonce, BookOp::WithdrawFees, computation_offset);
+
+        emit!(FeesWithdrawnEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            destination: ctx.accounts.destination.key(),
+            amount: o.1,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get the cumulative depth chart (running volume from the touch outward)
+    pub fn get_depth_cumulative(
+        ctx: Context<GetDepthCumulative>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetDepthCumulativeCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_depth_cumulative")]
+    pub fn get_depth_cumulative_callback(
+        ctx: Context<GetDepthCumulativeCallback>,
+        output: SignedComputationOutputs<GetDepthCumulativeOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetDepthCumulativeOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(CumulativeDepthEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            depth: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Check that a recorded match is reproducible from its inputs, for disputes
+    // Only the most recent match_orders call can be replayed: its pre-trade
+    // book and parameters are kept in the MatchSnapshot account. `recorded` /
+    // `recorded_nonce` are the MatchResult ciphertexts from that match's
+    // callback output (OrdersMatchedEvent may omit price and amount) and
+    // `match_offset` its computation offset
+    pub fn replay_match(
+        ctx: Context<ReplayMatch>,
+        computation_offset: u64,
+        match_offset: u64,
+        recorded: [[u8; 32]; MATCH_RESULT_FIELDS],
+        recorded_nonce: u128,
+    ) -> Result<()> {
+        let snapshot = &ctx.accounts.match_snapshot;
+        require!(
+            snapshot.replayable && snapshot.computation_offset == match_offset,
+            ErrorCode::ReplayStateMismatch
+        );
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(snapshot.nonce)
+            .account(snapshot.key(), MATCH_SNAPSHOT_BOOK_OFFSET, ORDERBOOK_LEN)
+            .plaintext_u64(snapshot.oracle_price)
+            .plaintext_u8(snapshot.oracle_fresh as u8)
+            .plaintext_u64(snapshot.slot)
+            .plaintext_u8(snapshot.rounding)
+            .plaintext_u8(snapshot.match_mode)
+            .plaintext_u64(snapshot.seed)
+            .plaintext_u64(snapshot.fill_increment)
+            .plaintext_u8(snapshot.priority_policy)
+            .plaintext_u64(snapshot.max_take_per_maker)
+            .plaintext_u8(snapshot.spread_policy)
+            .plaintext_u64(snapshot.round_lot_size)
+            .plaintext_u64(snapshot.match_cooldown_slots)
+            .plaintext_u64(snapshot.price_scale)
+            .plaintext_u64(snapshot.cross_tolerance)
+            .plaintext_u8(snapshot.pair_rules)
+            .plaintext_u64(snapshot.price_offset)
+            .plaintext_u8(snapshot.price_source_policy)
+            .plaintext_u64(snapshot.max_match_fraction_bps)
+            .plaintext_u8(snapshot.lock_policy)
+            .plaintext_u64(snapshot.activation_delay_slots)
+            .plaintext_u128(recorded_nonce)
+            .encrypted_u8(recorded[0])
+            .encrypted_u64(recorded[1])
+            .encrypted_u64(recorded[2])
+            .encrypted_u64(recorded[3])
+            .encrypted_u64(recorded[4])
+            .encrypted_u8(recorded[5])
+            .encrypted_u64(recorded[6])
+            .encrypted_u64(recorded[7])
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![ReplayMatchCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "replay_match")]
+    pub fn replay_match_callback(
+        ctx: Context<ReplayMatchCallback>,
+        output: SignedComputationOutputs<ReplayMatchOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(ReplayMatchOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(MatchReplayedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            reproducible: o,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Export one page of order slots to the operator (admin only: pages expose
+    // individual orders). Page through start_index = 0, BOOK_PAGE, ... to rebuild the book
+    pub fn get_book_page(
+        ctx: Context<GetBookPage>,
+        computation_offset: u64,
+        start_index: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            start_index
+                .checked_add(BOOK_PAGE as u64)
+                .filter(|end| *end <= MAX_ORDERS as u64)
+                .is_some(),
+            ErrorCode::InvalidPageStart
+        );
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(start_index)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetBookPageCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_book_page")]
+    pub fn get_book_page_callback(
+        ctx: Context<GetBookPageCallback>,
+        output: SignedComputationOutputs<GetBookPageOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetBookPageOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(BookPageEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            orders: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get per-level volume and order count (for queue-position estimates)
+    pub fn get_depth_counts(
+        ctx: Context<GetDepthCounts>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetDepthCountsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_depth_counts")]
+    pub fn get_depth_counts_callback(
+        ctx: Context<GetDepthCountsCallback>,
+        output: SignedComputationOutputs<GetDepthCountsOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetDepthCountsOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(DepthCountsEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            depth: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Submit an immediate-or-cancel order: it fills against the resting book
+    // at once and any remainder is cancelled instead of resting.
+    // Takes the same encrypted order fields as add_order
+    pub fn submit_ioc(
+        ctx: Context<SubmitIoc>,
+        computation_offset: u64,
+        order_price: [u8; 32],
+        order_amount: [u8; 32],
+        order_side: [u8; 32],
+        order_type: [u8; 32],
+        user_id: [u8; 32],
+        metadata: [u8; 32],
+        oco_group: [u8; 32],
+        order_flags: [u8; 32],
+        min_edge_bps: [u8; 32],
+        trade_group_id: [u8; 32],
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(ctx.accounts.orderbook.in_session(clock.unix_timestamp), ErrorCode::MarketClosed);
+        require!(ctx.accounts.trading_gate.trading_enabled, ErrorCode::TradingDisabled);
+        require!(ctx.accounts.orderbook.draining != DRAINING, ErrorCode::BookDraining);
+        let order_id = ctx.accounts.orderbook.allocate_order_id()?;
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(order_id)
+            .plaintext_u8(ctx.accounts.orderbook.allowlist_enabled as u8)
+            .plaintext_u64(clock.slot)
+            .plaintext_u64(clock.unix_timestamp as u64)
+            .plaintext_i64(ctx.accounts.orderbook.maker_fee_bps as i64)
+            .plaintext_u64(ctx.accounts.orderbook.taker_fee_bps as u64)
+            .plaintext_u8(ctx.accounts.orderbook.fee_rounding)
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u64(ctx.accounts.orderbook.price_scale())
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .plaintext_u64(ctx.accounts.orderbook.activation_delay_slots)
+            .plaintext_u64(ctx.accounts.orderbook.fill_increment)
+            .plaintext_u64(ctx.accounts.orderbook.match_cooldown_slots)
+            .plaintext_u64(MAX_FILLS_PER_SLOT)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .encrypted_u64(order_price)
+            .encrypted_u64(order_amount)
+            .encrypted_u8(order_side)
+            .encrypted_u8(order_type)
+            .encrypted_u128(user_id)
+            .encrypted_u128(metadata)
+            .encrypted_u128(oco_group)
+            .encrypted_u64(order_flags)
+            .encrypted_u64(min_edge_bps)
+            .encrypted_u128(trade_group_id)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![SubmitIocCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "submit_ioc")]
+    pub fn submit_ioc_callback(
+        ctx: Context<SubmitIocCallback>,
+        output: SignedComputationOutputs<SubmitIocOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(SubmitIocOutput { field_0, field_1 }) => (field_0, field_1),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.0.ciphertexts, o.0.nonce, BookOp::SubmitIoc, computation_offset);
+
+        emit!(IocExecutedEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            report: o.1.ciphertexts,
+            nonce: o.1.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Sweep the oldest resting market order through the book, filling resting
+    // orders in strict FIFO order within each price level. The fills come back
+    // encrypted to `pub_key`, so a taker sweeping its own order can read them
+    pub fn match_market_sweep(
+        ctx: Context<MatchMarketSweep>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(ctx.accounts.orderbook.match_window_slots == 0, ErrorCode::MatchingWindowActive);
+        let clock = Clock::get()?;
+        require!(ctx.accounts.orderbook.in_session(clock.unix_timestamp), ErrorCode::MarketClosed);
+        require!(ctx.accounts.trading_gate.trading_enabled, ErrorCode::TradingDisabled);
+
+        let args = ArgBuilder::new()
+            .plaintext_u64(clock.slot)
+            .plaintext_u64(clock.unix_timestamp as u64)
+            .plaintext_i64(ctx.accounts.orderbook.maker_fee_bps as i64)
+            .plaintext_u64(ctx.accounts.orderbook.taker_fee_bps as u64)
+            .plaintext_u8(ctx.accounts.orderbook.fee_rounding)
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u64(ctx.accounts.orderbook.price_scale())
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .plaintext_u64(ctx.accounts.orderbook.activation_delay_slots)
+            .plaintext_u64(ctx.accounts.orderbook.fill_increment)
+            .plaintext_u64(ctx.accounts.orderbook.match_cooldown_slots)
+            .plaintext_u64(MAX_FILLS_PER_SLOT)
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![MatchMarketSweepCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.orderbook.key(),
+                    is_writable: true,
+                }]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "match_market_sweep")]
+    pub fn match_market_sweep_callback(
+        ctx: Context<MatchMarketSweepCallback>,
+        output: SignedComputationOutputs<MatchMarketSweepOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(MatchMarketSweepOutput { field_0, field_1 }) => (field_0, field_1),
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        let computation_offset = ctx.accounts.computation_account.computation_offset;
+        let orderbook = &mut ctx.accounts.orderbook;
+        orderbook.store(o.0.ciphertexts, o.0.nonce, BookOp::MatchMarketSweep, computation_offset);
+
+        emit!(MarketSweptEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            state_hash: ctx.accounts.orderbook.state_hash,
+            fills: o.1.ciphertexts,
+            nonce: o.1.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Get resting buy and sell notional (open interest per side)
+    pub fn get_open_interest(
+        ctx: Context<GetOpenInterest>,
+        computation_offset: u64,
+        pub_key: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let args = ArgBuilder::new()
+            .plaintext_u64(ctx.accounts.orderbook.amount_unit())
+            .plaintext_u64(ctx.accounts.orderbook.price_offset())
+            .x25519_pubkey(pub_key)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.orderbook.nonce)
+            .account(ctx.accounts.orderbook.key(), ORDERBOOK_OFFSET, ORDERBOOK_LEN)
+            .build();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![GetOpenInterestCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[]
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "get_open_interest")]
+    pub fn get_open_interest_callback(
+        ctx: Context<GetOpenInterestCallback>,
+        output: SignedComputationOutputs<GetOpenInterestOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account
+        ) {
+            Ok(GetOpenInterestOutput { field_0 }) => field_0,
+            Err(e) => {
+                msg!("Error: {}", e);
+                return Err(ErrorCode::AbortedComputation.into())
+            },
+        };
+
+        emit!(OpenInterestEvent {
+            computation_offset: ctx.accounts.computation_account.computation_offset,
+            open_interest: o.ciphertexts,
+            nonce: o.nonce.to_le_bytes(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+}
+
+// Errors
+#[error_code]
+pub enum ErrorCode {
+    #[msg("The computation was aborted")]
+    AbortedComputation,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Invalid rounding mode")]
+    InvalidRounding,
+    #[msg("Invalid depth bucket range")]
+    InvalidDepthRange,
+    #[msg("Invalid match mode")]
+    InvalidMatchMode,
+    #[msg("Settlement queue still holds unprocessed records")]
+    SettlementQueueBusy,
+    #[msg("Order id has not been reserved")]
+    OrderIdNotReserved,
+    #[msg("Too many outstanding order id reservations")]
+    ReservationsFull,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+    #[msg("Computation offset was already processed")]
+    ComputationAlreadyProcessed,
+    #[msg("Invalid amount scale")]
+    InvalidAmountScale,
+    #[msg("Order book already has orders")]
+    OrderBookNotEmpty,
+    #[msg("Fee exceeds 10000 bps")]
+    InvalidFeeBps,
+    #[msg("Side must be 0 (buy) or 1 (sell)")]
+    InvalidSide,
+    #[msg("Invalid priority policy")]
+    InvalidPriorityPolicy,
+    #[msg("Invalid spread policy")]
+    InvalidSpreadPolicy,
+    #[msg("Lot size must be non-zero")]
+    InvalidLotSize,
+    #[msg("Matching runs through crank_match while a window is configured")]
+    MatchingWindowActive,
+    #[msg("The matching window has not closed yet")]
+    MatchingWindowOpen,
+    #[msg("No matching window is configured")]
+    NoMatchingWindow,
+    #[msg("Price decimals exceed the maximum")]
+    InvalidPriceDecimals,
+    #[msg("Unknown pair rule bits")]
+    InvalidPairRules,
+    #[msg("Invalid eviction policy")]
+    InvalidEvictionPolicy,
+    #[msg("Invalid trade reporting level")]
+    InvalidReportTrades,
+    #[msg("Capacity threshold exceeds the book size")]
+    InvalidCapacityThreshold,
+    #[msg("Invalid price source policy")]
+    InvalidPriceSourcePolicy,
+    #[msg("Match fraction exceeds 10000 bps")]
+    InvalidMatchFraction,
+    #[msg("Invalid lock policy")]
+    InvalidLockPolicy,
+    #[msg("The match is not the snapshotted one or its snapshot is not replayable")]
+    ReplayStateMismatch,
+    #[msg("Page extends past the end of the book")]
+    InvalidPageStart,
+    #[msg("Market is outside its trading session")]
+    MarketClosed,
+    #[msg("Session must open before it closes")]
+    InvalidSession,
+    #[msg("Trading is disabled by the compliance authority")]
+    TradingDisabled,
+    #[msg("Order book is draining and accepts no new orders")]
+    BookDraining,
+    #[msg("Invalid draining state")]
+    InvalidDrainingState,
+    #[msg("Heartbeat TTL is unset or below the minimum")]
+    InvalidHeartbeatTtl,
+    #[msg("Invalid dust policy")]
+    InvalidDustPolicy,
+    #[msg("Order book already uses the current layout")]
+    LayoutUpToDate,
+    #[msg("Order book layout version is not recognized")]
+    UnknownLayoutVersion,
+    #[msg("Fill increment must be a multiple of the lot size")]
+    InvalidFillIncrement,
+    #[msg("Match cooldown exceeds the maximum")]
+    InvalidMatchCooldown,
+    #[msg("Tick size exceeds the maximum")]
+    InvalidTickSize,
+    #[msg("Cross tolerance exceeds the maximum")]
+    InvalidCrossTolerance,
+    #[msg("Minimum notional exceeds the maximum")]
+    InvalidMinNotional,
+    #[msg("Fee vault can't cover the payout")]
+    FeeVaultInsufficient,
+}
+
+// Whether a fill increment is a whole number of lots (either being 0 is off)
+fn fits_lot_size(fill_increment: u64, lot_size: u64) -> bool {
+    fill_increment == 0 || lot_size == 0 || fill_increment % lot_size == 0
+}
+
+// OrdersMatchedEvent for a match result's ciphertexts. Private markets
+// (report_trades off) leave the encrypted price and size out of the event
+fn orders_matched_event(
+    report_trades: u8,
+    computation_offset: u64,
+    state_hash: u128,
+    result: &[[u8; 32]],
+    nonce: u128,
+    timestamp: i64,
+) -> OrdersMatchedEvent {
+    let report = report_trades == REPORT_TRADES_FULL;
+
+    OrdersMatchedEvent {
+        computation_offset,
+        state_hash,
+        match_result: result[0],
+        match_price: report.then(|| result[1]),
+        match_amount: report.then(|| result[2]),
+        buy_order_id: result[3],
+        sell_order_id: result[4],
+        taker_side: result[5],
+        scaled_price: report.then(|| result[6]),
+        nonce: nonce.to_le_bytes(),
+        timestamp,
+    }
+}
+
+// State accounts
+#[account]
+#[derive(InitSpace)]
+pub struct OraclePrice {
+    pub authority: Pubkey,
+    pub updater: Pubkey,
+    pub price: u64,
+    pub last_update_slot: u64,
+    pub max_staleness_slots: u64,
+    pub bump: u8,
+}
+
+impl OraclePrice {
+    // An oracle that was never updated is always stale
+    pub fn is_fresh(&self, current_slot: u64) -> bool {
+        self.last_update_slot != 0
+            && current_slot.saturating_sub(self.last_update_slot) <= self.max_staleness_slots
+    }
+}
+
+// Encrypted order book (owned by the MXE) plus plaintext bookkeeping
+#[account]
+#[derive(InitSpace)]
+pub struct OrderBookState {
+    pub bump: u8,
+    pub authority: Pubkey,
+    pub nonce: u128,
+    pub layout_version: u16, // ORDERBOOK_LAYOUT_VERSION the account was written under
+    pub orderbook: [[u8; 32]; ORDERBOOK_CIPHERTEXTS],
+    pub next_order_id: u64,
+    pub fill_increment: u64,
+    pub state_hash: u128,
+    pub processed_offsets: [u64; PROCESSED_OFFSETS], // ring of recently applied match offsets
+    pub processed_cursor: u8,
+    pub allowlist_enabled: bool,
+    pub amount_scale: u8,
+    pub max_bid_price: u64, // 0 = no cap
+    pub min_ask_price: u64, // 0 = no floor
+    pub min_rest_slots: u64, // orders can't be cancelled before resting this long
+    pub maker_fee_bps: i16, // negative = maker rebate
+    pub taker_fee_bps: u16,
+    pub lot_size: u64,
+    pub lot_segregation: bool, // match round lots against round lots first
+    pub match_cooldown_slots: u64, // slots a user sits out after a fill (0 = off)
+    pub tick_size: u64, // minimum price improvement that gains queue priority (0 = off)
+    pub match_window_slots: u64, // when non-zero, matching only happens via crank_match
+    pub window_start_slot: u64, // slot the current matching window opened
+    pub price_decimals: u8, // extra decimals carried by MatchResult.scaled_price
+    pub cross_tolerance: u64, // how far a bid must exceed an ask before they match
+    pub pair_rules: u8, // cross-order-type matching rules (PAIR_RULE_* bits)
+    pub eviction_policy: u8, // 0 = reject when full, 1 = evict the worst-priced order
+    pub signed_prices: bool, // prices are offset-binary (see SIGNED_PRICE_OFFSET)
+    pub report_trades: u8, // 0 = private (no price/amount in match events), 1 = report them
+    pub capacity_threshold: u64, // order count that triggers BookNearCapacityEvent (0 = off)
+    pub max_match_fraction_bps: u64, // cap on one fill vs the opposite side's visible volume (0 = off)
+    pub lock_policy: u8, // whether a locked market (bid == ask) matches or holds
+    pub activation_delay_slots: u64, // slots a new order waits before it can match (0 = none)
+    pub keeper_reward: u64, // fee units paid out of collected fees per filling match_orders call
+    pub min_notional: u64, // smallest limit order in quote notional (0 = off)
+    pub last_match_slot: u64, // slot of the last match_orders call that filled
+    pub dust_policy: u8, // whether match_orders cancels dust remainders (DUST_POLICY_CANCEL)
+    pub session_open_ts: i64, // trading session start (0 with session_close_ts 0 = always open)
+    pub session_close_ts: i64, // trading session end, exclusive
+    pub draining: u8, // DRAINING blocks new orders but not matching or cancels
+    pub heartbeat_ttl: u64, // cancel-on-disconnect TTL in seconds (0 = not configured)
+    pub processed_len: u8, // live entries in processed_offsets, up to PROCESSED_OFFSETS
+    pub reserved_ids: [u64; MAX_RESERVED_IDS], // outstanding reserve_order_id ids
+    pub reserved_by: [Pubkey; MAX_RESERVED_IDS], // reserver per entry, default = free
+    pub reserved_slot: [u64; MAX_RESERVED_IDS], // slot each reservation was made
+    pub keeper_rewards_paid: u128, // lifetime keeper rewards taken out of fees
+    pub rounding: u8, // midpoint rounding for limit pairs (0 = floor, 1 = ceil, 2 = toward-maker)
+    pub match_mode: u8, // 0 = price-time pair, 1 = pro-rata at the touch
+    pub fee_rounding: u8, // 0 = floor, 1 = ceil, 2 = nearest
+    pub priority_policy: u8, // pair selection within a class (PRIORITY_PRICE_TIME = price-time)
+    pub max_take_per_maker: u64, // most one match call takes from a resting order (0 = no cap)
+    pub spread_policy: u8, // limit-vs-limit price: 0 = midpoint, 1 = maker-, 2 = taker-favorable
+    pub price_source_policy: u8, // market-order price source (see PRICE_SOURCE_BOOK_FIRST)
+}
+
+// Encrypted records awaiting settlement (collateral release on cancel)
+#[account]
+#[derive(InitSpace)]
+pub struct SettlementQueue {
+    pub bump: u8,
+    pub nonce: u128,
+    pub cancellations: [[u8; 32]; CANCEL_RECORD_CIPHERTEXTS],
+    pub pending: bool,
+}
+
+// Compliance switch for matching, flipped by a dedicated authority
+#[account]
+#[derive(InitSpace)]
+pub struct TradingGate {
+    pub authority: Pubkey,
+    pub compliance_authority: Pubkey,
+    pub trading_enabled: bool,
+    pub bump: u8,
+}
+
+// Inputs of the most recent match_orders call: the book it was queued against
+// and its plaintext parameters, so replay_match can re-run it after the book
+// has moved on. The book ciphertexts must stay right after bump and nonce
+// (MATCH_SNAPSHOT_BOOK_OFFSET)
+#[account]
+#[derive(InitSpace)]
+pub struct MatchSnapshot {
+    pub bump: u8,
+    pub nonce: u128,
+    pub orderbook: [[u8; 32]; ORDERBOOK_CIPHERTEXTS],
+    pub computation_offset: u64,
+    pub state_hash: u128, // book state_hash when the match was queued
+    pub oracle_price: u64,
+    pub oracle_fresh: bool,
+    pub slot: u64,
+    pub rounding: u8,
+    pub match_mode: u8,
+    pub seed: u64,
+    pub fill_increment: u64,
+    pub priority_policy: u8,
+    pub max_take_per_maker: u64,
+    pub spread_policy: u8,
+    pub round_lot_size: u64,
+    pub match_cooldown_slots: u64,
+    pub price_scale: u64,
+    pub cross_tolerance: u64,
+    pub pair_rules: u8,
+    pub price_offset: u64,
+    pub price_source_policy: u8,
+    pub max_match_fraction_bps: u64,
+    pub lock_policy: u8,
+    pub activation_delay_slots: u64,
+    pub replayable: bool, // the match was applied to exactly this book
+}
+
+impl MatchSnapshot {
+    // Record the book and parameters a match is being queued against
+    pub fn capture(
+        &mut self,
+        book: &OrderBookState,
+        computation_offset: u64,
+        oracle_price: u64,
+        oracle_fresh: bool,
+        slot: u64,
+    ) {
+        self.nonce = book.nonce;
+        self.orderbook = book.orderbook;
+        self.computation_offset = computation_offset;
+        self.state_hash = book.state_hash;
+        self.oracle_price = oracle_price;
+        self.oracle_fresh = oracle_fresh;
+        self.slot = slot;
+        self.rounding = book.rounding;
+        self.match_mode = book.match_mode;
+        self.seed = book.remainder_seed();
+        self.fill_increment = book.fill_increment;
+        self.priority_policy = book.priority_policy;
+        self.max_take_per_maker = book.max_take_per_maker;
+        self.spread_policy = book.spread_policy;
+        self.round_lot_size = book.round_lot_size();
+        self.match_cooldown_slots = book.match_cooldown_slots;
+        self.price_scale = book.price_scale();
+        self.cross_tolerance = book.cross_tolerance;
+        self.pair_rules = book.pair_rules;
+        self.price_offset = book.price_offset();
+        self.price_source_policy = book.price_source_policy;
+        self.max_match_fraction_bps = book.max_match_fraction_bps;
+        self.lock_policy = book.lock_policy;
+        self.activation_delay_slots = book.activation_delay_slots;
+        self.replayable = false;
+    }
+
+    // Called by the match callback before it replaces the book. The snapshot
+    // is a faithful replay input only if it belongs to this match and no other
+    // operation changed the book between queueing and applying it
+    pub fn settle(&mut self, computation_offset: u64, pre_match_hash: u128) {
+        self.replayable = self.computation_offset == computation_offset && self.state_hash == pre_match_hash;
+    }
+}
+
+// Lamports that pay out the venue's fees: keeper rewards and fee withdrawals,
+// one lamport per fee unit. The book only accounts for fees; the vault is
+// funded with the real value (fund_fee_vault)
+#[account]
+#[derive(InitSpace)]
+pub struct FeeVault {
+    pub bump: u8,
+}
+
+// Lamports in the fee vault above its rent-exempt minimum
+fn fee_vault_spare(vault: &AccountInfo) -> Result<u64> {
+    let reserve = Rent::get()?.minimum_balance(vault.data_len());
+    Ok(vault.lamports().saturating_sub(reserve))
+}
+
+// Move `amount` lamports out of the fee vault; it is program-owned, so it is
+// debited directly
+fn pay_from_fee_vault(vault: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    require!(fee_vault_spare(vault)? >= amount, ErrorCode::FeeVaultInsufficient);
+    **vault.try_borrow_mut_lamports()? -= amount;
+    **to.try_borrow_mut_lamports()? += amount;
+    Ok(())
+}
+
+// Mutating operations folded into the order book's state hash chain
+#[derive(Clone, Copy)]
+#[repr(u8)]
+pub enum BookOp {
+    AddOrder,
+    MatchOrders,
+    CancelOrder,
+    MatchOrdersBatch,
+    CancelOrdersByIds,
+    InitOrderbook,
+    WarmBook,
+    EmergencyCancelAll,
+    AddToAllowlist,
+    RemoveFromAllowlist,
+    Heartbeat,
+    CancelStaleUsers,
+    ModifyOrder,
+    SnapshotDepth,
+    CrankMatch,
+    SwapPriority,
+    WithdrawFees,
+    SubmitIoc,
+    MatchMarketSweep,
+    MigrateBook,
+}
+
+impl OrderBookState {
+    // Layout version of a raw book account. v0 books have no layout_version
+    // header and are recognised by their size
+    pub fn stored_layout_version(data: &[u8]) -> u16 {
+        if data.len() + 2 == ORDERBOOK_V1_SIZE {
+            0
+        } else {
+            u16::from_le_bytes([data[LAYOUT_VERSION_OFFSET], data[LAYOUT_VERSION_OFFSET + 1]])
+        }
+    }
+
+    // Where the ciphertexts start in a book written before
+    // NOTIFY_KEY_LAYOUT_VERSION (v0 has no layout_version ahead of them)
+    pub fn legacy_ciphertext_offset(from_version: u16) -> usize {
+        if from_version == 0 { LAYOUT_VERSION_OFFSET } else { ORDERBOOK_OFFSET as usize }
+    }
+
+    // Lot size passed to the match circuits (0 when segregation is off)
+    pub fn round_lot_size(&self) -> u64 {
+        if self.lot_segregation { self.lot_size } else { 0 }
+    }
+
+    // Fixed-point scale of MatchResult.scaled_price (10^price_decimals)
+    pub fn price_scale(&self) -> u64 {
+        10u64.pow(self.price_decimals as u32)
+    }
+
+    // Offset added to every price on a signed-price book, 0 otherwise
+    pub fn price_offset(&self) -> u64 {
+        if self.signed_prices { SIGNED_PRICE_OFFSET } else { 0 }
+    }
+
+    // Base units per whole lot (10^amount_scale)
+    pub fn amount_unit(&self) -> u64 {
+        10u64.pow(self.amount_scale as u32)
+    }
+
+    // Whether adds and matching are allowed at `now` (unix timestamp)
+    pub fn in_session(&self, now: i64) -> bool {
+        let always_open = self.session_open_ts == 0 && self.session_close_ts == 0;
+        always_open || (now >= self.session_open_ts && now < self.session_close_ts)
+    }
+
+    // Recently applied match computation offsets (small ring buffer). Only
+    // the last processed_len entries before the cursor are live, so the zeroed
+    // slots of a fresh book don't make offset 0 read as processed.
+    // Arcium already refuses to queue a second computation at a used offset;
+    // the ring guards the other end, a callback for the same computation
+    // delivered again (a retried or duplicated callback transaction), which
+    // would otherwise re-store its stale book over newer state and re-pay fees
+    pub fn is_processed(&self, computation_offset: u64) -> bool {
+        let cursor = self.processed_cursor as usize % PROCESSED_OFFSETS;
+        (1..=self.processed_len as usize).any(|back| {
+            let slot = (cursor + PROCESSED_OFFSETS - back) % PROCESSED_OFFSETS;
+            self.processed_offsets[slot] == computation_offset
+        })
+    }
+
+    pub fn mark_processed(&mut self, computation_offset: u64) {
+        let cursor = self.processed_cursor as usize % PROCESSED_OFFSETS;
+        self.processed_offsets[cursor] = computation_offset;
+        self.processed_cursor = ((cursor + 1) % PROCESSED_OFFSETS) as u8;
+        self.processed_len = (self.processed_len as usize + 1).min(PROCESSED_OFFSETS) as u8;
+    }
+
+    // Pro-rata remainder seed: the low 64 bits of the current state_hash
+    pub fn remainder_seed(&self) -> u64 {
+        self.state_hash as u64
+    }
+
+    // Store a new book ciphertext and chain the op into state_hash:
+    // state_hash = H(prev_hash || op || computation_offset || nonce || ciphertexts)
+    // store() a matching path's output unless this computation offset was
+    // already applied; returns whether it was stored
+    pub fn store_once(
+        &mut self,
+        orderbook: [[u8; 32]; ORDERBOOK_CIPHERTEXTS],
+        nonce: u128,
+        op: BookOp,
+        computation_offset: u64,
+    ) -> bool {
+        if self.is_processed(computation_offset) {
+            return false;
+        }
+        self.mark_processed(computation_offset);
+        self.store(orderbook, nonce, op, computation_offset);
+        true
+    }
+
+    pub fn store(
+        &mut self,
+        orderbook: [[u8; 32]; ORDERBOOK_CIPHERTEXTS],
+        nonce: u128,
+        op: BookOp,
+        computation_offset: u64,
+    ) {
+        self.orderbook = orderbook;
+        self.nonce = nonce;
+
+        let prev_hash = self.state_hash.to_le_bytes();
+        let op_tag = [op as u8];
+        let offset = computation_offset.to_le_bytes();
+        let nonce_bytes = nonce.to_le_bytes();
+        let mut parts: Vec<&[u8]> = vec![&prev_hash, &op_tag, &offset, &nonce_bytes];
+        parts.extend(self.orderbook.iter().map(|c| c.as_slice()));
+
+        let digest = hashv(&parts).to_bytes();
+        let mut head = [0u8; 16];
+        head.copy_from_slice(&digest[..16]);
+        self.state_hash = u128::from_le_bytes(head);
+    }
+
+    pub fn allocate_order_id(&mut self) -> Result<u64> {
+        let order_id = self.next_order_id;
+        self.next_order_id = order_id.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        Ok(order_id)
+    }
+
+    fn reservation_live(&self, index: usize, current_slot: u64) -> bool {
+        self.reserved_by[index] != Pubkey::default()
+            && current_slot < self.reserved_slot[index].saturating_add(RESERVATION_TTL_SLOTS)
+    }
+
+    // Allocate an id and record it against the reserver in a free (or expired)
+    // reservation entry
+    pub fn reserve_order_id(&mut self, reserver: Pubkey, current_slot: u64) -> Result<u64> {
+        let index = (0..MAX_RESERVED_IDS)
+            .find(|&i| !self.reservation_live(i, current_slot))
+            .ok_or(ErrorCode::ReservationsFull)?;
+        let order_id = self.allocate_order_id()?;
+        self.reserved_ids[index] = order_id;
+        self.reserved_by[index] = reserver;
+        self.reserved_slot[index] = current_slot;
+        Ok(order_id)
+    }
+
+    // Consume a live reservation of order_id held by reserver
+    pub fn take_reservation(&mut self, order_id: u64, reserver: Pubkey, current_slot: u64) -> Result<()> {
+        let index = (0..MAX_RESERVED_IDS)
+            .find(|&i| self.reservation_live(i, current_slot) && self.reserved_ids[i] == order_id)
+            .ok_or(ErrorCode::OrderIdNotReserved)?;
+        require!(self.reserved_by[index] == reserver, ErrorCode::Unauthorized);
+        self.reserved_by[index] = Pubkey::default();
+        Ok(())
+    }
+}
+
+// Events
+#[event]
+pub struct OrderIdReservedEvent {
+    pub order_id: u64,
+    pub reserved_by: Pubkey,
+}
+
+#[event]
+pub struct OraclePriceUpdatedEvent {
+    pub price: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct TradingGateUpdatedEvent {
+    pub trading_enabled: bool,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OrderAddedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrdersMatchedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub match_result: [u8; 32],
+    pub match_price: Option<[u8; 32]>, // only when report_trades is on
+    pub match_amount: Option<[u8; 32]>,
+    pub buy_order_id: [u8; 32], // stable ids, encrypted like the rest of the result
+    pub sell_order_id: [u8; 32],
+    pub taker_side: [u8; 32],
+    pub scaled_price: Option<[u8; 32]>, // match price at the book's price_decimals
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderUnfilledEvent {
+    pub computation_offset: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BookNearCapacityEvent {
+    pub computation_offset: u64,
+    pub threshold: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderEvictedEvent {
+    pub computation_offset: u64,
+    pub record: [[u8; 32]; CANCEL_RECORD_FIELDS], // encrypted for settlement
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderCancelledEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct QueuePositionEvent {
+    pub computation_offset: u64,
+    pub position: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderStatusEvent {
+    pub computation_offset: u64,
+    pub status: [[u8; 32]; 7],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrdersBatchMatchedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub match_results: Option<Vec<[u8; 32]>>, // only when report_trades is on
+    pub nonce: [u8; 16],
+    pub net_settlements: Vec<[u8; 32]>, // one netted entry per order touched by the batch
+    pub net_nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct KeeperRewardedEvent {
+    pub computation_offset: u64,
+    pub keeper: Pubkey,
+    pub amount: u64, // fee units, paid out by the settlement layer
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct KeeperRewardUnpaidEvent {
+    pub computation_offset: u64,
+    pub keeper: Pubkey,
+    pub amount: u64, // reward the call earned but net fees couldn't cover
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BookStalenessEvent {
+    pub last_match_slot: u64,
+    pub staleness_slots: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FillNoticeEvent {
+    pub computation_offset: u64,
+    pub notice: [[u8; 32]; FILL_NOTICE_FIELDS], // all-zero fill for an order without a notify_key
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BookMigratedEvent {
+    pub from_version: u16,
+    pub to_version: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BatchMatchSummaryEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub summary: Option<[[u8; 32]; 3]>, // fills, volume, vwap; only when report_trades is on
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BookCrossedEvent {
+    pub computation_offset: u64,
+    pub crossed: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrdersCancelledEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderBookInitializedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderBookWarmedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepthRangeEvent {
+    pub computation_offset: u64,
+    pub depth: Vec<[u8; 32]>,
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyCancelAllEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EffectiveSpreadEvent {
+    pub computation_offset: u64,
+    pub spread_bps: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct QuoteDepthEvent {
+    pub computation_offset: u64,
+    pub depth: [[u8; 32]; DEPTH_BUCKETS as usize],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AllowlistUpdatedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LastTradeEvent {
+    pub computation_offset: u64,
+    pub last_trade: [[u8; 32]; 2],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FragmentationEvent {
+    pub computation_offset: u64,
+    pub fragmentation: [[u8; 32]; 2],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PriceAtVolumeEvent {
+    pub computation_offset: u64,
+    pub price: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FillLikelihoodEvent {
+    pub computation_offset: u64,
+    pub ratio_bps: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActiveDistributionEvent {
+    pub computation_offset: u64,
+    pub counts: [[u8; 32]; DEPTH_BUCKETS as usize],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BookDiffEvent {
+    pub computation_offset: u64,
+    pub diff: [[u8; 32]; BOOK_DIFF_CIPHERTEXTS],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TimeRankEvent {
+    pub computation_offset: u64,
+    pub rank: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct HeartbeatEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub recorded: [u8; 32], // encrypted to the caller: 1 if enrolled, 0 if the table was full
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StaleUsersCancelledEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ImbalanceRatioEvent {
+    pub computation_offset: u64,
+    pub ratio_bps: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeesUpdatedEvent {
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserVolumeEvent {
+    pub computation_offset: u64,
+    pub volume: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgeHistogramEvent {
+    pub computation_offset: u64,
+    pub counts: [[u8; 32]; AGE_BUCKETS],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderModifiedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepthSnapshotEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub snapshot: [[u8; 32]; DEPTH_BUCKETS as usize + 1],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepthDeltaEvent {
+    pub computation_offset: u64,
+    pub delta: [[u8; 32]; DEPTH_DELTA_CIPHERTEXTS],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CrankMatchedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub summary: Option<[[u8; 32]; 2]>, // fills, volume; only when report_trades is on
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InvariantsCheckedEvent {
+    pub computation_offset: u64,
+    pub violations: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserFillsEvent {
+    pub computation_offset: u64,
+    pub fills: [[u8; 32]; MAX_USER_FILLS * MATCH_RESULT_FIELDS],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SortedLadderEvent {
+    pub computation_offset: u64,
+    pub ladder: [[u8; 32]; 4 * LADDER_LEVELS],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrdersModifiedEvent {
+    pub computation_offset: u64,
+    pub updates: [[u8; 32]; MAX_MODIFIED_ORDERS * ORDER_UPDATE_FIELDS],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Volume24hEvent {
+    pub computation_offset: u64,
+    pub volume: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WorstOrdersEvent {
+    pub computation_offset: u64,
+    pub worst: [[u8; 32]; 4],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserAvgPriceEvent {
+    pub computation_offset: u64,
+    pub avg_price: [u8; 32],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PrioritySwappedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeAccountingEvent {
+    pub computation_offset: u64,
+    pub fees_collected: u128,
+    pub rebates_owed: u128,
+    pub fees_net: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeesWithdrawnEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub destination: Pubkey,
+    pub amount: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CumulativeDepthEvent {
+    pub computation_offset: u64,
+    pub depth: [[u8; 32]; DEPTH_BUCKETS as usize],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MatchReplayedEvent {
+    pub computation_offset: u64,
+    pub reproducible: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BookPageEvent {
+    pub computation_offset: u64,
+    pub orders: [[u8; 32]; BOOK_PAGE * ORDER_FIELDS],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepthCountsEvent {
+    pub computation_offset: u64,
+    pub depth: [[u8; 32]; 2 * DEPTH_BUCKETS as usize],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct IocExecutedEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub report: [[u8; 32]; 3],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarketSweptEvent {
+    pub computation_offset: u64,
+    pub state_hash: u128,
+    pub fills: [[u8; 32]; MAX_IOC_FILLS * MATCH_RESULT_FIELDS], // encrypted to the sweep caller's key
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OpenInterestEvent {
+    pub computation_offset: u64,
+    pub open_interest: [[u8; 32]; 2],
+    pub nonce: [u8; 16],
+    pub timestamp: i64,
+}
+
+// Account structures (auto-generated by Arcium)
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitAddOrderCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitMatchOrdersCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitCancelOrderCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetOrderbookDepthCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetQueuePositionCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetOrderStatusCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitMatchOrdersBatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitIsCrossedCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitCancelOrdersByIdsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitInitOrderbookCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitWarmBookCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitMigrateBookCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetDepthRangeCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitEmergencyCancelAllCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetEffectiveSpreadBpsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetOrderbookDepthQuoteCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitAddToAllowlistCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitRemoveFromAllowlistCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetLastTradeCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetFragmentationCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetPriceAtVolumeCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetFillLikelihoodCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetActiveDistributionCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetBookDiffCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetTimeRankCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitHeartbeatCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitCancelStaleUsersCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetImbalanceRatioCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetUserVolumeCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetAgeHistogramCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitModifyOrderCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitSnapshotDepthCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetDepthDeltaCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitCrankMatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitCheckInvariantsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetUserFillsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetSortedLadderCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetOrdersModifiedSinceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetVolume24hCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetWorstOrdersCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetUserAvgPriceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitSwapPriorityCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetFeeAccountingCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitWithdrawFeesCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetDepthCumulativeCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitReplayMatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetBookPageCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetDepthCountsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitSubmitIocCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitMatchMarketSweepCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitGetOpenInterestCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitOraclePrice<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OraclePrice::INIT_SPACE,
+        seeds = [b"oracle_price"],
+        bump,
+    )]
+    pub oracle_price: Account<'info, OraclePrice>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOraclePrice<'info> {
+    pub updater: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"oracle_price"],
+        bump = oracle_price.bump,
+        has_one = updater @ ErrorCode::Unauthorized,
+    )]
+    pub oracle_price: Account<'info, OraclePrice>,
+}
+
+#[derive(Accounts)]
+pub struct InitTradingGate<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TradingGate::INIT_SPACE,
+        seeds = [b"trading_gate"],
+        bump,
+    )]
+    pub trading_gate: Account<'info, TradingGate>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitMatchSnapshot<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + MatchSnapshot::INIT_SPACE,
+        seeds = [b"match_snapshot"],
+        bump,
+    )]
+    pub match_snapshot: Box<Account<'info, MatchSnapshot>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitFeeVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + FeeVault::INIT_SPACE,
+        seeds = [b"fee_vault"],
+        bump,
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundFeeVault<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    #[account(mut, seeds = [b"fee_vault"], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetTradingEnabled<'info> {
+    pub compliance_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"trading_gate"],
+        bump = trading_gate.bump,
+        has_one = compliance_authority @ ErrorCode::Unauthorized,
+    )]
+    pub trading_gate: Account<'info, TradingGate>,
+}
+
+#[derive(Accounts)]
+pub struct InitSettlementQueue<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + SettlementQueue::INIT_SPACE,
+        seeds = [b"settlement_queue"],
+        bump,
+    )]
+    pub settlement_queue: Box<Account<'info, SettlementQueue>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct MigrateBook<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: an old-layout book doesn't deserialize as OrderBookState;
+    /// migrate_book checks the discriminator and authority by hand
+    #[account(mut, seeds = [b"orderbook"], bump, owner = crate::ID)]
+    pub orderbook: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateBookCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    /// CHECK: still at its old size until this callback resizes it
+    #[account(mut, seeds = [b"orderbook"], bump, owner = crate::ID)]
+    pub orderbook: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOrderBookConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+pub struct GetBookStaleness<'info> {
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+pub struct ReserveOrderId<'info> {
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct AddOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddOrderCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct MatchOrders<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    #[account(seeds = [b"oracle_price"], bump = oracle_price.bump)]
+    pub oracle_price: Account<'info, OraclePrice>,
+    #[account(seeds = [b"trading_gate"], bump = trading_gate.bump)]
+    pub trading_gate: Account<'info, TradingGate>,
+    #[account(mut, seeds = [b"match_snapshot"], bump = match_snapshot.bump)]
+    pub match_snapshot: Box<Account<'info, MatchSnapshot>>,
+    #[account(seeds = [b"fee_vault"], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MatchOrdersCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    /// CHECK: the match_orders payer, credited with the keeper reward
+    #[account(mut)]
+    pub keeper: AccountInfo<'info>,
+    #[account(mut, seeds = [b"match_snapshot"], bump = match_snapshot.bump)]
+    pub match_snapshot: Box<Account<'info, MatchSnapshot>>,
+    #[account(mut, seeds = [b"fee_vault"], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CancelOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOrderCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetQueuePosition<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetQueuePositionCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetOrderStatus<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetOrderStatusCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct MatchOrdersBatch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    #[account(seeds = [b"oracle_price"], bump = oracle_price.bump)]
+    pub oracle_price: Account<'info, OraclePrice>,
+    #[account(seeds = [b"trading_gate"], bump = trading_gate.bump)]
+    pub trading_gate: Account<'info, TradingGate>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MatchOrdersBatchCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct IsCrossed<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IsCrossedCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CancelOrdersByIds<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOrdersByIdsCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitOrderbook<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OrderBookState::INIT_SPACE,
+        seeds = [b"orderbook"],
+        bump,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitOrderbookCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct WarmBook<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WarmBookCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetDepthRange<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetDepthRangeCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct EmergencyCancelAll<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"settlement_queue"], bump = settlement_queue.bump)]
+    pub settlement_queue: Box<Account<'info, SettlementQueue>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyCancelAllCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"settlement_queue"], bump = settlement_queue.bump)]
+    pub settlement_queue: Box<Account<'info, SettlementQueue>>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetEffectiveSpreadBps<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetEffectiveSpreadBpsCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetOrderbookDepthQuote<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetOrderbookDepthQuoteCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct AddToAllowlist<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddToAllowlistCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RemoveFromAllowlist<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFromAllowlistCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetLastTrade<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetLastTradeCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetFragmentation<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetFragmentationCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetPriceAtVolume<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetPriceAtVolumeCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetFillLikelihood<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetFillLikelihoodCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetActiveDistribution<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetActiveDistributionCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetBookDiff<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetBookDiffCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetTimeRank<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetTimeRankCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct Heartbeat<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct HeartbeatCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CancelStaleUsers<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelStaleUsersCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetImbalanceRatio<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetImbalanceRatioCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetUserVolume<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetUserVolumeCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetAgeHistogram<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetAgeHistogramCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ModifyOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyOrderCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct SnapshotDepth<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SnapshotDepthCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetDepthDelta<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetDepthDeltaCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CrankMatch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    #[account(seeds = [b"oracle_price"], bump = oracle_price.bump)]
+    pub oracle_price: Account<'info, OraclePrice>,
+    #[account(seeds = [b"trading_gate"], bump = trading_gate.bump)]
+    pub trading_gate: Account<'info, TradingGate>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CrankMatchCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CheckInvariants<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CheckInvariantsCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetUserFills<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetUserFillsCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetSortedLadder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetSortedLadderCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetOrdersModifiedSince<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
 
-        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+#[derive(Accounts)]
+pub struct GetOrdersModifiedSinceCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
 
-        queue_computation(
-            ctx.accounts,
-            computation_offset,
-            args,
-            None,
-            vec![MatchOrdersCallback::callback_ix(
-                computation_offset,
-                &ctx.accounts.mxe_account,
-                &[]
-            )?],
-            1,
-            0,
-        )?;
-        Ok(())
-    }
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetVolume24h<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
 
-    #[arcium_callback(encrypted_ix = "match_orders")]
-    pub fn match_orders_callback(
-        ctx: Context<MatchOrdersCallback>,
-        output: SignedComputationOutputs<MatchOrdersOutput>,
-    ) -> Result<()> {
-        let o = match output.verify_output(
-            &ctx.accounts.cluster_account,
-            &ctx.accounts.computation_account
-        ) {
-            Ok(MatchOrdersOutput { field_0, field_1 }) => (field_0, field_1),
-            Err(e) => {
-                msg!("Error: {}", e);
-                return Err(ErrorCode::AbortedComputation.into())
-            },
-        };
+#[derive(Accounts)]
+pub struct GetVolume24hCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
 
-        emit!(OrdersMatchedEvent {
-            computation_offset: ctx.accounts.computation_account.computation_offset,
-            match_result: o.1.ciphertexts[0],
-            nonce: o.1.nonce.to_le_bytes(),
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-        Ok(())
-    }
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetWorstOrders<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
 
-    // Cancel order
-    pub fn cancel_order(
-        ctx: Context<CancelOrder>,
-        computation_offset: u64,
-        order_id: u64,
-        user_id: [u8; 32],
-        pub_key: [u8; 32],
-        nonce: u128,
-    ) -> Result<()> {
-        let args = ArgBuilder::new()
-            .plaintext_u64(order_id)
-            .x25519_pubkey(pub_key)
-            .plaintext_u128(nonce)
-            .encrypted_u128(user_id)
-            .build();
+#[derive(Accounts)]
+pub struct GetWorstOrdersCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
 
-        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetUserAvgPrice<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub system_program: Program<'info, System>,
+}
 
-        queue_computation(
-            ctx.accounts,
-            computation_offset,
-            args,
-            None,
-            vec![CancelOrderCallback::callback_ix(
-                computation_offset,
-                &ctx.accounts.mxe_account,
-                &[]
-            )?],
-            1,
-            0,
-        )?;
-        Ok(())
-    }
+#[derive(Accounts)]
+pub struct GetUserAvgPriceCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
 
-    #[arcium_callback(encrypted_ix = "cancel_order")]
-    pub fn cancel_order_callback(
-        ctx: Context<CancelOrderCallback>,
-        output: SignedComputationOutputs<CancelOrderOutput>,
-    ) -> Result<()> {
-        let _o = match output.verify_output(
-            &ctx.accounts.cluster_account,
-            &ctx.accounts.computation_account
-        ) {
-            Ok(CancelOrderOutput { field_0 }) => field_0,
-            Err(e) => {
-                msg!("Error: {}", e);
-                return Err(ErrorCode::AbortedComputation.into())
-            },
-        };
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct SwapPriority<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
-        emit!(OrderCancelledEvent {
-            computation_offset: ctx.accounts.computation_account.computation_offset,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-        Ok(())
-    }
+#[derive(Accounts)]
+pub struct SwapPriorityCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
 }
 
-// Events
-#[event]
-pub struct OrderAddedEvent {
-    pub computation_offset: u64,
-    pub timestamp: i64,
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct GetFeeAccounting<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
-#[event]
-pub struct OrdersMatchedEvent {
-    pub computation_offset: u64,
-    pub match_result: [u8; 32],
-    pub nonce: [u8; 16],
-    pub timestamp: i64,
+#[derive(Accounts)]
+pub struct GetFeeAccountingCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
 }
 
-#[event]
-pub struct OrderCancelledEvent {
-    pub computation_offset: u64,
-    pub timestamp: i64,
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct WithdrawFees<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"fee_vault"], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,
+    /// CHECK: any account the authority designates to receive the fees
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFeesCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    #[account(mut, seeds = [b"fee_vault"], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,
+    /// CHECK: the destination withdraw_fees was queued with
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
 }
 
-// Account structures (auto-generated by Arcium)
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct InitAddOrderCompDef<'info> {
+pub struct GetDepthCumulative<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct GetDepthCumulativeCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct InitMatchOrdersCompDef<'info> {
+pub struct ReplayMatch<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(seeds = [b"match_snapshot"], bump = match_snapshot.bump)]
+    pub match_snapshot: Box<Account<'info, MatchSnapshot>>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct ReplayMatchCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct InitCancelOrderCompDef<'info> {
+pub struct GetBookPage<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"orderbook"],
+        bump = orderbook.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct GetBookPageCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct InitGetOrderbookDepthCompDef<'info> {
+pub struct GetDepthCounts<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct GetDepthCountsCallback<'info> {
+    pub cluster_account: AccountInfo<'info>,
+    pub computation_account: AccountInfo<'info>,
+    pub mxe_account: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct AddOrder<'info> {
+pub struct SubmitIoc<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    #[account(seeds = [b"trading_gate"], bump = trading_gate.bump)]
+    pub trading_gate: Account<'info, TradingGate>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct AddOrderCallback<'info> {
+pub struct SubmitIocCallback<'info> {
     pub cluster_account: AccountInfo<'info>,
     pub computation_account: AccountInfo<'info>,
     pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
 }
 
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct MatchOrders<'info> {
+pub struct MatchMarketSweep<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
+    #[account(seeds = [b"trading_gate"], bump = trading_gate.bump)]
+    pub trading_gate: Account<'info, TradingGate>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct MatchOrdersCallback<'info> {
+pub struct MatchMarketSweepCallback<'info> {
     pub cluster_account: AccountInfo<'info>,
     pub computation_account: AccountInfo<'info>,
     pub mxe_account: AccountInfo<'info>,
+    #[account(mut, seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
 }
 
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct CancelOrder<'info> {
+pub struct GetOpenInterest<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(seeds = [b"orderbook"], bump = orderbook.bump)]
+    pub orderbook: Box<Account<'info, OrderBookState>>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CancelOrderCallback<'info> {
+pub struct GetOpenInterestCallback<'info> {
     pub cluster_account: AccountInfo<'info>,
     pub computation_account: AccountInfo<'info>,
     pub mxe_account: AccountInfo<'info>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A book as init_orderbook leaves it: every plaintext field zeroed
+    fn empty_book() -> Box<OrderBookState> {
+        let zeroed = vec![0u8; OrderBookState::INIT_SPACE];
+        Box::new(OrderBookState::deserialize(&mut zeroed.as_slice()).unwrap())
+    }
+
+    #[test]
+    fn reservation_is_taken_once_by_its_reserver() {
+        let mut book = empty_book();
+        let reserver = Pubkey::new_unique();
+        let order_id = book.reserve_order_id(reserver, 10).unwrap();
+
+        assert_eq!(
+            book.take_reservation(order_id, Pubkey::new_unique(), 11).unwrap_err(),
+            ErrorCode::Unauthorized.into()
+        );
+        book.take_reservation(order_id, reserver, 11).unwrap();
+        assert_eq!(
+            book.take_reservation(order_id, reserver, 12).unwrap_err(),
+            ErrorCode::OrderIdNotReserved.into()
+        );
+    }
+
+    #[test]
+    fn expired_reservations_are_rejected_and_reused() {
+        let mut book = empty_book();
+        let reserver = Pubkey::new_unique();
+        let expired = book.reserve_order_id(reserver, 10).unwrap();
+        for _ in 1..MAX_RESERVED_IDS {
+            book.reserve_order_id(reserver, 10).unwrap();
+        }
+        assert_eq!(
+            book.reserve_order_id(reserver, 11).unwrap_err(),
+            ErrorCode::ReservationsFull.into()
+        );
+
+        let later = 10 + RESERVATION_TTL_SLOTS;
+        assert_eq!(
+            book.take_reservation(expired, reserver, later).unwrap_err(),
+            ErrorCode::OrderIdNotReserved.into()
+        );
+        assert!(book.reserve_order_id(reserver, later).is_ok());
+    }
+
+    #[test]
+    fn fill_increment_must_be_whole_lots() {
+        assert!(fits_lot_size(30, 10));
+        assert!(!fits_lot_size(25, 10));
+        assert!(fits_lot_size(0, 10));
+        assert!(fits_lot_size(25, 0));
+    }
+
+    #[test]
+    fn remainder_seed_follows_the_book_state() {
+        let mut book = empty_book();
+        let mut twin = empty_book();
+        assert_eq!(book.remainder_seed(), twin.remainder_seed());
+
+        // The seed moves only when the book's state does, and the same history
+        // yields the same seed whoever replays it
+        let ciphertexts = [[1u8; 32]; ORDERBOOK_CIPHERTEXTS];
+        book.store(ciphertexts, 5, BookOp::AddOrder, 42);
+        twin.store(ciphertexts, 5, BookOp::AddOrder, 42);
+        assert_eq!(book.remainder_seed(), twin.remainder_seed());
+        assert_ne!(book.remainder_seed(), empty_book().remainder_seed());
+    }
+
+    #[test]
+    fn reordering_two_ops_changes_the_state_hash() {
+        // Both ops leave the same ciphertext, so only the chain tells them apart
+        let ciphertexts = [[1u8; 32]; ORDERBOOK_CIPHERTEXTS];
+        let replay = |ops: [(BookOp, u64); 2]| {
+            let mut book = empty_book();
+            for (op, offset) in ops {
+                book.store(ciphertexts, 5, op, offset);
+            }
+            book.state_hash
+        };
+
+        let in_order = replay([(BookOp::AddOrder, 1), (BookOp::CancelOrder, 2)]);
+        assert_eq!(in_order, replay([(BookOp::AddOrder, 1), (BookOp::CancelOrder, 2)]));
+        assert_ne!(in_order, replay([(BookOp::CancelOrder, 2), (BookOp::AddOrder, 1)]));
+    }
+
+    #[test]
+    fn match_events_carry_price_and_size_only_when_reporting() {
+        let result: Vec<[u8; 32]> = (0..8u8).map(|k| [k; 32]).collect();
+
+        let private = orders_matched_event(0, 42, 7, &result, 9, 100);
+        assert_eq!((private.match_price, private.match_amount, private.scaled_price), (None, None, None));
+        assert_eq!((private.match_result, private.buy_order_id, private.taker_side), ([0; 32], [3; 32], [5; 32]));
+
+        let full = orders_matched_event(REPORT_TRADES_FULL, 42, 7, &result, 9, 100);
+        assert_eq!(full.match_price, Some([1; 32]));
+        assert_eq!(full.match_amount, Some([2; 32]));
+        assert_eq!(full.scaled_price, Some([6; 32]));
+        assert_eq!(full.nonce, private.nonce);
+    }
+
+    // A snapshot as init_match_snapshot leaves it
+    fn empty_snapshot() -> Box<MatchSnapshot> {
+        let zeroed = vec![0u8; MatchSnapshot::INIT_SPACE];
+        Box::new(MatchSnapshot::deserialize(&mut zeroed.as_slice()).unwrap())
+    }
+
+    #[test]
+    fn snapshot_replays_a_match_applied_to_its_book() {
+        let mut book = empty_book();
+        book.store([[3u8; 32]; ORDERBOOK_CIPHERTEXTS], 9, BookOp::AddOrder, 1);
+        let mut snapshot = empty_snapshot();
+        snapshot.capture(&book, 42, 100, true, 7);
+        assert!(!snapshot.replayable);
+        assert_eq!(snapshot.orderbook, book.orderbook);
+        assert_eq!(snapshot.seed, book.remainder_seed());
+
+        snapshot.settle(42, book.state_hash);
+        assert!(snapshot.replayable);
+
+        // Later operations move the book on without touching the snapshot
+        book.store([[4u8; 32]; ORDERBOOK_CIPHERTEXTS], 10, BookOp::MatchOrders, 42);
+        assert_eq!(snapshot.orderbook, [[3u8; 32]; ORDERBOOK_CIPHERTEXTS]);
+    }
+
+    #[test]
+    fn snapshot_is_not_replayable_when_the_book_changed_before_the_match() {
+        let mut book = empty_book();
+        let mut snapshot = empty_snapshot();
+        snapshot.capture(&book, 42, 100, true, 7);
+
+        // An add landed between queueing and applying the match
+        book.store([[5u8; 32]; ORDERBOOK_CIPHERTEXTS], 11, BookOp::AddOrder, 43);
+        snapshot.settle(42, book.state_hash);
+        assert!(!snapshot.replayable);
+
+        // A second match queued meanwhile owns the snapshot now
+        snapshot.capture(&book, 44, 100, true, 8);
+        snapshot.settle(42, book.state_hash);
+        assert!(!snapshot.replayable);
+    }
+
+    fn oracle(last_update_slot: u64, max_staleness_slots: u64) -> OraclePrice {
+        OraclePrice {
+            authority: Pubkey::new_unique(),
+            updater: Pubkey::new_unique(),
+            price: 100,
+            last_update_slot,
+            max_staleness_slots,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn oracle_is_stale_until_updated_and_after_max_staleness() {
+        // As init_oracle_price leaves it
+        assert!(!oracle(0, 50).is_fresh(10));
+
+        assert!(oracle(100, 50).is_fresh(100));
+        assert!(oracle(100, 50).is_fresh(150));
+        assert!(!oracle(100, 50).is_fresh(151));
+    }
+
+    #[test]
+    fn fresh_book_has_processed_nothing() {
+        let book = empty_book();
+        assert!(!book.is_processed(0));
+    }
+
+    #[test]
+    fn processed_offsets_age_out_of_the_ring() {
+        let mut book = empty_book();
+        book.mark_processed(7);
+        assert!(book.is_processed(7));
+        assert!(!book.is_processed(0));
+
+        for offset in 100..100 + PROCESSED_OFFSETS as u64 {
+            book.mark_processed(offset);
+        }
+        assert!(!book.is_processed(7));
+        assert!(book.is_processed(100));
+    }
+
+    #[test]
+    fn redelivered_match_output_is_ignored() {
+        let mut book = empty_book();
+        let output = [[1u8; 32]; ORDERBOOK_CIPHERTEXTS];
+        assert!(book.store_once(output, 5, BookOp::MatchOrders, 42));
+        let (hash, nonce) = (book.state_hash, book.nonce);
+
+        // The same delivery again, or any other output claiming offset 42,
+        // leaves the book as the first one did
+        assert!(!book.store_once(output, 5, BookOp::MatchOrders, 42));
+        assert!(!book.store_once([[2u8; 32]; ORDERBOOK_CIPHERTEXTS], 6, BookOp::MatchOrders, 42));
+        assert_eq!((book.state_hash, book.nonce), (hash, nonce));
+        assert_eq!(book.orderbook, output);
+
+        // A different offset still applies
+        assert!(book.store_once(output, 7, BookOp::MatchOrders, 43));
+        assert_ne!(book.state_hash, hash);
+    }
+}
@@ -0,0 +1,93 @@
+import * as anchor from '@coral-xyz/anchor';
+import { expectError, program, provider, setupMarket } from './helpers';
+
+function asAuthority() {
+  return { authority: provider.wallet.publicKey };
+}
+
+describe('config setter bounds', () => {
+  before(setupMarket);
+
+  describe('set_fill_increment', () => {
+    before(async () => {
+      await program.methods
+        .setLotSegregation(new anchor.BN(10), true)
+        .accountsPartial(asAuthority())
+        .rpc({ commitment: 'confirmed' });
+    });
+
+    after(async () => {
+      await program.methods
+        .setFillIncrement(new anchor.BN(0))
+        .accountsPartial(asAuthority())
+        .rpc({ commitment: 'confirmed' });
+      await program.methods
+        .setLotSegregation(new anchor.BN(0), false)
+        .accountsPartial(asAuthority())
+        .rpc({ commitment: 'confirmed' });
+    });
+
+    it('rejects an increment that is not a whole number of lots', async () => {
+      await expectError(
+        program.methods.setFillIncrement(new anchor.BN(25)).accountsPartial(asAuthority()).rpc(),
+        'InvalidFillIncrement'
+      );
+    });
+
+    it('keeps the lot size consistent with the increment', async () => {
+      await program.methods
+        .setFillIncrement(new anchor.BN(30))
+        .accountsPartial(asAuthority())
+        .rpc({ commitment: 'confirmed' });
+      await expectError(
+        program.methods.setLotSegregation(new anchor.BN(20), true).accountsPartial(asAuthority()).rpc(),
+        'InvalidFillIncrement'
+      );
+    });
+  });
+
+  describe('set_match_cooldown', () => {
+    it('rejects a cooldown longer than a day of slots', async () => {
+      await expectError(
+        program.methods.setMatchCooldown(new anchor.BN(216_001)).accountsPartial(asAuthority()).rpc(),
+        'InvalidMatchCooldown'
+      );
+    });
+  });
+
+  describe('set_tick_size', () => {
+    it('rejects a tick wider than 2^32', async () => {
+      await expectError(
+        program.methods
+          .setTickSize(new anchor.BN(2).pow(new anchor.BN(32)).addn(1))
+          .accountsPartial(asAuthority())
+          .rpc(),
+        'InvalidTickSize'
+      );
+    });
+  });
+
+  describe('set_cross_tolerance', () => {
+    it('rejects a tolerance wider than 2^32', async () => {
+      await expectError(
+        program.methods
+          .setCrossTolerance(new anchor.BN(2).pow(new anchor.BN(32)).addn(1))
+          .accountsPartial(asAuthority())
+          .rpc(),
+        'InvalidCrossTolerance'
+      );
+    });
+  });
+
+  describe('set_min_notional', () => {
+    it('rejects a floor above 2^48', async () => {
+      await expectError(
+        program.methods
+          .setMinNotional(new anchor.BN(2).pow(new anchor.BN(48)).addn(1))
+          .accountsPartial(asAuthority())
+          .rpc(),
+        'InvalidMinNotional'
+      );
+    });
+  });
+});
@@ -0,0 +1,114 @@
+import * as anchor from '@coral-xyz/anchor';
+import { expect } from 'chai';
+import {
+  addBlankOrder,
+  arciumAccounts,
+  blank,
+  cancelOrder,
+  expectError,
+  modifyOrder,
+  newComputationOffset,
+  orderStatus,
+  outsider,
+  placeOrder,
+  program,
+  provider,
+  runMatch,
+  setupMarket,
+} from './helpers';
+
+function setDraining(draining: number) {
+  return program.methods
+    .setDraining(draining)
+    .accountsPartial({ authority: provider.wallet.publicKey })
+    .rpc({ commitment: 'confirmed' });
+}
+
+describe('draining', () => {
+  before(setupMarket);
+
+  it('rejects an unknown state', async () => {
+    await expectError(setDraining(2), 'InvalidDrainingState');
+  });
+
+  it('is admin only', async () => {
+    await expectError(
+      program.methods
+        .setDraining(1)
+        .accountsPartial({ authority: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      'Unauthorized'
+    );
+  });
+
+  describe('while draining', () => {
+    let crossingBid: anchor.BN;
+    let crossingAsk: anchor.BN;
+    let restingBid: anchor.BN;
+
+    // Orders placed before draining starts
+    before(async () => {
+      crossingBid = await placeOrder({ price: 100, amount: 10, side: 0, userId: 401 });
+      crossingAsk = await placeOrder({ price: 100, amount: 10, side: 1, userId: 402 });
+      restingBid = await placeOrder({ price: 80, amount: 10, side: 0, userId: 403 });
+      await setDraining(1);
+    });
+    after(() => setDraining(0));
+
+    it('rejects add_order', async () => {
+      await expectError(addBlankOrder(), 'BookDraining');
+    });
+
+    it('rejects submit_ioc', async () => {
+      const computationOffset = newComputationOffset();
+      await expectError(
+        program.methods
+          .submitIoc(
+            computationOffset,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            new anchor.BN(0)
+          )
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            ...arciumAccounts('submit_ioc', computationOffset),
+          })
+          .rpc(),
+        'BookDraining'
+      );
+    });
+
+    it('still matches resting orders', async () => {
+      await runMatch();
+      expect((await orderStatus(crossingBid, 401)).active).to.equal(false);
+      expect((await orderStatus(crossingAsk, 402)).active).to.equal(false);
+      expect((await orderStatus(restingBid, 403)).active).to.equal(true);
+    });
+
+    it('ignores amendments that grow an order or reprice it toward the touch', async () => {
+      await modifyOrder(restingBid, 403, 80, 12);
+      await modifyOrder(restingBid, 403, 85, 10);
+      expect(await orderStatus(restingBid, 403)).to.deep.equal({ active: true, price: 80, amount: 10 });
+    });
+
+    it('applies amendments that reduce an order', async () => {
+      await modifyOrder(restingBid, 403, 75, 6);
+      expect(await orderStatus(restingBid, 403)).to.deep.equal({ active: true, price: 75, amount: 6 });
+    });
+
+    it('still cancels', async () => {
+      await cancelOrder(restingBid, 403);
+      expect((await orderStatus(restingBid, 403)).active).to.equal(false);
+    });
+  });
+});
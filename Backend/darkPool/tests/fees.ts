@@ -0,0 +1,115 @@
+import * as anchor from '@coral-xyz/anchor';
+import { awaitComputationFinalization } from '@arcium-hq/client';
+import { expect } from 'chai';
+import {
+  arciumAccounts,
+  expectError,
+  feeVaultPda,
+  newComputationOffset,
+  outsider,
+  placeOrder,
+  program,
+  provider,
+  setupMarket,
+} from './helpers';
+
+function asAuthority() {
+  return { authority: provider.wallet.publicKey };
+}
+
+// Receives withdrawals; funded up front so small credits keep it rent-exempt
+const destination = anchor.web3.Keypair.generate();
+
+async function withdrawFees(): Promise<void> {
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .withdrawFees(computationOffset)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      authority: provider.wallet.publicKey,
+      destination: destination.publicKey,
+      ...arciumAccounts('withdraw_fees', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+}
+
+// Cross a 10 @ 100 buy with a 10 @ 100 sell and match them
+async function fillOnce(userId: number): Promise<void> {
+  await placeOrder({ price: 100, amount: 10, side: 0, userId });
+  await placeOrder({ price: 100, amount: 10, side: 1, userId: userId + 1 });
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .matchOrders(computationOffset)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('match_orders', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+}
+
+function balance(key: anchor.web3.PublicKey): Promise<number> {
+  return provider.connection.getBalance(key, 'confirmed');
+}
+
+describe('withdraw_fees', () => {
+  before(async () => {
+    await setupMarket();
+    const sig = await provider.connection.requestAirdrop(destination.publicKey, anchor.web3.LAMPORTS_PER_SOL);
+    await provider.connection.confirmTransaction(sig, 'confirmed');
+    await program.methods.updateFees(0, 100).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    // Start from a book with no fees left over from other suites
+    await withdrawFees();
+  });
+
+  after(async () => {
+    await program.methods.updateFees(0, 0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+  });
+
+  it('is admin only', async () => {
+    const computationOffset = newComputationOffset();
+    await expectError(
+      program.methods
+        .withdrawFees(computationOffset)
+        .accountsPartial({
+          payer: outsider.publicKey,
+          authority: outsider.publicKey,
+          destination: outsider.publicKey,
+          ...arciumAccounts('withdraw_fees', computationOffset),
+        })
+        .signers([outsider])
+        .rpc(),
+      'Unauthorized'
+    );
+  });
+
+  it('pays the fees accrued over two matches to the destination', async () => {
+    // 1% taker fee on a notional of 100 * 10 per fill
+    await fillOnce(201);
+    await fillOnce(203);
+
+    const destinationBefore = await balance(destination.publicKey);
+    const vaultBefore = await balance(feeVaultPda);
+    await withdrawFees();
+
+    expect((await balance(destination.publicKey)) - destinationBefore).to.equal(20);
+    expect(vaultBefore - (await balance(feeVaultPda))).to.equal(20);
+  });
+
+  it('pays nothing once the fees are withdrawn', async () => {
+    const destinationBefore = await balance(destination.publicKey);
+    await withdrawFees();
+    expect(await balance(destination.publicKey)).to.equal(destinationBefore);
+  });
+
+  it('charges updated fees from the next match on', async () => {
+    await program.methods.updateFees(50, 200).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    // 0.5% maker plus 2% taker on a notional of 100 * 10
+    await fillOnce(205);
+
+    const destinationBefore = await balance(destination.publicKey);
+    await withdrawFees();
+    expect((await balance(destination.publicKey)) - destinationBefore).to.equal(25);
+  });
+});
@@ -0,0 +1,105 @@
+import * as anchor from '@coral-xyz/anchor';
+import { awaitComputationFinalization } from '@arcium-hq/client';
+import { expect } from 'chai';
+import {
+  arciumAccounts,
+  encrypt,
+  expectError,
+  newCipher,
+  newComputationOffset,
+  orderStatus,
+  orderbookPda,
+  outsider,
+  placeOrder,
+  program,
+  provider,
+  setupMarket,
+} from './helpers';
+
+async function heartbeat(userId: number): Promise<void> {
+  const { publicKey, cipher } = await newCipher();
+  const { ciphertexts, nonce } = encrypt(cipher, [BigInt(userId)]);
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .heartbeat(computationOffset, ciphertexts[0], publicKey, nonce)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('heartbeat', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+}
+
+async function cancelStaleUsers(): Promise<void> {
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .cancelStaleUsers(computationOffset)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('cancel_stale_users', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+}
+
+describe('set_heartbeat_ttl', () => {
+  before(setupMarket);
+
+  it('rejects a TTL below the minimum', async () => {
+    await expectError(
+      program.methods
+        .setHeartbeatTtl(new anchor.BN(29))
+        .accountsPartial({ authority: provider.wallet.publicKey })
+        .rpc(),
+      'InvalidHeartbeatTtl'
+    );
+  });
+
+  it('is admin only', async () => {
+    await expectError(
+      program.methods
+        .setHeartbeatTtl(new anchor.BN(60))
+        .accountsPartial({ authority: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      'Unauthorized'
+    );
+  });
+
+  it('stores the TTL for cancel_stale_users', async () => {
+    await program.methods
+      .setHeartbeatTtl(new anchor.BN(60))
+      .accountsPartial({ authority: provider.wallet.publicKey })
+      .rpc({ commitment: 'confirmed' });
+
+    const book = await program.account.orderBookState.fetch(orderbookPda);
+    expect(book.heartbeatTtl.toNumber()).to.equal(60);
+  });
+});
+
+describe('cancel_stale_users', function () {
+  // Waits out the minimum TTL
+  this.timeout(180_000);
+
+  before(async () => {
+    await setupMarket();
+    await program.methods
+      .setHeartbeatTtl(new anchor.BN(30))
+      .accountsPartial({ authority: provider.wallet.publicKey })
+      .rpc({ commitment: 'confirmed' });
+  });
+
+  it('sweeps the orders of a user past the TTL and keeps a live user\'s', async () => {
+    const staleOrder = await placeOrder({ price: 50, amount: 10, side: 0, userId: 501 });
+    await heartbeat(501);
+
+    await new Promise((resolve) => setTimeout(resolve, 35_000));
+
+    const liveOrder = await placeOrder({ price: 51, amount: 10, side: 0, userId: 502 });
+    await heartbeat(502);
+    await cancelStaleUsers();
+
+    expect((await orderStatus(staleOrder, 501)).active).to.equal(false);
+    expect((await orderStatus(liveOrder, 502)).active).to.equal(true);
+  });
+});
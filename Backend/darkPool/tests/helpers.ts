@@ -0,0 +1,359 @@
+import * as anchor from '@coral-xyz/anchor';
+import { Program } from '@coral-xyz/anchor';
+import { PublicKey } from '@solana/web3.js';
+import { randomBytes } from 'crypto';
+import { expect } from 'chai';
+import {
+  RescueCipher,
+  awaitComputationFinalization,
+  buildFinalizeCompDefTx,
+  deserializeLE,
+  getArciumAccountBaseSeed,
+  getArciumEnv,
+  getArciumProgramId,
+  getClusterAccAddress,
+  getCompDefAccOffset,
+  getComputationAccAddress,
+  getExecutingPoolAccAddress,
+  getMempoolAccAddress,
+  getMXEAccAddress,
+  getMXEPublicKey,
+  x25519,
+} from '@arcium-hq/client';
+import { Darkpool } from '../target/types/darkpool';
+
+anchor.setProvider(anchor.AnchorProvider.env());
+
+export const provider = anchor.getProvider() as anchor.AnchorProvider;
+export const program = anchor.workspace.Darkpool as Program<Darkpool>;
+export const arciumEnv = getArciumEnv();
+
+// Signs as the gate's compliance authority; the provider wallet is the book authority
+export const complianceAuthority = anchor.web3.Keypair.generate();
+
+// Signs admin and compliance instructions it has no rights to
+export const outsider = anchor.web3.Keypair.generate();
+
+// Stand-in for encrypted inputs; every guard under test rejects before the
+// ciphertexts are used
+export const blank = Array(32).fill(0);
+
+export const orderbookPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('orderbook')],
+  program.programId
+)[0];
+
+export const feeVaultPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('fee_vault')],
+  program.programId
+)[0];
+
+export function newComputationOffset(): anchor.BN {
+  return new anchor.BN(randomBytes(8), 'hex');
+}
+
+function compDefAddress(circuit: string): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [
+      getArciumAccountBaseSeed('ComputationDefinitionAccount'),
+      program.programId.toBuffer(),
+      getCompDefAccOffset(circuit),
+    ],
+    getArciumProgramId()
+  )[0];
+}
+
+// Arcium accounts for queueing `circuit` at `computationOffset`
+export function arciumAccounts(circuit: string, computationOffset: anchor.BN) {
+  return {
+    computationAccount: getComputationAccAddress(arciumEnv.arciumClusterOffset, computationOffset),
+    clusterAccount: getClusterAccAddress(arciumEnv.arciumClusterOffset),
+    mxeAccount: getMXEAccAddress(program.programId),
+    mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+    executingPool: getExecutingPoolAccAddress(arciumEnv.arciumClusterOffset),
+    compDefAccount: compDefAddress(circuit),
+  };
+}
+
+// Initialize and finalize a computation definition unless it already exists
+async function initCompDef(circuit: string): Promise<void> {
+  const compDefAccount = compDefAddress(circuit);
+  if (await provider.connection.getAccountInfo(compDefAccount)) return;
+
+  const method = `init${circuit.replace(/(^|_)(\w)/g, (_, __, c) => c.toUpperCase())}CompDef`;
+  await (program.methods as any)[method]()
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      compDefAccount,
+      mxeAccount: getMXEAccAddress(program.programId),
+    })
+    .rpc({ commitment: 'confirmed' });
+
+  const finalizeTx = await buildFinalizeCompDefTx(
+    provider,
+    Buffer.from(getCompDefAccOffset(circuit)).readUInt32LE(),
+    program.programId
+  );
+  const latest = await provider.connection.getLatestBlockhash();
+  finalizeTx.recentBlockhash = latest.blockhash;
+  finalizeTx.lastValidBlockHeight = latest.lastValidBlockHeight;
+  await provider.sendAndConfirm(finalizeTx);
+}
+
+const CIRCUITS = [
+  'init_orderbook',
+  'warm_book',
+  'add_order',
+  'match_orders',
+  'cancel_order',
+  'get_order_status',
+  'heartbeat',
+  'cancel_stale_users',
+  'match_orders_batch',
+  'crank_match',
+  'submit_ioc',
+  'match_market_sweep',
+  'modify_order',
+  'replay_match',
+  'withdraw_fees',
+];
+
+let ready: Promise<void> | undefined;
+
+// Create the book, oracle, trading gate and match snapshot once per test run
+export function setupMarket(): Promise<void> {
+  ready ??= (async () => {
+    for (const circuit of CIRCUITS) {
+      await initCompDef(circuit);
+    }
+
+    const computationOffset = newComputationOffset();
+    await program.methods
+      .initOrderbook(computationOffset, new anchor.BN(randomBytes(16), 'hex'))
+      .accountsPartial({
+        payer: provider.wallet.publicKey,
+        ...arciumAccounts('init_orderbook', computationOffset),
+      })
+      .rpc({ commitment: 'confirmed' });
+    await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+
+    await program.methods
+      .initOraclePrice(provider.wallet.publicKey, new anchor.BN(100))
+      .accountsPartial({ payer: provider.wallet.publicKey })
+      .rpc({ commitment: 'confirmed' });
+    await program.methods
+      .initTradingGate(complianceAuthority.publicKey)
+      .accountsPartial({ payer: provider.wallet.publicKey })
+      .rpc({ commitment: 'confirmed' });
+    await program.methods
+      .initMatchSnapshot()
+      .accountsPartial({ payer: provider.wallet.publicKey })
+      .rpc({ commitment: 'confirmed' });
+    await program.methods
+      .initFeeVault()
+      .accountsPartial({ payer: provider.wallet.publicKey })
+      .rpc({ commitment: 'confirmed' });
+    await program.methods
+      .fundFeeVault(new anchor.BN(1_000_000))
+      .accountsPartial({ funder: provider.wallet.publicKey })
+      .rpc({ commitment: 'confirmed' });
+  })();
+  return ready;
+}
+
+// add_order with blank ciphertexts, for checks that reject before the circuit runs
+export function addBlankOrder() {
+  const computationOffset = newComputationOffset();
+  return program.methods
+    .addOrder(
+      computationOffset,
+      null,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      blank,
+      new anchor.BN(0)
+    )
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('add_order', computationOffset),
+    })
+    .rpc();
+}
+
+// A fresh x25519 key and the Rescue cipher it shares with the MXE
+export async function newCipher() {
+  const privateKey = x25519.utils.randomSecretKey();
+  const publicKey = Array.from(x25519.getPublicKey(privateKey));
+  const mxePublicKey = await getMXEPublicKey(provider, program.programId);
+  const cipher = new RescueCipher(x25519.getSharedSecret(privateKey, mxePublicKey!));
+  return { publicKey, cipher };
+}
+
+// Encrypt `fields` under a fresh nonce, shaped as instruction arguments
+export function encrypt(cipher: RescueCipher, fields: bigint[]) {
+  const nonce = randomBytes(16);
+  const ciphertexts = cipher.encrypt(fields, nonce).map((c) => Array.from(c));
+  return { ciphertexts, nonce: new anchor.BN(deserializeLE(nonce).toString()) };
+}
+
+// Resolve with the first `name` event emitted for `computationOffset`
+async function awaitEvent(name: string, computationOffset: anchor.BN): Promise<any> {
+  let listener = 0;
+  const event = await new Promise<any>((resolve) => {
+    listener = program.addEventListener(name as any, (e: any) => {
+      if (e.computationOffset.eq(computationOffset)) resolve(e);
+    });
+  });
+  await program.removeEventListener(listener);
+  return event;
+}
+
+export interface OrderParams {
+  price: number;
+  amount: number;
+  side: 0 | 1;
+  userId: number;
+  orderId?: anchor.BN;
+}
+
+// add_order for a limit order encrypted to the MXE, waiting for the callback.
+// Returns the order's id
+export async function placeOrder({ price, amount, side, userId, orderId }: OrderParams): Promise<anchor.BN> {
+  const { publicKey, cipher } = await newCipher();
+  // price, amount, side, type, user_id, metadata, oco_group, flags, min_edge_bps, trade_group_id
+  const fields = [BigInt(price), BigInt(amount), BigInt(side), 1n, BigInt(userId), 0n, 0n, 0n, 0n, 0n];
+  const { ciphertexts: ct, nonce } = encrypt(cipher, fields);
+  const id = orderId ?? (await program.account.orderBookState.fetch(orderbookPda)).nextOrderId;
+
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .addOrder(
+      computationOffset,
+      orderId ?? null,
+      ct[0],
+      ct[1],
+      ct[2],
+      ct[3],
+      ct[4],
+      ct[5],
+      ct[6],
+      ct[7],
+      ct[8],
+      ct[9],
+      publicKey,
+      publicKey,
+      nonce
+    )
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('add_order', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+  return id;
+}
+
+export interface OrderStatus {
+  active: boolean;
+  price: number;
+  amount: number;
+}
+
+// get_order_status for `userId`'s order, decrypted
+export async function orderStatus(orderId: anchor.BN, userId: number): Promise<OrderStatus> {
+  const { publicKey, cipher } = await newCipher();
+  const { ciphertexts, nonce } = encrypt(cipher, [BigInt(userId)]);
+
+  const computationOffset = newComputationOffset();
+  const event = awaitEvent('orderStatusEvent', computationOffset);
+  await program.methods
+    .getOrderStatus(computationOffset, orderId, ciphertexts[0], publicKey, nonce)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('get_order_status', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+
+  const { status, nonce: outputNonce } = await event;
+  const [active, price, amount] = cipher.decrypt(status, Uint8Array.from(outputNonce));
+  return { active: active === 1n, price: Number(price), amount: Number(amount) };
+}
+
+// cancel_order for `userId`'s order, waiting for the callback
+export async function cancelOrder(orderId: anchor.BN, userId: number): Promise<void> {
+  const { publicKey, cipher } = await newCipher();
+  const { ciphertexts, nonce } = encrypt(cipher, [BigInt(userId)]);
+
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .cancelOrder(computationOffset, orderId, ciphertexts[0], publicKey, nonce)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('cancel_order', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+}
+
+// modify_order for `userId`'s order, waiting for the callback
+export async function modifyOrder(orderId: anchor.BN, userId: number, price: number, amount: number): Promise<void> {
+  const { publicKey, cipher } = await newCipher();
+  const { ciphertexts: ct, nonce } = encrypt(cipher, [BigInt(userId), BigInt(price), BigInt(amount)]);
+
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .modifyOrder(computationOffset, orderId, ct[0], ct[1], ct[2], publicKey, nonce)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('modify_order', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+}
+
+// match_orders as the provider wallet, waiting for the callback
+export async function runMatch(): Promise<void> {
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .matchOrders(computationOffset)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('match_orders', computationOffset),
+    })
+    .rpc({ commitment: 'confirmed' });
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+}
+
+// match_orders under the book's configured policies
+export function matchOrders() {
+  const computationOffset = newComputationOffset();
+  return program.methods
+    .matchOrders(computationOffset)
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('match_orders', computationOffset),
+    })
+    .rpc();
+}
+
+// Await `tx` and check it failed with the program error named `code`
+export async function expectError(tx: Promise<unknown>, code: string): Promise<void> {
+  let err: unknown;
+  try {
+    await tx;
+  } catch (e) {
+    err = e;
+  }
+  expect(err, `expected ${code}`).to.be.instanceOf(anchor.AnchorError);
+  expect((err as anchor.AnchorError).error.errorCode.code).to.equal(code);
+}
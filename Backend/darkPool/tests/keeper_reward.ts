@@ -0,0 +1,92 @@
+import * as anchor from '@coral-xyz/anchor';
+import { awaitComputationFinalization } from '@arcium-hq/client';
+import { expect } from 'chai';
+import {
+  arciumAccounts,
+  expectError,
+  newComputationOffset,
+  orderbookPda,
+  outsider,
+  placeOrder,
+  program,
+  provider,
+  setupMarket,
+} from './helpers';
+
+function asAuthority() {
+  return { authority: provider.wallet.publicKey };
+}
+
+// Signs match_orders as its payer; the provider wallet pays the transaction fee
+const keeper = anchor.web3.Keypair.generate();
+
+// Run match_orders as the keeper and return what the callback credited it.
+// The balance is read after the queueing transaction lands, so the
+// computation account rent it pays doesn't count
+async function matchAsKeeper(): Promise<number> {
+  const computationOffset = newComputationOffset();
+  await program.methods
+    .matchOrders(computationOffset)
+    .accountsPartial({
+      payer: keeper.publicKey,
+      ...arciumAccounts('match_orders', computationOffset),
+    })
+    .signers([keeper])
+    .rpc({ commitment: 'confirmed' });
+  const before = await provider.connection.getBalance(keeper.publicKey, 'confirmed');
+  await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+  const after = await provider.connection.getBalance(keeper.publicKey, 'confirmed');
+  return after - before;
+}
+
+describe('keeper reward', () => {
+  before(async () => {
+    await setupMarket();
+    const sig = await provider.connection.requestAirdrop(keeper.publicKey, anchor.web3.LAMPORTS_PER_SOL);
+    await provider.connection.confirmTransaction(sig, 'confirmed');
+    await program.methods.updateFees(0, 100).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods
+      .setKeeperReward(new anchor.BN(5))
+      .accountsPartial(asAuthority())
+      .rpc({ commitment: 'confirmed' });
+  });
+
+  after(async () => {
+    await program.methods
+      .setKeeperReward(new anchor.BN(0))
+      .accountsPartial(asAuthority())
+      .rpc({ commitment: 'confirmed' });
+    await program.methods.updateFees(0, 0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+  });
+
+  it('is admin only', async () => {
+    await expectError(
+      program.methods
+        .setKeeperReward(new anchor.BN(5))
+        .accountsPartial({ authority: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      'Unauthorized'
+    );
+  });
+
+  it('pays the keeper out of the fee vault for a match that fills', async () => {
+    await placeOrder({ price: 100, amount: 10, side: 0, userId: 101 });
+    await placeOrder({ price: 100, amount: 10, side: 1, userId: 102 });
+    const paidBefore = (await program.account.orderBookState.fetch(orderbookPda)).keeperRewardsPaid;
+
+    expect(await matchAsKeeper()).to.equal(5);
+
+    const book = await program.account.orderBookState.fetch(orderbookPda);
+    expect(book.keeperRewardsPaid.sub(paidBefore).toNumber()).to.equal(5);
+  });
+
+  it('pays nothing for a match that fills nothing', async () => {
+    const paidBefore = (await program.account.orderBookState.fetch(orderbookPda)).keeperRewardsPaid;
+
+    expect(await matchAsKeeper()).to.equal(0);
+
+    const book = await program.account.orderBookState.fetch(orderbookPda);
+    expect(book.keeperRewardsPaid.eq(paidBefore)).to.equal(true);
+  });
+});
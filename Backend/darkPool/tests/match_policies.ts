@@ -0,0 +1,75 @@
+import * as anchor from '@coral-xyz/anchor';
+import { expect } from 'chai';
+import { expectError, orderbookPda, outsider, program, provider, setupMarket } from './helpers';
+
+function asAuthority() {
+  return { authority: provider.wallet.publicKey };
+}
+
+describe('matching policies', () => {
+  before(setupMarket);
+
+  after(async () => {
+    await program.methods.setRounding(0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setMatchMode(0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setFeeRounding(0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setPriorityPolicy(0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods
+      .setMaxTakePerMaker(new anchor.BN(0))
+      .accountsPartial(asAuthority())
+      .rpc({ commitment: 'confirmed' });
+    await program.methods.setSpreadPolicy(0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setPriceSourcePolicy(0).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+  });
+
+  it('are admin only', async () => {
+    await expectError(
+      program.methods
+        .setMatchMode(1)
+        .accountsPartial({ authority: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      'Unauthorized'
+    );
+  });
+
+  it('reject out-of-range values', async () => {
+    await expectError(program.methods.setRounding(3).accountsPartial(asAuthority()).rpc(), 'InvalidRounding');
+    await expectError(program.methods.setMatchMode(2).accountsPartial(asAuthority()).rpc(), 'InvalidMatchMode');
+    await expectError(program.methods.setFeeRounding(3).accountsPartial(asAuthority()).rpc(), 'InvalidRounding');
+    await expectError(
+      program.methods.setPriorityPolicy(2).accountsPartial(asAuthority()).rpc(),
+      'InvalidPriorityPolicy'
+    );
+    await expectError(
+      program.methods.setSpreadPolicy(3).accountsPartial(asAuthority()).rpc(),
+      'InvalidSpreadPolicy'
+    );
+    await expectError(
+      program.methods.setPriceSourcePolicy(3).accountsPartial(asAuthority()).rpc(),
+      'InvalidPriceSourcePolicy'
+    );
+  });
+
+  it('are stored on the book for every matching path', async () => {
+    await program.methods.setRounding(2).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setMatchMode(1).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setFeeRounding(1).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setPriorityPolicy(1).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods
+      .setMaxTakePerMaker(new anchor.BN(25))
+      .accountsPartial(asAuthority())
+      .rpc({ commitment: 'confirmed' });
+    await program.methods.setSpreadPolicy(2).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+    await program.methods.setPriceSourcePolicy(1).accountsPartial(asAuthority()).rpc({ commitment: 'confirmed' });
+
+    const book = await program.account.orderBookState.fetch(orderbookPda);
+    expect(book.rounding).to.equal(2);
+    expect(book.matchMode).to.equal(1);
+    expect(book.feeRounding).to.equal(1);
+    expect(book.priorityPolicy).to.equal(1);
+    expect(book.maxTakePerMaker.toNumber()).to.equal(25);
+    expect(book.spreadPolicy).to.equal(2);
+    expect(book.priceSourcePolicy).to.equal(1);
+  });
+});
@@ -0,0 +1,36 @@
+import * as anchor from '@coral-xyz/anchor';
+import { PublicKey } from '@solana/web3.js';
+import {
+  arciumAccounts,
+  blank,
+  expectError,
+  newComputationOffset,
+  program,
+  provider,
+  setupMarket,
+} from './helpers';
+
+const matchSnapshotPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('match_snapshot')],
+  program.programId
+)[0];
+
+function replay(matchOffset: anchor.BN) {
+  const computationOffset = newComputationOffset();
+  return program.methods
+    .replayMatch(computationOffset, matchOffset, Array(8).fill(blank), new anchor.BN(0))
+    .accountsPartial({
+      payer: provider.wallet.publicKey,
+      ...arciumAccounts('replay_match', computationOffset),
+    })
+    .rpc();
+}
+
+describe('replay_match', () => {
+  before(setupMarket);
+
+  it('rejects a match that is not the snapshotted one', async () => {
+    const snapshot = await program.account.matchSnapshot.fetch(matchSnapshotPda);
+    await expectError(replay(snapshot.computationOffset.addn(1)), 'ReplayStateMismatch');
+  });
+});
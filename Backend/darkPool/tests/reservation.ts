@@ -0,0 +1,58 @@
+import * as anchor from '@coral-xyz/anchor';
+import { expect } from 'chai';
+import {
+  expectError,
+  orderStatus,
+  orderbookPda,
+  placeOrder,
+  program,
+  provider,
+  setupMarket,
+} from './helpers';
+
+async function reserveOrderId(): Promise<anchor.BN> {
+  const { nextOrderId } = await program.account.orderBookState.fetch(orderbookPda);
+  await program.methods
+    .reserveOrderId()
+    .accountsPartial({ payer: provider.wallet.publicKey })
+    .rpc({ commitment: 'confirmed' });
+  return nextOrderId;
+}
+
+describe('reserve_order_id', () => {
+  before(setupMarket);
+
+  it('places an order under a reserved id', async () => {
+    const orderId = await reserveOrderId();
+
+    let book = await program.account.orderBookState.fetch(orderbookPda);
+    const entry = book.reservedIds.findIndex((id, i) => id.eq(orderId) && book.reservedBy[i].equals(provider.wallet.publicKey));
+    expect(entry).to.not.equal(-1);
+    expect(book.nextOrderId.eq(orderId.addn(1))).to.equal(true);
+
+    await placeOrder({ price: 20, amount: 7, side: 0, userId: 801, orderId });
+
+    // The order rests under the reserved id, the reservation is spent and no
+    // second id was allocated for it
+    expect(await orderStatus(orderId, 801)).to.deep.equal({ active: true, price: 20, amount: 7 });
+    book = await program.account.orderBookState.fetch(orderbookPda);
+    expect(book.reservedBy[entry].equals(anchor.web3.PublicKey.default)).to.equal(true);
+    expect(book.nextOrderId.eq(orderId.addn(1))).to.equal(true);
+  });
+
+  it('rejects a second order under the same id', async () => {
+    const orderId = await reserveOrderId();
+    await placeOrder({ price: 20, amount: 7, side: 0, userId: 802, orderId });
+    await expectError(placeOrder({ price: 21, amount: 7, side: 0, userId: 802, orderId }), 'OrderIdNotReserved');
+  });
+
+  it('keeps ids allocated without a reservation distinct from reserved ones', async () => {
+    const reserved = await reserveOrderId();
+    const unreserved = await placeOrder({ price: 22, amount: 5, side: 0, userId: 803 });
+    await placeOrder({ price: 23, amount: 5, side: 0, userId: 804, orderId: reserved });
+
+    expect(unreserved.eq(reserved)).to.equal(false);
+    expect((await orderStatus(unreserved, 803)).price).to.equal(22);
+    expect((await orderStatus(reserved, 804)).price).to.equal(23);
+  });
+});
@@ -0,0 +1,77 @@
+import * as anchor from '@coral-xyz/anchor';
+import { expect } from 'chai';
+import {
+  addBlankOrder,
+  expectError,
+  matchOrders,
+  orderStatus,
+  outsider,
+  placeOrder,
+  program,
+  provider,
+  runMatch,
+  setupMarket,
+} from './helpers';
+
+function setSession(open: number, close: number) {
+  return program.methods
+    .setSession(new anchor.BN(open), new anchor.BN(close))
+    .accountsPartial({ authority: provider.wallet.publicKey })
+    .rpc({ commitment: 'confirmed' });
+}
+
+describe('trading session window', () => {
+  before(setupMarket);
+
+  after(() => setSession(0, 0));
+
+  it('rejects a window that closes before it opens', async () => {
+    await expectError(setSession(200, 100), 'InvalidSession');
+  });
+
+  it('is admin only', async () => {
+    await expectError(
+      program.methods
+        .setSession(new anchor.BN(1), new anchor.BN(2))
+        .accountsPartial({ authority: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      'Unauthorized'
+    );
+  });
+
+  describe('outside the window', () => {
+    let bid: anchor.BN;
+    let ask: anchor.BN;
+
+    // A crossing pair rests on the book, then the window closes (it ended at
+    // the start of the epoch)
+    before(async () => {
+      bid = await placeOrder({ price: 100, amount: 10, side: 0, userId: 601 });
+      ask = await placeOrder({ price: 100, amount: 10, side: 1, userId: 602 });
+      await setSession(1, 2);
+    });
+
+    it('rejects add_order', async () => {
+      await expectError(addBlankOrder(), 'MarketClosed');
+    });
+
+    it('rejects match_orders and leaves the book untouched', async () => {
+      await expectError(matchOrders(), 'MarketClosed');
+      expect((await orderStatus(bid, 601)).active).to.equal(true);
+      expect((await orderStatus(ask, 602)).active).to.equal(true);
+    });
+
+    it('accepts adds and resumes matching once the window opens', async () => {
+      const now = Math.floor(Date.now() / 1000);
+      await setSession(now - 3600, now + 3600);
+
+      const late = await placeOrder({ price: 40, amount: 10, side: 0, userId: 603 });
+      await runMatch();
+
+      expect((await orderStatus(bid, 601)).active).to.equal(false);
+      expect((await orderStatus(ask, 602)).active).to.equal(false);
+      expect((await orderStatus(late, 603)).active).to.equal(true);
+    });
+  });
+});
@@ -0,0 +1,40 @@
+import { expect } from 'chai';
+import { orderbookPda, placeOrder, program, provider, runMatch, setupMarket } from './helpers';
+
+// The BookStalenessEvent get_book_staleness emits, read from a simulation
+async function staleness(): Promise<{ lastMatchSlot: number; stalenessSlots: number }> {
+  const { events } = await program.methods.getBookStaleness().accountsPartial({ orderbook: orderbookPda }).simulate();
+  const event = events.find((e) => e.name === 'bookStalenessEvent')!.data as any;
+  return { lastMatchSlot: event.lastMatchSlot.toNumber(), stalenessSlots: event.stalenessSlots.toNumber() };
+}
+
+async function lastMatchSlot(): Promise<number> {
+  return (await program.account.orderBookState.fetch(orderbookPda)).lastMatchSlot.toNumber();
+}
+
+describe('get_book_staleness', () => {
+  before(setupMarket);
+
+  it('reports the slot of the last match that filled', async () => {
+    await placeOrder({ price: 100, amount: 10, side: 0, userId: 901 });
+    await placeOrder({ price: 100, amount: 10, side: 1, userId: 902 });
+    const before = await provider.connection.getSlot('confirmed');
+    await runMatch();
+
+    const matchedAt = await lastMatchSlot();
+    expect(matchedAt).to.be.greaterThan(before);
+    const now = await provider.connection.getSlot('confirmed');
+    const report = await staleness();
+    expect(report.lastMatchSlot).to.equal(matchedAt);
+    expect(report.stalenessSlots).to.be.at.least(now - matchedAt);
+  });
+
+  it('keeps growing through a match that fills nothing', async () => {
+    const matchedAt = await lastMatchSlot();
+    const { stalenessSlots } = await staleness();
+    await runMatch();
+
+    expect(await lastMatchSlot()).to.equal(matchedAt);
+    expect((await staleness()).stalenessSlots).to.be.greaterThan(stalenessSlots);
+  });
+});
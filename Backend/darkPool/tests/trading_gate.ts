@@ -0,0 +1,154 @@
+import * as anchor from '@coral-xyz/anchor';
+import { expect } from 'chai';
+import {
+  arciumAccounts,
+  blank,
+  cancelOrder,
+  complianceAuthority,
+  expectError,
+  matchOrders,
+  newComputationOffset,
+  orderStatus,
+  outsider,
+  placeOrder,
+  program,
+  provider,
+  runMatch,
+  setupMarket,
+} from './helpers';
+
+function setTradingEnabled(enabled: boolean) {
+  return program.methods
+    .setTradingEnabled(enabled)
+    .accountsPartial({ complianceAuthority: complianceAuthority.publicKey })
+    .signers([complianceAuthority])
+    .rpc({ commitment: 'confirmed' });
+}
+
+describe('trading gate', () => {
+  before(setupMarket);
+
+  it('only the compliance authority can switch it', async () => {
+    await expectError(
+      program.methods
+        .setTradingEnabled(false)
+        .accountsPartial({ complianceAuthority: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      'Unauthorized'
+    );
+    // The book authority has no say over the gate either
+    await expectError(
+      program.methods
+        .setTradingEnabled(false)
+        .accountsPartial({ complianceAuthority: provider.wallet.publicKey })
+        .rpc(),
+      'Unauthorized'
+    );
+  });
+
+  let bid: anchor.BN;
+  let ask: anchor.BN;
+
+  describe('while disabled', () => {
+    let resting: anchor.BN;
+
+    // A crossing pair and an unrelated order rest on the book before the gate closes
+    before(async () => {
+      bid = await placeOrder({ price: 100, amount: 10, side: 0, userId: 701 });
+      ask = await placeOrder({ price: 100, amount: 10, side: 1, userId: 702 });
+      resting = await placeOrder({ price: 30, amount: 10, side: 0, userId: 703 });
+      await setTradingEnabled(false);
+    });
+    after(() => setTradingEnabled(true));
+
+    it('blocks match_orders and leaves the crossing pair resting', async () => {
+      await expectError(matchOrders(), 'TradingDisabled');
+      expect((await orderStatus(bid, 701)).active).to.equal(true);
+      expect((await orderStatus(ask, 702)).active).to.equal(true);
+    });
+
+    it('still allows cancels', async () => {
+      await cancelOrder(resting, 703);
+      expect((await orderStatus(resting, 703)).active).to.equal(false);
+    });
+
+    it('blocks match_orders_batch', async () => {
+      const computationOffset = newComputationOffset();
+      await expectError(
+        program.methods
+          .matchOrdersBatch(computationOffset, new anchor.BN(4))
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            ...arciumAccounts('match_orders_batch', computationOffset),
+          })
+          .rpc(),
+        'TradingDisabled'
+      );
+    });
+
+    it('blocks crank_match', async () => {
+      const computationOffset = newComputationOffset();
+      await expectError(
+        program.methods
+          .crankMatch(computationOffset)
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            ...arciumAccounts('crank_match', computationOffset),
+          })
+          .rpc(),
+        'TradingDisabled'
+      );
+    });
+
+    it('blocks submit_ioc', async () => {
+      const computationOffset = newComputationOffset();
+      await expectError(
+        program.methods
+          .submitIoc(
+            computationOffset,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            blank,
+            new anchor.BN(0)
+          )
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            ...arciumAccounts('submit_ioc', computationOffset),
+          })
+          .rpc(),
+        'TradingDisabled'
+      );
+    });
+
+    it('blocks match_market_sweep', async () => {
+      const computationOffset = newComputationOffset();
+      await expectError(
+        program.methods
+          .matchMarketSweep(computationOffset, blank, new anchor.BN(0))
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            ...arciumAccounts('match_market_sweep', computationOffset),
+          })
+          .rpc(),
+        'TradingDisabled'
+      );
+    });
+  });
+
+  describe('once re-enabled', () => {
+    it('resumes matching', async () => {
+      await runMatch();
+      expect((await orderStatus(bid, 701)).active).to.equal(false);
+      expect((await orderStatus(ask, 702)).active).to.equal(false);
+    });
+  });
+});
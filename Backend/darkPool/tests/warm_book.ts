@@ -0,0 +1,35 @@
+import { awaitComputationFinalization } from '@arcium-hq/client';
+import { expect } from 'chai';
+import { arciumAccounts, newComputationOffset, program, provider, runMatch, setupMarket } from './helpers';
+
+// Wall-clock time from queueing `run` to its callback landing
+async function timed(run: () => Promise<void>): Promise<number> {
+  const start = Date.now();
+  await run();
+  return Date.now() - start;
+}
+
+describe('warm_book', function () {
+  this.timeout(300_000);
+
+  before(setupMarket);
+
+  it('gives the first match after warming the latency of a later one', async () => {
+    const computationOffset = newComputationOffset();
+    await program.methods
+      .warmBook(computationOffset)
+      .accountsPartial({
+        payer: provider.wallet.publicKey,
+        ...arciumAccounts('warm_book', computationOffset),
+      })
+      .rpc({ commitment: 'confirmed' });
+    await awaitComputationFinalization(provider, computationOffset, program.programId, 'confirmed');
+
+    const first = await timed(runMatch);
+    const second = await timed(runMatch);
+    console.log(`      match latency after warm_book: first ${first} ms, second ${second} ms`);
+
+    // Same bounds: at most half again as slow, with slack for a busy validator
+    expect(first).to.be.at.most(second * 1.5 + 2_000);
+  });
+});